@@ -0,0 +1,77 @@
+use std::env;
+use bevy::prelude::*;
+
+use rr_g::modules::{matchup, rng, rules, simulation};
+
+//safety net for matches stuck e.g. in MovingToStartPosition, roughly ten seconds of game time per turn
+const MAX_UPDATES_PER_TURN: u32 = 600;
+//overtime is sudden death, it could go on forever when nobody scores
+const MAX_OVERTIME_TURNS: u32 = 20;
+
+fn parse_arg(args: &Vec<String>, index: usize, default: u32) -> u32 {
+    args.get(index)
+        .map(|arg| arg.parse::<u32>().expect(format!("Argument '{}' is not a number", arg).as_str()))
+        .unwrap_or(default)
+}
+
+fn run_match(settings: &simulation::SimulationSettings, ruleset: &rules::Ruleset, seed: u64) -> (u8, u8, bool) {
+    let mut builder = App::build();
+    builder
+        .insert_resource(simulation::SimulationSettings {
+            home_formation: settings.home_formation.clone(),
+            away_formation: settings.away_formation.clone(),
+            home_roster: settings.home_roster.clone(),
+            away_roster: settings.away_roster.clone(),
+            arena: settings.arena.clone(),
+        })
+        .insert_resource(ruleset.clone())
+        .insert_resource(rng::GameSeed(seed))
+        .add_plugins(MinimalPlugins)
+        .add_plugin(simulation::SimulationPlugin);
+    let mut app = std::mem::take(&mut builder.app);
+
+    let max_updates = (ruleset.halves * ruleset.turns_per_half + MAX_OVERTIME_TURNS) * MAX_UPDATES_PER_TURN;
+    let mut updates = 0;
+    loop {
+        app.update();
+        updates += 1;
+        let finished = app.world.get_resource::<simulation::SimulationProgress>().map_or(false, |p| p.finished);
+        if finished || updates >= max_updates {
+            let matchup = app.world.get_resource::<matchup::Matchup>().expect("Matchup was not created!");
+            return (matchup.score_home, matchup.score_away, finished);
+        }
+    }
+}
+
+//usage: simulate [matches] [ruleset] [seed] [home formation] [away formation] [home roster] [away roster] [arena], match n is played with seed + n so any of them can be replayed
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let matches = parse_arg(&args, 1, 1);
+    let default_settings = simulation::SimulationSettings::default();
+    let settings = simulation::SimulationSettings {
+        home_formation: args.get(4).cloned().unwrap_or(default_settings.home_formation),
+        away_formation: args.get(5).cloned().unwrap_or(default_settings.away_formation),
+        home_roster: args.get(6).cloned().unwrap_or(default_settings.home_roster),
+        away_roster: args.get(7).cloned().unwrap_or(default_settings.away_roster),
+        arena: args.get(8).cloned().unwrap_or(default_settings.arena),
+    };
+    let ruleset = rules::Ruleset::preset(args.get(2).map_or(rules::DEFAULT_RULESET, |arg| arg.as_str()));
+    let seed = args.get(3)
+        .map(|arg| arg.parse::<u64>().expect(format!("Seed '{}' is not a number", arg).as_str()))
+        .unwrap_or_else(rand::random);
+
+    let (mut wins_home, mut wins_away, mut draws) = (0, 0, 0);
+    for i in 0..matches {
+        let match_seed = seed.wrapping_add(i as u64);
+        let (score_home, score_away, finished) = run_match(&settings, &ruleset, match_seed);
+        println!("Match {} (seed {}): Home {} - {} Away{}", i + 1, match_seed, score_home, score_away, if finished { "" } else { " (aborted)" });
+        if score_home > score_away {
+            wins_home += 1;
+        } else if score_away > score_home {
+            wins_away += 1;
+        } else {
+            draws += 1;
+        }
+    }
+    println!("Home wins: {}, Away wins: {}, draws: {}", wins_home, wins_away, draws);
+}
@@ -0,0 +1,31 @@
+use bevy::prelude::*;
+
+pub mod modules;
+use modules::{actor, ai, animation, ball, clock, collision, injury, matchup, physics, referee, rng, round, rules, stamina, states, zone};
+
+//game rules and simulation without any presentation, front-ends add ui, input and helpers on top
+//and are responsible for spawning the arena and actors
+pub struct RrGamePlugin;
+
+impl Plugin for RrGamePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .add_state(states::AppState::Introduction)
+            .init_resource::<states::PlanningHold>()
+            .add_plugin(rng::RngPlugin)
+            .add_plugin(clock::ClockPlugin)
+            .add_plugin(physics::PhysicsPlugin)
+            .add_plugin(collision::CollisionPlugin)
+            .add_plugin(zone::ZonePlugin)
+            .add_plugin(rules::RulesPlugin)
+            .add_plugin(round::RoundPlugin)
+            .add_plugin(actor::ActorPlugin)
+            .add_plugin(stamina::StaminaPlugin)
+            .add_plugin(injury::InjuryPlugin)
+            .add_plugin(referee::RefereePlugin)
+            .add_plugin(ball::BallPlugin)
+            .add_plugin(matchup::MatchupPlugin)
+            .add_plugin(ai::AiPlugin)
+            .add_plugin(animation::AnimationPlugin);
+    }
+}
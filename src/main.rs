@@ -1,12 +1,8 @@
+use std::env;
 use bevy::prelude::*;
-use bevy_rapier2d::{
-    physics::{RapierConfiguration, RapierPhysicsPlugin},
-    rapier::{math::Vector},
-    // render::RapierRenderPlugin,
-};
 
-mod modules;
-use modules::{actor, ai, animation, arena, ball, collision, helpers, input, matchup, physics, round, states, team, ui, utils};
+use rr_g::RrGamePlugin;
+use rr_g::modules::{actor, arena, ball, camera, formation, helpers, hotseat, input, matchup, network, preview, replay, rng, roster, rules, savegame, team, ui, utils};
 
 
 fn setup(
@@ -14,199 +10,169 @@ fn setup(
     asset_server: Res<AssetServer>,
     mut texture_atlases: ResMut<Assets<TextureAtlas>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
-    mut configuration: ResMut<RapierConfiguration>,
 ) {
-    configuration.gravity = Vector::y() * 0.0;
-    // configuration.time_dependent_number_of_timesteps = true;
-
     helpers::setup_helper_materials(&mut commands, &asset_server, &mut materials);
     actor::setup_actor_sprites(&mut commands, &asset_server, &mut texture_atlases);
     ui::setup_ui_materials(&mut commands, &asset_server, &mut materials);
     ball::setup_ball_material(&mut commands, &asset_server, &mut texture_atlases);
     arena::setup_arena_materials(&mut commands, &mut materials);
-    commands.insert_resource(actor::CurrentControlMode(actor::ControlMode::Run));
-    commands.insert_resource(ball::BallPossession::new());
-    commands.insert_resource(matchup::Matchup::new(Vec2::new(100.0, 0.0), Vec2::new(100.0, 0.0)));
 }
 
 fn initialize_game(
     mut commands: Commands,
     arena_materials: Res<arena::ArenaMaterials>,
+    arena_layout: Res<arena::ArenaLayout>,
+    mut meshes: ResMut<Assets<Mesh>>,
     actor_sprites: Res<actor::ActorTextures>,
     mut matchup_res: ResMut<matchup::Matchup>,
+    replay_playback: Option<Res<replay::ReplayPlayback>>,
+    pending_load: Option<Res<savegame::PendingLoad>>,
+    formations: Res<formation::SelectedFormations>,
+    rosters: Res<roster::SelectedRosters>,
+    hotseat: Option<Res<hotseat::Hotseat>>,
+    network_session: Option<Res<network::NetworkSession>>,
+    ruleset: Res<rules::Ruleset>,
 ) {
-    commands.spawn_bundle(OrthographicCameraBundle::new_2d());
+    camera::spawn_game_camera(&mut commands);
     commands.spawn_bundle(UiCameraBundle::default());
-    let player_team = team::Team::Home;
+    let player_team = network_session.as_ref().map_or(team::Team::Home, |session| session.team);
+    let player_teams = if hotseat.is_some() { vec![team::Team::Home, team::Team::Away] } else { vec![player_team] };
 
-    let actors: Vec<(Entity, Vec2, team::Team)> = vec![
-        (Vec2::new(-50.0, 100.0), Vec2::new(-150.0, 0.0),  team::Team::Home),
-        (Vec2::new(-85.0, 100.0), Vec2::new(-150.0, 50.0),  team::Team::Home),
-        (Vec2::new(50.0, 100.0), Vec2::new(150.0, 0.0),  team::Team::Away),
-        (Vec2::new(80.0, 100.0), Vec2::new(150.0, 50.0),  team::Team::Away),
-    ].iter().map(|(initial_position, target_position, team)| -> (Entity, Vec2, team::Team) {
-        (
-            actor::spawn_actor(&mut commands, &actor_sprites, *initial_position, *team, *team == player_team),
-            target_position.clone(),
-            team.clone()
-        )
-    }).collect();
+    let (actors, (bench_home, bench_away)) = match (replay_playback, pending_load) {
+        (Some(playback), _) => (
+            replay::spawn_replay_actors(&mut commands, Some(&*actor_sprites), &playback.replay),
+            (playback.replay.bench_home.clone(), playback.replay.bench_away.clone()),
+        ),
+        (None, Some(pending_load)) => (
+            matchup::spawn_actors(&mut commands, Some(&*actor_sprites), &pending_load.0.get_lineup(), &player_teams),
+            (pending_load.0.bench_home.clone(), pending_load.0.bench_away.clone()),
+        ),
+        (None, None) => {
+            let lineup = formations.get_lineup();
+            (
+                matchup::spawn_actors(&mut commands, Some(&*actor_sprites), &rosters.assign(lineup.clone()), &player_teams),
+                rosters.get_bench(&lineup),
+            )
+        },
+    };
 
+    if network_session.is_some() {
+        network::release_remote_actors(&mut commands, &actors, player_team);
+    }
     matchup_res.add_actors(actors);
-    arena::create_simple(&mut commands, &arena_materials, utils::WIN_W, utils::WIN_H - ui::UI_SIZE, 0.0, ui::UI_SIZE, player_team);
+    matchup_res.bench_home = bench_home;
+    matchup_res.bench_away = bench_away;
+    arena::create_from_layout(&mut commands, Some(&*arena_materials), Some(&mut *meshes), &arena_layout, Some(player_team), ruleset.points.has_touchdowns());
+}
+
+fn get_arg_value(args: &Vec<String>, name: &str) -> Option<String> {
+    args.iter()
+        .position(|arg| arg == name)
+        .and_then(|i| args.get(i + 1))
+        .cloned()
 }
 
 fn main() {
-    App::build()
+    let args: Vec<String> = env::args().collect();
+    let mut app = App::build();
+    if let Some(seed) = get_arg_value(&args, "--seed") {
+        app.insert_resource(rng::GameSeed(seed.parse().expect("Seed has to be a number")));
+    }
+    let home_formation = get_arg_value(&args, "--home-formation").unwrap_or(formation::DEFAULT_FORMATION.to_string());
+    let away_formation = get_arg_value(&args, "--away-formation").unwrap_or(formation::DEFAULT_FORMATION.to_string());
+    let home_roster = get_arg_value(&args, "--home-roster").unwrap_or(roster::DEFAULT_ROSTER.to_string());
+    let away_roster = get_arg_value(&args, "--away-roster").unwrap_or(roster::DEFAULT_ROSTER.to_string());
+    let arena_name = get_arg_value(&args, "--arena").unwrap_or(arena::DEFAULT_ARENA.to_string());
+    let mut arena_layout = arena::ArenaLayout::load(&arena_name);
+    let mut ruleset = rules::Ruleset::preset(&get_arg_value(&args, "--rules").unwrap_or(rules::DEFAULT_RULESET.to_string()));
+    if args.iter().any(|arg| arg == "--injuries") {
+        ruleset.injuries = true;
+    }
+    if let Some(address) = get_arg_value(&args, "--host") {
+        let seed = get_arg_value(&args, "--seed").map_or_else(rand::random, |seed| seed.parse().expect("Seed has to be a number"));
+        network::start_host(&address, network::MatchSettings {
+            seed,
+            home_formation: home_formation.clone(),
+            away_formation: away_formation.clone(),
+            home_roster: home_roster.clone(),
+            away_roster: away_roster.clone(),
+            arena: arena_name.clone(),
+            ruleset: ruleset.clone(),
+        });
+    }
+    let network_address = get_arg_value(&args, "--connect").or(get_arg_value(&args, "--host"));
+    if let Some(address) = network_address {
+        let (client, team, settings) = match network::connect(&address) {
+            Ok(connection) => connection,
+            Err(e) => {
+                println!("Cannot join network match: {}", e);
+                return;
+            }
+        };
+        arena_layout = arena::ArenaLayout::load(&settings.arena);
+        app
+            .insert_resource(rng::GameSeed(settings.seed))
+            .insert_resource(formation::SelectedFormations::load(&settings.home_formation, &settings.away_formation))
+            .insert_resource(roster::SelectedRosters::load(&settings.home_roster, &settings.away_roster))
+            .insert_resource(settings.ruleset)
+            .insert_resource(network::NetworkSession::new(team))
+            .insert_resource(client)
+            .add_plugin(network::NetworkPlugin);
+    } else {
+        app
+            .insert_resource(formation::SelectedFormations::load(&home_formation, &away_formation))
+            .insert_resource(roster::SelectedRosters::load(&home_roster, &away_roster))
+            .insert_resource(ruleset);
+    }
+    if let Some(path) = get_arg_value(&args, "--load") {
+        let save_game = savegame::SaveGame::load(&path);
+        arena_layout = save_game.arena.clone();
+        app
+            .insert_resource(rng::GameSeed(save_game.seed))
+            .insert_resource(save_game.ruleset.clone())
+            .insert_resource(savegame::PendingLoad(save_game))
+            .insert_resource(savegame::SaveGameSettings { path });
+    }
+    let replay_path = get_arg_value(&args, "--replay");
+    if let Some(path) = replay_path.as_ref() {
+        let replay = replay::Replay::load(path);
+        arena_layout = replay.arena.clone();
+        app
+            .insert_resource(rng::GameSeed(replay.seed))
+            .insert_resource(replay.ruleset.clone())
+            .insert_resource(replay::ReplayPlayback::new(replay))
+            .add_plugin(replay::ReplayPlaybackPlugin);
+    } else {
+        app
+            .add_plugin(input::InputPlugin)
+            .add_plugin(preview::PreviewPlugin);
+    }
+    if args.iter().any(|arg| arg == "--hotseat") {
+        app.add_plugin(hotseat::HotseatPlugin);
+    }
+    if let Some(path) = get_arg_value(&args, "--record") {
+        app
+            .insert_resource(replay::ReplayRecorder::new(path, arena_layout.clone()))
+            .add_plugin(replay::ReplayRecordPlugin);
+    }
+    app
+        .insert_resource(arena_layout)
         .insert_resource(WindowDescriptor {
             title: "Lobda".to_string(),
             width: utils::WIN_W,
             height: utils::WIN_H,
             vsync: true,
-            resizable: false,
+            resizable: true,
             ..Default::default()
         })
-        .add_state(states::AppState::Introduction)
         .insert_resource(ClearColor(Color::rgb(0.1, 0.1, 0.1)))
         .add_plugins(DefaultPlugins)
-        .add_plugin(RapierPhysicsPlugin)
-        // .add_plugin(RapierRenderPlugin)
-        .add_event::<collision::RRCollisionEvent>()
-        .add_event::<ball::BallEvent>()
-        .add_event::<actor::ActorEvents>()
-        .add_event::<matchup::MatchupEvents>()
-        .add_event::<ui::ButtonEvent>()
+        .add_plugin(RrGamePlugin)
+        .add_plugin(ui::UiPlugin)
+        .add_plugin(camera::CameraPlugin)
+        .add_plugin(helpers::HelpersPlugin)
+        .add_plugin(savegame::SaveGamePlugin)
         .add_startup_system(setup.system())
         .add_startup_stage("game_initialization", SystemStage::single(initialize_game.system()))
-        .add_system_set(ui::ui_changes_listeners())
-        .add_system(animation::animate_sprite.system())
-        .add_system(ui::button_state_changed.system())
-        .add_system(ui::button_interactions.system())
-        .add_system(ui::handle_button_events.system())
-        .add_system_set(
-            SystemSet::on_enter(states::AppState::Introduction)
-                .with_system(ui::spawn_score_text.system())
-                .with_system(ui::add_pre_game_text.system())
-                .with_system(ui::spawn_debug_ui.system())
-                .with_system(helpers::spawn_selected_helper.system())
-                .with_system(ui::spawn_buttons.system()
-                    .label("spawn_buttons")
-                )
-        )
-        .add_system_set(
-            SystemSet::on_update(states::AppState::Introduction)
-                .with_system(input::handle_keyboard_input_pre_round.system())
-        )
-        .add_system_set(
-            SystemSet::on_exit(states::AppState::Introduction)
-                .with_system(ui::clear_game_text.system())
-        )
-        .add_system_set(
-            SystemSet::on_enter(states::AppState::Scored)
-                .with_system(ui::add_score_text.system())
-                .with_system(physics::pause_physics.system())
-        )
-        .add_system_set(
-            SystemSet::on_update(states::AppState::Scored)
-                .with_system(input::handle_keyboard_input_pre_round.system())
-        )
-        .add_system_set(
-            SystemSet::on_exit(states::AppState::Scored)
-                .with_system(ui::clear_game_text.system())
-        )
-        .add_system_set(
-            SystemSet::on_enter(states::AppState::MovingToStartPosition)
-                .with_system(physics::resume_physics.system())
-                .with_system(matchup::move_actors_to_positions.system())
-        )
-        .add_system_set(
-            SystemSet::on_update(states::AppState::MovingToStartPosition)
-                .with_system(actor::handle_actors_refresh_action.system()
-                    .label("handle_actors_refresh_action_start_position")
-                )
-                .with_system(actor::handle_actor_action_start.system()
-                    .label("handle_actor_action_start_start_position")
-                    .after("handle_actors_refresh_action_start_position")
-                )
-                .with_system(matchup::are_actors_in_position.system()
-                    .after("handle_actor_action_start_start_position")
-                )
-        )
-        .add_system_set(
-            SystemSet::on_exit(states::AppState::MovingToStartPosition)
-                .with_system(matchup::update_actors_facing.system())
-                .with_system(ball::add_ball_to_arena.system())
-        )
-        .add_system_set(
-            SystemSet::on_enter(states::AppState::Plan)
-                .with_system(helpers::cleanup_movement_helpers.system())
-                .with_system(physics::pause_physics.system())
-                .with_system(ui::enable_buttons.system())
-                .with_system(ai::process_ai.system())
-        )
-        .add_system_set(
-            SystemSet::on_update(states::AppState::Plan)
-                .with_system(input::handle_mouse_click.system())
-                .with_system(input::handle_keyboard_input.system())
-                .with_system(helpers::update_selected_helper.system())
-        )
-        .add_system_set(
-            SystemSet::on_exit(states::AppState::Plan)
-                .with_system(helpers::deselect_all.system())
-                .with_system(ui::disable_buttons.system())
-        )
-        .add_system_set(
-            SystemSet::on_enter(states::AppState::Play)
-                .with_system(round::start_timer.system())
-                .with_system(actor::reset_control_mode.system())
-                .with_system(physics::resume_physics.system())
-        )
-        .add_system_set(
-            SystemSet::on_update(states::AppState::Play)
-                .with_system(round::update_timer.system())
-                .with_system(collision::get_contact_events.system()
-                    .label("get_contact_events")
-                )
-                .with_system(collision::handle_collision_events.system()
-                    .label("handle_collision_events")
-                    .after("get_contact_events")
-                )
-                .with_system(actor::handle_actor_action_start.system()
-                    .label("handle_actor_action_start")
-                    .after("handle_collision_events")
-                )
-                .with_system(actor::handle_actors_refresh_action.system()
-                    .label("handle_actors_refresh_action")
-                    .after("handle_actor_action_start")
-                )
-                .with_system(actor::handle_actor_events.system()
-                    .label("handle_actor_events")
-                    .after("handle_actors_refresh_action")
-                )
-                .with_system(ball::handle_ball_events.system()
-                    .label("handle_ball_events")
-                    .after("handle_actor_events")
-                )
-                .with_system(ball::update_thrown_ball.system()
-                    .label("update_thrown_ball")
-                    .after("handle_ball_events")
-                )
-                .with_system(matchup::handle_matchup_events.system()
-                    .label("handle_matchup_events")
-                    .after("update_thrown_ball")
-                )
-                .with_system(actor::update_helpers.system()
-                    .after("handle_matchup_events")
-                )
-        )
-        .add_system_set(
-            SystemSet::on_exit(states::AppState::Play)
-                .with_system(actor::after_round_reset.system().label("after_round_reset"))
-                .with_system(actor::handle_actor_action_start.system()
-                    .after("after_round_reset")
-                )
-                .with_system(ai::reset_ai_roles.system())
-        )
-        .add_system(bevy::input::system::exit_on_esc_system.system())
         .run();
 }
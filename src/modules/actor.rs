@@ -1,34 +1,125 @@
-use std::time::Duration;
-use bevy::prelude::*;
+use std::{collections::VecDeque, time::Duration};
+use bevy::{ecs::query::WorldQuery, prelude::*};
+use serde::{Deserialize, Serialize};
 use bevy_rapier2d::{
     physics::{RigidBodyHandleComponent},
     rapier::{
         dynamics::{RigidBodySet},
     }
 };
-use super::{animation, ai, ball, collision, helpers, physics, team, utils};
+use super::{animation, ai, ball, clock, collision, helpers, injury, matchup, passing, physics, round, stamina, states, team, utils, zone};
 
+//base values for an average actor, ActorStats scale them
 pub const PLAYER_RUN_SPEED: f32 = 100.0;
 const PLAYER_TACKLE_SPEED: f32 = 225.0;
 pub const PLAYER_GUARD_RADIUS: f32 = 60.0;
 pub const PLAYER_TACKLE_RADIUS: f32 = 120.0;
+const PLAYER_HOLD_RADIUS: f32 = 80.0;
+const PLAYER_DODGE_SPEED: f32 = 200.0;
+const PLAYER_DODGE_DISTANCE: f32 = 40.0;
+const PLAYER_SHOVE_RANGE: f32 = 40.0;
+const PLAYER_SHOVE_SPEED: f32 = 150.0;
 const PLAYER_RECOVERY_TIME_BUMPED: f32 = 0.3;
 const PLAYER_RECOVERY_TIME_TACKLED: f32 = 0.9;
 const PLAYER_RECOVERY_LINEAR_DAMPING: f32 = 1.5;
-pub const PLAYER_THROWING_POWER: f32 = 0.5;
+//three frames of throwing animation
+pub const PLAYER_THROWING_TIME: f32 = 3.0 / 8.0;
+//shove is three frames too, with a short lunge forward
+const PLAYER_SHOVE_TIME: f32 = 3.0 / 8.0;
+const PLAYER_SHOVE_LUNGE_SPEED: f32 = 40.0;
+const PLAYER_SHOVE_LINEAR_DAMPING: f32 = 4.0;
+const PLAYER_BLOCK_TIME: f32 = 1.0;
+
+pub struct ActorPlugin;
+
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ActorLabel {
+    RefreshActionStartPosition,
+    ActionStartStartPosition,
+    ActionStart,
+    RefreshAction,
+    HandleEvents,
+    AfterRoundReset,
+    ApplyOrders,
+    EvaluateConditions,
+    StartPlanning,
+}
 
 pub struct ActorTextures {
     red: Handle<TextureAtlas>,
     blue: Handle<TextureAtlas>,
 }
 pub struct ActionTimer(Timer);
+
+//ratings of an actor, 1.0 is average, missing ones in roster data are average too
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ActorStats {
+    pub speed: f32,
+    pub strength: f32,
+    pub agility: f32,
+    pub throwing: f32,
+    pub catching: f32,
+}
+impl ActorStats {
+    pub fn new() -> Self {
+        Self {
+            speed: 1.0,
+            strength: 1.0,
+            agility: 1.0,
+            throwing: 1.0,
+            catching: 1.0,
+        }
+    }
+    pub fn get_run_speed(&self) -> f32 {
+        PLAYER_RUN_SPEED * self.speed
+    }
+    pub fn get_tackle_speed(&self) -> f32 {
+        PLAYER_TACKLE_SPEED * self.speed
+    }
+    //agile actors notice runners and reach them from further away
+    pub fn get_guard_radius(&self) -> f32 {
+        PLAYER_GUARD_RADIUS * self.agility
+    }
+    pub fn get_tackle_radius(&self) -> f32 {
+        PLAYER_TACKLE_RADIUS * self.agility
+    }
+    pub fn get_hold_radius(&self) -> f32 {
+        PLAYER_HOLD_RADIUS * self.agility
+    }
+    pub fn get_dodge_speed(&self) -> f32 {
+        PLAYER_DODGE_SPEED * self.agility
+    }
+    pub fn get_dodge_distance(&self) -> f32 {
+        PLAYER_DODGE_DISTANCE * self.agility
+    }
+    //strong actor shoves opponents further
+    pub fn get_shove_speed(&self) -> f32 {
+        PLAYER_SHOVE_SPEED * self.strength
+    }
+    //strong hitter knocks down for longer, strong actor gets up sooner
+    pub fn get_recovery_time(&self, base_time: f32, hitter: &ActorStats) -> f32 {
+        base_time * hitter.strength / self.strength
+    }
+    pub fn get_throw_range(&self) -> f32 {
+        passing::DEFAULT_THROW_RANGE * self.throwing
+    }
+}
+impl Default for ActorStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 // pub struct BallPossession(pub bool);
 pub struct IsTackleTarget(pub bool);
 pub struct CurrentControlMode(pub ControlMode);
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum ControlMode {
     Run,
-    Throw
+    Throw(passing::PassType),
+    Dodge,
+    Block,
+    Shove,
 }
 
 pub enum ActorEvents{
@@ -42,38 +133,152 @@ pub enum ActorEvents{
         actor_action: ActorAction,
         other_actor_entity: Entity,
         other_actor_action: ActorAction
-    }
+    },
+    //outcomes of a tackle on the ball carrier, resolved once for both actors
+    CleanSteal {
+        tackler_entity: Entity,
+        carrier_entity: Entity,
+    },
+    Fumble {
+        tackler_entity: Entity,
+        carrier_entity: Entity,
+        bounce: Vec2,
+    },
+    BrokenTackle {
+        tackler_entity: Entity,
+        carrier_entity: Entity,
+    },
+    BothDown {
+        tackler_entity: Entity,
+        carrier_entity: Entity,
+    },
+    //tackle that missed the dodging actor
+    Dodged {
+        tackler_entity: Entity,
+        dodger_entity: Entity,
+    },
+    Shove {
+        entity: Entity,
+        team: team::Team,
+        position: Vec2,
+        direction: Vec2,
+    },
+    GuardZone {
+        entity: Entity,
+        team: team::Team,
+        center: Vec2,
+    },
+}
+
+//orders are the only way actions get planned (by input, AI or replay), so they can be recorded and sent around
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ActorOrder {
+    Set(ActorAction),
+    Queue(ActorAction),
+    //takes back the last order given in this Plan phase
+    Undo,
+    Conditional(ConditionalOrder),
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ActorCondition {
+    //opposing ball carrier gets within radius
+    CarrierInRange(f32),
+    //thrown ball will fly by within radius
+    BallThrownNear(f32),
+    //round time reaches given seconds
+    TimeElapsed(f32),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ActorReaction {
+    TackleCarrier,
+    InterceptBall,
+    Act(ActorAction),
+}
+
+//checked every frame of Play phase and fired at most once, it lasts only for the planned round
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ConditionalOrder {
+    pub condition: ActorCondition,
+    pub reaction: ActorReaction,
+}
+pub struct ActorOrderEvent {
+    pub entity: Entity,
+    pub order: ActorOrder,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub enum ActorAction {
     Idle,
     Lookout,
     Running { x: f32, y: f32 },
-    Throwing { x: f32, y: f32 },
+    Throwing { x: f32, y: f32, pass_type: passing::PassType },
     Tackling { x: f32, y: f32 },
-    Recovering(f32)
+    Recovering(f32),
+    //side-step to the target that makes the first tackle miss
+    Dodge { x: f32, y: f32 },
+    //stands facing the target, hits from the front don't move it
+    Block { x: f32, y: f32 },
+    //pushes the nearest opponent in front of the actor towards the target
+    Shove { x: f32, y: f32 },
+    //guards the zone around the target and tackles opponents entering it
+    HoldPosition { x: f32, y: f32 },
 }
 #[derive(Debug)]
 pub struct Actor {
     pub act_action: ActorAction,
-    queued_action: Option<ActorAction>,
+    queued_actions: VecDeque<ActorAction>,
     has_tackled: bool,
+    //orders given during current Plan phase and state before them, so they can be undone
+    planned_orders: Vec<ActorOrder>,
+    plan_start: (ActorAction, VecDeque<ActorAction>, Option<ConditionalOrder>),
+    conditional_order: Option<ConditionalOrder>,
 }
 impl Actor {
     pub fn new() -> Self {
         Self {
             act_action: ActorAction::Lookout,
-            queued_action: None,
+            queued_actions: VecDeque::new(),
             has_tackled: false,
+            planned_orders: vec![],
+            plan_start: (ActorAction::Lookout, VecDeque::new(), None),
+            conditional_order: None,
         }
     }
     pub fn trigger_queued_action(&mut self, has_ball: bool) {
-        self.act_action = self.queued_action.unwrap_or(if self.has_tackled || has_ball { ActorAction::Idle } else { ActorAction::Lookout });
+        self.act_action = self.queued_actions.pop_front().unwrap_or(if self.has_tackled || has_ball { ActorAction::Idle } else { ActorAction::Lookout });
     }
     pub fn set_action(&mut self, action: ActorAction) {
         self.act_action = action;
-        self.queued_action = None;
+        self.queued_actions.clear();
+    }
+    //same action starts again, e.g. to get back the speed lost in a hit, the queue stays as it is
+    //changed actor gets its action started by handle_actor_action_start
+    pub fn restart_action(&mut self) {
+        let queued_actions = std::mem::take(&mut self.queued_actions);
+        self.set_action(self.act_action);
+        self.queued_actions = queued_actions;
+    }
+    pub fn get_queued_actions(&self) -> Vec<ActorAction> {
+        self.queued_actions.iter().cloned().collect()
+    }
+    pub fn has_tackled(&self) -> bool {
+        self.has_tackled
+    }
+    pub fn restore(&mut self, act_action: ActorAction, queued_actions: Vec<ActorAction>, has_tackled: bool) {
+        self.act_action = act_action;
+        self.queued_actions = queued_actions.into_iter().collect();
+        self.has_tackled = has_tackled;
+    }
+    //guard gets up and goes back to its zone
+    pub fn knock_down(&mut self, recovery_time: f32) {
+        let previous_action = self.act_action;
+        self.set_action(ActorAction::Recovering(recovery_time));
+        if let ActorAction::HoldPosition { x, y } = previous_action {
+            self.queue_action(ActorAction::Running { x, y });
+            self.queue_action(previous_action);
+        }
     }
     pub fn queue_action(&mut self, action: ActorAction) {
         match self.act_action {
@@ -81,10 +286,63 @@ impl Actor {
                 self.set_action(action);
             },
             _ => {
-                self.queued_action = Some(action);
+                self.queued_actions.push_back(action);
             }
         }
     }
+    pub fn start_planning(&mut self) {
+        self.planned_orders.clear();
+        self.plan_start = (self.act_action, self.queued_actions.clone(), self.conditional_order);
+    }
+    pub fn get_conditional_order(&self) -> Option<ConditionalOrder> {
+        self.conditional_order
+    }
+    pub fn has_planned_orders(&self) -> bool {
+        !self.planned_orders.is_empty()
+    }
+    pub fn apply_order(&mut self, order: ActorOrder) {
+        match order {
+            ActorOrder::Set(action) => {
+                self.planned_orders.push(order);
+                self.set_action(action);
+            },
+            ActorOrder::Queue(action) => {
+                self.planned_orders.push(order);
+                self.queue_action(action);
+            },
+            ActorOrder::Conditional(conditional_order) => {
+                self.planned_orders.push(order);
+                self.conditional_order = Some(conditional_order);
+            },
+            ActorOrder::Undo => {
+                let mut orders = std::mem::take(&mut self.planned_orders);
+                orders.pop();
+                self.act_action = self.plan_start.0;
+                self.queued_actions = self.plan_start.1.clone();
+                self.conditional_order = self.plan_start.2;
+                for order in orders {
+                    self.apply_order(order);
+                }
+            }
+        };
+    }
+    //current action and all queued ones as (action, position where it starts, estimated duration)
+    pub fn get_plan(&self, position: Vec2, stats: &ActorStats) -> Vec<(ActorAction, Vec2, f32)> {
+        let mut from = position;
+        std::iter::once(&self.act_action).chain(self.queued_actions.iter()).map(|action| {
+            let (duration, to) = estimate_action(*action, from, stats);
+            let step = (*action, from, duration);
+            from = to;
+            step
+        }).collect()
+    }
+    pub fn get_plan_end_position(&self, position: Vec2, stats: &ActorStats) -> Vec2 {
+        self.get_plan(position, stats).last().map_or(position, |(action, from, _duration)| estimate_action(*action, *from, stats).1)
+    }
+    //plans are limited to what can be done during one round
+    pub fn get_plan_time_left(&self, position: Vec2, stats: &ActorStats) -> f32 {
+        round::ROUND_TIME - self.get_plan(position, stats).iter().map(|(_action, _from, duration)| duration).sum::<f32>()
+    }
 }
 
 pub struct Selected {}
@@ -97,6 +355,10 @@ fn get_idle_indexes(ball_possession: bool) -> Vec<usize> {
     if ball_possession { vec![3] } else { vec![0] }
 }
 
+fn get_dodge_indexes(ball_possession: bool) -> Vec<usize> {
+    if ball_possession  { vec![4, 5] } else { vec![1, 2] }
+}
+
 
 pub fn setup_actor_sprites(
     commands: &mut Commands,
@@ -119,45 +381,59 @@ pub fn setup_actor_sprites(
 
 pub fn spawn_actor(
     commands: &mut Commands,
-    actor_sprites: &Res<ActorTextures>,
+    actor_sprites: Option<&ActorTextures>,
     position: Vec2,
     team: team::Team,
     is_player_controlled: bool,
+    offense_focus: ai::AiFocus,
+    defense_focus: ai::AiFocus,
+    stats: ActorStats,
 ) -> Entity {
-    let (texture_atlas, is_left_side) = match team {
-        team::Team::Home => (actor_sprites.blue.clone(), false),
-        team::Team::Away => (actor_sprites.red.clone(), true),
+    let transform = Transform::from_translation(
+        Vec3::new(
+            position.x,
+            position.y,
+            utils::PLAYING_FIELD_Z
+        )
+    );
+    let mut actor = commands.spawn();
+    match actor_sprites {
+        Some(actor_sprites) => {
+            let (texture_atlas, is_left_side) = match team {
+                team::Team::Home => (actor_sprites.blue.clone(), false),
+                team::Team::Away => (actor_sprites.red.clone(), true),
+            };
+            actor.insert_bundle(SpriteSheetBundle {
+                texture_atlas,
+                transform,
+                sprite: TextureAtlasSprite {
+                    flip_x: is_left_side,
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+        },
+        None => actor.insert_bundle((transform, GlobalTransform::identity())),
     };
 
-    let e = commands
-        .spawn_bundle(SpriteSheetBundle {
-            texture_atlas,
-            transform: Transform::from_translation(
-                Vec3::new(
-                    position.x,
-                    position.y,
-                    utils::PLAYING_FIELD_Z
-                )
-            ),
-            sprite: TextureAtlasSprite {
-                flip_x: is_left_side,
-                ..Default::default()
-            },
-            ..Default::default()
-        })
+    let e = actor
         .insert(Actor::new())
         .insert(team)
         .insert(IsTackleTarget(false))
         .insert(animation::Animation::new(vec![0]))
         .insert(animation::AnimationTimer(Timer::from_seconds(1.0/8.0, true)))
         .insert(ActionTimer(Timer::from_seconds(1.0, false)))
+        .insert(stats)
+        .insert(stamina::Stamina::new())
+        .insert(injury::Health::new())
+        .insert(zone::Surface::new())
         .insert(collision::ColliderType::Actor)
         .id();
 
     if is_player_controlled {
         commands.entity(e).insert(ai::PlayerControlled {});
     } else {
-        commands.entity(e).insert(ai::AiControlled::new(ai::AiFocus::GuardBallCarrier, ai::AiFocus::DefendGoalPost));
+        commands.entity(e).insert(ai::AiControlled::new(offense_focus, defense_focus));
     }
     physics::create_physics_actor(commands, e, position);
 
@@ -177,13 +453,14 @@ pub fn after_round_reset(
     for (entity, mut actor, mut is_tackle_target) in query.iter_mut() {
         match actor.act_action {
             //actor will reset action only if running - if he was running at end of the turn he can tackle next round
-            ActorAction::Running { x: _, y: _ } => {
+            ActorAction::Running { x: _, y: _ } | ActorAction::Dodge { x: _, y: _ } => {
                 let has_ball = ball_possession.has_actor_ball(entity);
                 actor.set_action(if has_ball { ActorAction::Idle } else { ActorAction::Lookout });
             },
             _ => ()
         }
         actor.has_tackled = false;
+        actor.conditional_order = None;
         is_tackle_target.0 = false;
     }
 }
@@ -196,7 +473,7 @@ pub fn reset_action_timer(timer: &mut ActionTimer, t: f32) {
 
 
 pub fn handle_actors_refresh_action(
-    time: Res<Time>,
+    clock: Res<clock::GameClock>,
     mut ball_events: EventWriter<ball::BallEvent>,
     mut query: Query<(Entity, &team::Team, &mut Actor, &mut Transform, &mut ActionTimer, &animation::Animation)>,
     mut event_tackle_target: EventWriter<ActorEvents>,
@@ -220,8 +497,18 @@ pub fn handle_actors_refresh_action(
                 });
                 false
             },
+            ActorAction::HoldPosition { x, y } => {
+                if !actor.has_tackled {
+                    event_tackle_target.send(ActorEvents::GuardZone {
+                        entity,
+                        team: *team,
+                        center: Vec2::new(x, y),
+                    });
+                }
+                false
+            },
             ActorAction::Idle => false,
-            ActorAction::Running { x, y} | ActorAction::Tackling { x, y } => {
+            ActorAction::Running { x, y} | ActorAction::Tackling { x, y } | ActorAction::Dodge { x, y } => {
                 let d_x = transform.translation.x - x;
                 let d_y = transform.translation.y - y;
                 //when this value is too big (e.g. 10.0), then actor get from tackle action too soon into idle
@@ -229,14 +516,14 @@ pub fn handle_actors_refresh_action(
                 //test with from running from -100.0, 100.0 to 132.0, 48.0 and tackling actor standing at 100, 100
                 d_x.abs() < 2.0 && d_y.abs() < 2.0
             },
-            ActorAction::Throwing { x, y } => {
+            ActorAction::Throwing { x, y, pass_type } => {
                 if animation.finished {
                     if has_ball {
                         ball_events.send(ball::BallEvent::Throw {
                             entity,
                             position: Vec2::new(transform.translation.x, transform.translation.y),
                             throw_target: Vec2::new(x, y),
-                            power: PLAYER_THROWING_POWER,
+                            pass_type,
                         });
                     } else {
                         println!("Wanted to throw non-existing ball!");
@@ -244,8 +531,23 @@ pub fn handle_actors_refresh_action(
                 }
                 animation.finished
             },
-            ActorAction::Recovering(_) => {
-                timer.0.tick(time.delta());
+            ActorAction::Shove { x, y } => {
+                if animation.finished {
+                    let position = Vec2::new(transform.translation.x, transform.translation.y);
+                    let delta = Vec2::new(x, y) - position;
+                    if delta.length_squared() > 0.0 {
+                        event_tackle_target.send(ActorEvents::Shove {
+                            entity,
+                            team: *team,
+                            position,
+                            direction: delta.normalize(),
+                        });
+                    }
+                }
+                animation.finished
+            },
+            ActorAction::Recovering(_) | ActorAction::Block { x: _, y: _ } => {
+                timer.0.tick(clock.delta());
                 timer.0.finished()
             }
         };
@@ -256,15 +558,24 @@ pub fn handle_actors_refresh_action(
     }
 }
 
+fn face_towards(sprite: &mut Option<Mut<TextureAtlasSprite>>, delta_x: f32) {
+    if let Some(sprite) = sprite {
+        sprite.flip_x = delta_x < 0.0;
+    }
+}
+
 pub fn handle_actor_action_start(
     mut query: Query<(
         Entity,
         &mut Actor,
         &Transform,
         &RigidBodyHandleComponent,
-        &mut TextureAtlasSprite,
+        Option<&mut TextureAtlasSprite>,
         &mut animation::Animation,
         &mut ActionTimer,
+        &ActorStats,
+        &stamina::Stamina,
+        &zone::Surface,
     ), Changed<Actor>>,
     mut rigid_body_set: ResMut<RigidBodySet>,
     ball_possession: Res<ball::BallPossession>,
@@ -277,7 +588,13 @@ pub fn handle_actor_action_start(
         mut sprite,
         mut animation,
         mut timer,
+        stats,
+        stamina,
+        surface,
     ) in query.iter_mut() {
+        let stats = stamina.apply(stats);
+        let speed_factor = surface.get_speed_factor();
+        let damping_factor = surface.get_damping_factor();
         let has_ball = ball_possession.has_actor_ball(entity);
         match actor.act_action {
             ActorAction::Lookout | ActorAction::Idle => {
@@ -285,37 +602,59 @@ pub fn handle_actor_action_start(
                 physics::set_rb_properties(rigid_body_handle, &mut rigid_body_set,  Some(Vec2::ZERO), None, Some(0.0));
             },
             ActorAction::Tackling {x, y} => {
-                let delta = (Vec3::new(x, y, transform.translation.z) - transform.translation).normalize() * PLAYER_TACKLE_SPEED ;
-                sprite.flip_x = delta.x < 0.0;
+                let delta = get_direction(Vec2::from(transform.translation), Vec2::new(x, y)) * stats.get_tackle_speed() * speed_factor;
+                face_towards(&mut sprite, delta.x);
                 animation.update_sprites_indexes(vec![10, 11, 12], false);
                 physics::set_rb_properties(rigid_body_handle, &mut rigid_body_set, Some(Vec2::new(delta.x, delta.y)), None, Some(0.0));
                 actor.has_tackled = true;
             }
             ActorAction::Running { x, y} => {
-                let delta = (Vec3::new(x, y, transform.translation.z) - transform.translation).normalize() * PLAYER_RUN_SPEED;
-                sprite.flip_x = delta.x < 0.0;
+                let delta = get_direction(Vec2::from(transform.translation), Vec2::new(x, y)) * stats.get_run_speed() * speed_factor;
+                face_towards(&mut sprite, delta.x);
                 animation.update_sprites_indexes(get_running_indexes(has_ball), true);
                 physics::set_rb_properties(rigid_body_handle, &mut rigid_body_set, Some(Vec2::new(delta.x, delta.y)), None, Some(0.0));
             },
-            ActorAction::Throwing { x, y} => {
+            ActorAction::Throwing { x, y, pass_type: _ } => {
                 animation.update_sprites_indexes(vec![7, 8, 9], false);
-                let delta = (Vec3::new(x, y, transform.translation.z) - transform.translation).normalize();
-                sprite.flip_x = delta.x < 0.0;
+                let delta = get_direction(Vec2::from(transform.translation), Vec2::new(x, y));
+                face_towards(&mut sprite, delta.x);
                 physics::set_rb_properties(rigid_body_handle, &mut rigid_body_set, None, None, Some(0.0));
             }
             ActorAction::Recovering(t) => {
                 animation.update_sprites_indexes(vec![6], true);
                 reset_action_timer(&mut timer, t);
-                physics::set_rb_properties(rigid_body_handle, &mut rigid_body_set, None, None, Some(PLAYER_RECOVERY_LINEAR_DAMPING));
+                physics::set_rb_properties(rigid_body_handle, &mut rigid_body_set, None, None, Some(PLAYER_RECOVERY_LINEAR_DAMPING * damping_factor));
             }
+            ActorAction::Dodge { x, y } => {
+                let delta = get_direction(Vec2::from(transform.translation), Vec2::new(x, y)) * stats.get_dodge_speed() * speed_factor;
+                face_towards(&mut sprite, delta.x);
+                animation.update_sprites_indexes(get_dodge_indexes(has_ball), true);
+                physics::set_rb_properties(rigid_body_handle, &mut rigid_body_set, Some(Vec2::new(delta.x, delta.y)), None, Some(0.0));
+            },
+            ActorAction::Block { x, y: _ } => {
+                face_towards(&mut sprite, x - transform.translation.x);
+                animation.update_sprites_indexes(vec![10], true);
+                reset_action_timer(&mut timer, PLAYER_BLOCK_TIME);
+                physics::set_rb_properties(rigid_body_handle, &mut rigid_body_set, Some(Vec2::ZERO), None, Some(0.0));
+            },
+            ActorAction::Shove { x, y } => {
+                let delta = get_direction(Vec2::from(transform.translation), Vec2::new(x, y)) * PLAYER_SHOVE_LUNGE_SPEED * speed_factor;
+                face_towards(&mut sprite, delta.x);
+                animation.update_sprites_indexes(vec![11, 12, 10], false);
+                physics::set_rb_properties(rigid_body_handle, &mut rigid_body_set, Some(Vec2::new(delta.x, delta.y)), None, Some(PLAYER_SHOVE_LINEAR_DAMPING * damping_factor));
+            },
+            ActorAction::HoldPosition { x: _, y: _ } => {
+                animation.update_sprites_indexes(vec![0, 10], true);
+                physics::set_rb_properties(rigid_body_handle, &mut rigid_body_set,  Some(Vec2::ZERO), None, Some(0.0));
+            },
         };
     }
 }
 
-fn get_tackle_hit_position(target_position: Vec2, target_velocity: Vec2, origin_position: Vec2) -> Option<Vec2> {
+fn get_tackle_hit_position(target_position: Vec2, target_velocity: Vec2, origin_position: Vec2, stats: &ActorStats) -> Option<Vec2> {
     let mut last_magnitude = f32::INFINITY;
     let mut step = 0.2;
-    let actor_speed_squared = PLAYER_TACKLE_SPEED.powi(2);
+    let actor_speed_squared = stats.get_tackle_speed().powi(2);
 
     //P1 + V1*step = P2 + V2*step ---> iterate through steps, solve for V2
     loop {
@@ -323,7 +662,7 @@ fn get_tackle_hit_position(target_position: Vec2, target_velocity: Vec2, origin_
         let new_magnitude = tackle_velocity.length_squared();
         let hit_position = origin_position + (tackle_velocity*step);
         //if it's possible for actor to reach this velocity and if hit position is in tackle range then return it
-        if new_magnitude < actor_speed_squared && (hit_position - origin_position).length_squared() < PLAYER_TACKLE_RADIUS.powi(2) {
+        if new_magnitude < actor_speed_squared && (hit_position - origin_position).length_squared() < stats.get_tackle_radius().powi(2) {
             return Some(hit_position);
         }
         //if the new magnitude is higher, e.g. target is getting away and there is no chance to catch it + limit number of calculation to prevent infinite loops
@@ -335,15 +674,137 @@ fn get_tackle_hit_position(target_position: Vec2, target_velocity: Vec2, origin_
     }
 }
 
+fn get_ball_interception_position(ball_position: Vec2, ball_velocity: Vec2, air_time_left: f32, origin_position: Vec2) -> Vec2 {
+    //closest point of the remaining flight path
+    let flight = ball_velocity * air_time_left;
+    if flight.length_squared() == 0.0 {
+        return ball_position;
+    }
+    let t = ((origin_position - ball_position).dot(flight) / flight.length_squared()).clamp(0.0, 1.0);
+    ball_position + flight * t
+}
+
+pub fn evaluate_conditional_orders(
+    mut query: Query<(Entity, &team::Team, &mut Actor, &Transform, &RigidBodyHandleComponent, &ActorStats, &stamina::Stamina)>,
+    mut query_tackle_target: Query<&mut IsTackleTarget>,
+    query_ball: Query<(&Transform, &RigidBodyHandleComponent, &ball::AirTime), With<ball::Ball>>,
+    query_round_timer: Query<&round::RoundTimer>,
+    rigid_body_set: ResMut<RigidBodySet>,
+    ball_possession: Res<ball::BallPossession>,
+) {
+    let elapsed = query_round_timer.single().map_or(0.0, |timer| timer.elapsed_secs());
+    //carrier can be tackled only while running, same as with Lookout
+    let carrier = ball_possession.get().and_then(|entity| query.get_mut(entity).ok().and_then(|(_entity, team, actor, transform, rigid_body_handle, _stats, _stamina)| match actor.act_action {
+        ActorAction::Running { x: _, y: _ } => Some((entity, *team, Vec2::from(transform.translation), physics::get_velocity(rigid_body_handle, &rigid_body_set).unwrap_or(Vec2::ZERO))),
+        _ => None
+    }));
+    //only the low part of the flight can be intercepted
+    let ball_flight = query_ball.single().ok().and_then(|(transform, rigid_body_handle, air_time)| {
+        if air_time.is_in_flight() {
+            let velocity = physics::get_velocity(rigid_body_handle, &rigid_body_set).unwrap_or(Vec2::ZERO);
+            let high_secs = air_time.high_secs_left();
+            Some((Vec2::from(transform.translation) + velocity * high_secs, velocity, air_time.remaining_secs() - high_secs))
+        } else {
+            None
+        }
+    });
+
+    for (_entity, team, mut actor, transform, _rigid_body_handle, stats, stamina) in query.iter_mut() {
+        let stats = stamina.apply(stats);
+        let conditional_order = match actor.conditional_order {
+            Some(conditional_order) => conditional_order,
+            None => continue
+        };
+        //actor has to be on his feet and free to react
+        match actor.act_action {
+            ActorAction::Recovering(_) | ActorAction::Tackling { x: _, y: _ } | ActorAction::Throwing { x: _, y: _, pass_type: _ } | ActorAction::Dodge { x: _, y: _ } | ActorAction::Shove { x: _, y: _ } => continue,
+            _ => ()
+        };
+        let position = Vec2::from(transform.translation);
+        let opposing_carrier = carrier.filter(|(_entity, carrier_team, _position, _velocity)| carrier_team != team);
+
+        let is_met = match conditional_order.condition {
+            ActorCondition::CarrierInRange(radius) => opposing_carrier.map_or(false, |(_entity, _team, carrier_position, _velocity)| (carrier_position - position).length_squared() <= radius.powi(2)),
+            ActorCondition::BallThrownNear(radius) => ball_flight.map_or(false, |(ball_position, ball_velocity, air_time_left)| {
+                (get_ball_interception_position(ball_position, ball_velocity, air_time_left, position) - position).length_squared() <= radius.powi(2)
+            }),
+            ActorCondition::TimeElapsed(t) => elapsed >= t,
+        };
+        if !is_met {
+            continue;
+        }
+
+        //reaction that has no target yet waits for the next frame
+        match conditional_order.reaction {
+            ActorReaction::TackleCarrier => {
+                let hit = opposing_carrier.and_then(|(carrier_entity, _team, carrier_position, carrier_velocity)| {
+                    let is_tackle_target = query_tackle_target.get_mut(carrier_entity).map_or(true, |is_tackle_target| is_tackle_target.0);
+                    if is_tackle_target || actor.has_tackled {
+                        return None;
+                    }
+                    get_tackle_hit_position(carrier_position, carrier_velocity, position, &stats).map(|hp| (carrier_entity, hp))
+                });
+                if let Some((carrier_entity, hp)) = hit {
+                    if let Ok(mut is_tackle_target) = query_tackle_target.get_mut(carrier_entity) {
+                        is_tackle_target.0 = true;
+                    }
+                    actor.set_action(ActorAction::Tackling { x: hp.x, y: hp.y });
+                    actor.queue_action(ActorAction::Idle);
+                    actor.conditional_order = None;
+                }
+            },
+            ActorReaction::InterceptBall => {
+                if let Some((ball_position, ball_velocity, air_time_left)) = ball_flight {
+                    let target = get_ball_interception_position(ball_position, ball_velocity, air_time_left, position);
+                    actor.set_action(ActorAction::Running { x: target.x, y: target.y });
+                    actor.conditional_order = None;
+                }
+            },
+            ActorReaction::Act(action) => {
+                actor.set_action(action);
+                actor.conditional_order = None;
+            }
+        };
+    }
+}
+
+//stats the actor has at the moment, tired actors are weaker
+fn get_current_stats<Q: WorldQuery>(query: &Query<Q>, entity: Entity) -> ActorStats {
+    let stats = query.get_component::<ActorStats>(entity).expect("Cannot get actor stats!");
+    let stamina = query.get_component::<stamina::Stamina>(entity).expect("Cannot get actor stamina!");
+    stamina.apply(stats)
+}
+
+fn knock_down<Q: WorldQuery>(query: &mut Query<Q>, entity: Entity, hitter_entity: Entity, base_recovery_time: f32) -> f32 {
+    let hitter_stats = get_current_stats(query, hitter_entity);
+    let recovery_time = get_current_stats(query, entity).get_recovery_time(base_recovery_time, &hitter_stats);
+    query.get_component_mut::<Actor>(entity).expect("Cannot get actor that was knocked down!").knock_down(recovery_time);
+    recovery_time
+}
+
+//tackles are the only hits hard enough to injure
+fn tackle_down<Q: WorldQuery>(query: &mut Query<Q>, hard_hits: &mut EventWriter<injury::HardHit>, entity: Entity, hitter_entity: Entity) {
+    let recovery_time = knock_down(query, entity, hitter_entity, PLAYER_RECOVERY_TIME_TACKLED);
+    hard_hits.send(injury::HardHit { entity, severity: recovery_time / PLAYER_RECOVERY_TIME_TACKLED });
+}
+
+fn stop_tackle<Q: WorldQuery>(query: &mut Query<Q>, entity: Entity) {
+    query.get_component_mut::<Actor>(entity).expect("Cannot get actor that tackled!").set_action(ActorAction::Idle);
+}
+
 pub fn handle_actor_events(
     mut events: EventReader<ActorEvents>,
     mut ball_events: EventWriter<ball::BallEvent>,
+    mut hard_hits: EventWriter<injury::HardHit>,
     mut query: Query<(
+        Entity,
         &mut Actor,
         &team::Team,
         &mut IsTackleTarget,
         &Transform,
-        &RigidBodyHandleComponent
+        &RigidBodyHandleComponent,
+        &ActorStats,
+        &stamina::Stamina,
     )>,
     mut rigid_body_set: ResMut<RigidBodySet>,
     mut ball_possession: ResMut<ball::BallPossession>,
@@ -351,64 +812,187 @@ pub fn handle_actor_events(
     for event in events.iter() {
         match event {
             ActorEvents::ActorsCollided { actor_entity, actor_action, other_actor_entity,  other_actor_action} => {
-                let recovery_time = match *other_actor_action {
+                let (_entity, _actor, other_team, _is_tackle_target, other_transform, _rigid_body_handle, other_stats, other_stamina) = query.get_mut(*other_actor_entity).expect("Cannot get actor that hit!");
+                let other_stats = other_stamina.apply(other_stats);
+                let other_team = *other_team;
+                let other_position = Vec2::new(other_transform.translation.x, other_transform.translation.y);
+                let (
+                    _entity,
+                    mut actor,
+                    team,
+                    _is_tackle_target,
+                    transform,
+                    rigid_body_handle,
+                    stats,
+                    stamina,
+                ) = query.get_mut(*actor_entity).expect("Cannot get actor that was hit!");
+                let position = Vec2::new(transform.translation.x, transform.translation.y);
+
+                //blocker doesn't move when hit from the front, opponents running into it bounce off
+                if is_blocking(*actor_action, position, other_position) {
+                    continue;
+                }
+                let base_recovery_time = match *other_actor_action {
                     ActorAction::Tackling { x: _, y: _ } => PLAYER_RECOVERY_TIME_TACKLED,
-                    ActorAction::Running { x: _, y: _ } => {
+                    ActorAction::Running { x: _, y: _ } | ActorAction::Dodge { x: _, y: _ } => {
                         match *actor_action {
                             ActorAction::Tackling  { x: _, y: _ } => 0.0,
                             _ => PLAYER_RECOVERY_TIME_BUMPED
                         }
                     },
+                    ActorAction::Block { x: _, y: _ } if other_team != *team && is_blocking(*other_actor_action, other_position, position) => PLAYER_RECOVERY_TIME_BUMPED,
                     _ => 0.0
                 };
-                let (
-                    mut actor,
-                    _team,
-                    _is_tackle_target,
-                    transform,
-                    rigid_body_handle
-                ) = query.get_mut(*actor_entity).expect("Cannot get actor that was hit!");
 
-                let action = if recovery_time > 0.0 { ActorAction::Recovering(recovery_time) } else { ActorAction::Idle };
-                actor.set_action(action);
+                let recovery_time = stamina.apply(stats).get_recovery_time(base_recovery_time, &other_stats);
+                if recovery_time > 0.0 && base_recovery_time == PLAYER_RECOVERY_TIME_TACKLED {
+                    hard_hits.send(injury::HardHit { entity: *actor_entity, severity: recovery_time / PLAYER_RECOVERY_TIME_TACKLED });
+                }
+                match (recovery_time > 0.0, actor.act_action) {
+                    (true, _) => actor.knock_down(recovery_time),
+                    //guard stays in its zone after a light bump
+                    (false, ActorAction::HoldPosition { x: _, y: _ }) => (),
+                    (false, _) => actor.set_action(ActorAction::Idle),
+                };
 
+                //tackles on the carrier are resolved separately, any other hit makes it drop the ball
                 if ball_possession.has_actor_ball(*actor_entity){
-                    match  *other_actor_action {
-                        ActorAction::Tackling { x:_, y:_ } => {
-                            ball_possession.set(*other_actor_entity);
-                        },
-                        _ => {
-                            let rb_vel = physics::get_velocity(rigid_body_handle, &mut rigid_body_set).expect("Cannot get velocity information from actor");
-                            ball_events.send(ball::BallEvent::Drop {
-                                entity: *actor_entity,
-                                position: Vec2::new(transform.translation.x, transform.translation.y),
-                                velocity_vector: rb_vel,
-                            });
-                        }
+                    let rb_vel = physics::get_velocity(rigid_body_handle, &mut rigid_body_set).expect("Cannot get velocity information from actor");
+                    ball_events.send(ball::BallEvent::Drop {
+                        entity: *actor_entity,
+                        position,
+                        velocity_vector: rb_vel,
+                    });
+                }
+            },
+            ActorEvents::CleanSteal { tackler_entity, carrier_entity } => {
+                tackle_down(&mut query, &mut hard_hits, *carrier_entity, *tackler_entity);
+                stop_tackle(&mut query, *tackler_entity);
+                ball_possession.set(*tackler_entity);
+            },
+            ActorEvents::Fumble { tackler_entity, carrier_entity, bounce } => {
+                tackle_down(&mut query, &mut hard_hits, *carrier_entity, *tackler_entity);
+                stop_tackle(&mut query, *tackler_entity);
+                let transform = query.get_component::<Transform>(*carrier_entity).expect("Cannot get actor that fumbled!");
+                ball_events.send(ball::BallEvent::Drop {
+                    entity: *carrier_entity,
+                    position: Vec2::new(transform.translation.x, transform.translation.y),
+                    velocity_vector: *bounce,
+                });
+            },
+            ActorEvents::BrokenTackle { tackler_entity, carrier_entity } => {
+                tackle_down(&mut query, &mut hard_hits, *tackler_entity, *carrier_entity);
+                //the hit only slowed the carrier down, starting the action again gets it back to full speed
+                query.get_component_mut::<Actor>(*carrier_entity).expect("Cannot get actor that broke the tackle!").restart_action();
+            },
+            //carrier goes down holding the ball
+            ActorEvents::BothDown { tackler_entity, carrier_entity } => {
+                tackle_down(&mut query, &mut hard_hits, *carrier_entity, *tackler_entity);
+                tackle_down(&mut query, &mut hard_hits, *tackler_entity, *carrier_entity);
+            },
+            //tackler dives into empty space, dodge is used up and the actor goes on with its plan
+            ActorEvents::Dodged { tackler_entity, dodger_entity } => {
+                knock_down(&mut query, *tackler_entity, *dodger_entity, PLAYER_RECOVERY_TIME_BUMPED);
+                let has_ball = ball_possession.has_actor_ball(*dodger_entity);
+                query.get_component_mut::<Actor>(*dodger_entity).expect("Cannot get actor that dodged!").trigger_queued_action(has_ball);
+            },
+            ActorEvents::Shove { entity, team, position, direction } => {
+                let mut target = None;
+                for (target_entity, actor, target_team, _is_tackle_target, transform, _rigid_body_handle, _stats, _stamina) in query.iter_mut() {
+                    let offset = Vec2::new(transform.translation.x, transform.translation.y) - *position;
+                    let distance_squared = offset.length_squared();
+                    let is_down = match actor.act_action {
+                        ActorAction::Recovering(_) => true,
+                        _ => false
+                    };
+                    if target_team == team || is_down || offset.dot(*direction) <= 0.0 || distance_squared > PLAYER_SHOVE_RANGE.powi(2) {
+                        continue;
+                    }
+                    if target.map_or(true, |(_entity, closest_distance_squared)| distance_squared < closest_distance_squared) {
+                        target = Some((target_entity, distance_squared));
+                    }
+                }
+
+                if let Some((target_entity, _distance_squared)) = target {
+                    let velocity = *direction * get_current_stats(&query, *entity).get_shove_speed();
+                    knock_down(&mut query, target_entity, *entity, PLAYER_RECOVERY_TIME_BUMPED);
+                    let (_entity, _actor, _team, _is_tackle_target, transform, rigid_body_handle, _stats, _stamina) = query.get_mut(target_entity).expect("Cannot get actor that was shoved!");
+                    physics::set_rb_properties(rigid_body_handle, &mut rigid_body_set, Some(velocity), None, None);
+                    if ball_possession.has_actor_ball(target_entity) {
+                        ball_events.send(ball::BallEvent::Drop {
+                            entity: target_entity,
+                            position: Vec2::new(transform.translation.x, transform.translation.y),
+                            velocity_vector: velocity,
+                        });
                     }
                 }
             },
+            ActorEvents::GuardZone { entity, team, center } => {
+                let stats = get_current_stats(&query, *entity);
+                let guard_transform = query.get_component::<Transform>(*entity).expect("Player that spawned GuardZone event no longer exists!");
+                let guard_position = Vec2::new(guard_transform.translation.x, guard_transform.translation.y);
+                let hold_radius_squared = stats.get_hold_radius().powi(2);
+                let mut hit_position = None;
+                for (
+                    _target_entity,
+                    actor,
+                    team_target,
+                    mut is_tackle_target,
+                    transform,
+                    rigid_body_handle,
+                    _stats,
+                    _stamina,
+                ) in query.iter_mut() {
+                    let target_position = Vec2::new(transform.translation.x, transform.translation.y);
+                    //anyone on his feet entering the zone is engaged, not only runners
+                    let is_down = match actor.act_action {
+                        ActorAction::Recovering(_) => true,
+                        _ => false
+                    };
+                    if team_target == team || (target_position - *center).length_squared() > hold_radius_squared || is_down || is_tackle_target.0 {
+                        continue;
+                    }
+                    let target_velocity = physics::get_velocity(rigid_body_handle, &mut rigid_body_set).expect("Cannot get velocity information from actor");
+                    hit_position = get_tackle_hit_position(target_position, target_velocity, guard_position, &stats);
+                    if hit_position.is_some() {
+                        is_tackle_target.0 = true;
+                        break;
+                    }
+                }
+
+                if let Some(hp) = hit_position {
+                    let mut actor = query.get_component_mut::<Actor>(*entity).expect("Player that spawned GuardZone event no longer exists!");
+                    actor.set_action(ActorAction::Tackling { x: hp.x, y: hp.y });
+                    actor.queue_action(ActorAction::Running { x: center.x, y: center.y });
+                    actor.queue_action(ActorAction::HoldPosition { x: center.x, y: center.y });
+                }
+            },
             ActorEvents::LookForTackle { entity, position, team } => {
-                let actor_tackle_radius_squared = PLAYER_GUARD_RADIUS.powi(2);
+                let (_entity, _actor, _team, _is_tackle_target, _transform, _rigid_body_handle, stats, stamina) = query.get_mut(*entity).expect("Player that spawned LookForTackle event no longer exists!");
+                let stats = stamina.apply(stats);
+                let actor_tackle_radius_squared = stats.get_guard_radius().powi(2);
                 let mut hit_position = None;
                 for (
+                    _target_entity,
                     actor,
                     team_target,
                     mut is_tackle_target,
                     transform,
-                    rigid_body_handle
+                    rigid_body_handle,
+                    _stats,
+                    _stamina,
                 ) in query.iter_mut() {
                     let target_position = Vec2::new(transform.translation.x, transform.translation.y);
                     //TODO: maybe even when throwing, altough it seems to be bugged atm
                     let is_in_nontacklable_state = match actor.act_action {
-                        ActorAction::Running { x: _, y: _ } => false,
+                        ActorAction::Running { x: _, y: _ } | ActorAction::Dodge { x: _, y: _ } => false,
                         _ => true
                     };
                     if team_target == team || (target_position - *position).length_squared() > actor_tackle_radius_squared || is_in_nontacklable_state || is_tackle_target.0 {
                         continue;
                     }
                     let target_velocity = physics::get_velocity(rigid_body_handle, &mut rigid_body_set).expect("Cannot get velocity information from actor");
-                    hit_position = get_tackle_hit_position(target_position, target_velocity, *position);
+                    hit_position = get_tackle_hit_position(target_position, target_velocity, *position, &stats);
                     if hit_position.is_some() {
                         is_tackle_target.0 = true;
                         break;
@@ -417,11 +1001,14 @@ pub fn handle_actor_events(
 
                 if let Some(hp) = hit_position {
                     let (
+                        _entity,
                         mut actor,
                         _team,
                         _is_tackle_target,
                         _transform,
-                        _rigid_body_handle
+                        _rigid_body_handle,
+                        _stats,
+                        _stamina,
                     ) =  query.get_mut(*entity).expect("Player that spawned LookForTackle event no longer exists!");
                     actor.set_action(ActorAction::Tackling { x: hp.x, y: hp. y });
                     actor.queue_action(ActorAction::Idle);
@@ -457,6 +1044,79 @@ pub fn change_ball_possession(
     }
 }
 
+//estimated (duration, end position) of an action started at given position
+pub fn estimate_action(action: ActorAction, from: Vec2, stats: &ActorStats) -> (f32, Vec2) {
+    match action {
+        ActorAction::Running { x, y } => ((Vec2::new(x, y) - from).length() / stats.get_run_speed(), Vec2::new(x, y)),
+        ActorAction::Tackling { x, y } => ((Vec2::new(x, y) - from).length() / stats.get_tackle_speed(), Vec2::new(x, y)),
+        ActorAction::Throwing { x: _, y: _, pass_type: _ } => (PLAYER_THROWING_TIME, from),
+        ActorAction::Recovering(t) => (t, from),
+        ActorAction::Dodge { x, y } => ((Vec2::new(x, y) - from).length() / stats.get_dodge_speed(), Vec2::new(x, y)),
+        ActorAction::Block { x: _, y: _ } => (PLAYER_BLOCK_TIME, from),
+        ActorAction::Shove { x: _, y: _ } => (PLAYER_SHOVE_TIME, from),
+        ActorAction::Idle | ActorAction::Lookout | ActorAction::HoldPosition { x: _, y: _ } => (0.0, from),
+    }
+}
+
+//dodge is only a short side-step, further targets are cut to its length
+pub fn clamp_dodge_target(from: Vec2, target: Vec2, stats: &ActorStats) -> Vec2 {
+    let delta = target - from;
+    if delta.length() > stats.get_dodge_distance() {
+        from + delta.normalize() * stats.get_dodge_distance()
+    } else {
+        target
+    }
+}
+
+//blocker facing the target stops hits coming from the front
+fn is_blocking(action: ActorAction, position: Vec2, hitter_position: Vec2) -> bool {
+    match action {
+        ActorAction::Block { x, y } => (hitter_position - position).dot(Vec2::new(x, y) - position) > 0.0,
+        _ => false
+    }
+}
+
+//actor already standing at the target doesn't move anywhere
+pub fn get_direction(from: Vec2, to: Vec2) -> Vec2 {
+    let delta = to - from;
+    if delta.length_squared() > 0.0 { delta.normalize() } else { Vec2::ZERO }
+}
+
+//velocity the actor is trying to move with, physics can differ after collisions
+pub fn get_action_velocity(action: ActorAction, from: Vec2, stats: &ActorStats) -> Vec2 {
+    let (target, speed) = match action {
+        ActorAction::Running { x, y } => (Vec2::new(x, y), stats.get_run_speed()),
+        ActorAction::Tackling { x, y } => (Vec2::new(x, y), stats.get_tackle_speed()),
+        ActorAction::Dodge { x, y } => (Vec2::new(x, y), stats.get_dodge_speed()),
+        _ => return Vec2::ZERO
+    };
+    get_direction(from, target) * speed
+}
+
+//result depends only on the actor's own state, so orders of different actors can be applied in any order
+pub fn apply_order(actor: &mut Actor, order: ActorOrder) {
+    actor.apply_order(order);
+}
+
+pub fn start_planning(
+    mut query: Query<&mut Actor>,
+) {
+    for mut actor in query.iter_mut() {
+        actor.start_planning();
+    }
+}
+
+pub fn apply_actor_orders(
+    mut events: EventReader<ActorOrderEvent>,
+    mut query: Query<&mut Actor>,
+) {
+    for event in events.iter() {
+        if let Ok(mut actor) = query.get_mut(event.entity) {
+            apply_order(&mut actor, event.order);
+        }
+    }
+}
+
 pub fn update_helpers(
     mut commands: Commands,
     mut query: Query<(Entity, &Actor)>,
@@ -465,7 +1125,7 @@ pub fn update_helpers(
     for (entity, actor) in query.iter_mut() {
         let should_keep_helpers = match actor.act_action {
             ActorAction::Running { x: _, y: _ } => true,
-            _ => false,
+            _ => !actor.queued_actions.is_empty() || actor.conditional_order.is_some(),
         };
 
         if should_keep_helpers {
@@ -479,3 +1139,70 @@ pub fn update_helpers(
         }
     }
 }
+
+impl Plugin for ActorPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .insert_resource(CurrentControlMode(ControlMode::Run))
+            .add_event::<ActorEvents>()
+            .add_event::<ActorOrderEvent>()
+            .add_system_set(
+                SystemSet::on_update(states::AppState::MovingToStartPosition)
+                    .with_system(handle_actors_refresh_action.system()
+                        .label(ActorLabel::RefreshActionStartPosition)
+                        .after(animation::AnimationLabel::Animate)
+                    )
+                    .with_system(handle_actor_action_start.system()
+                        .label(ActorLabel::ActionStartStartPosition)
+                        .after(ActorLabel::RefreshActionStartPosition)
+                    )
+            )
+            .add_system_set(
+                SystemSet::on_enter(states::AppState::Plan)
+                    .with_system(start_planning.system()
+                        .label(ActorLabel::StartPlanning)
+                    )
+            )
+            .add_system_set(
+                SystemSet::on_update(states::AppState::Plan)
+                    .with_system(apply_actor_orders.system()
+                        .label(ActorLabel::ApplyOrders)
+                    )
+            )
+            .add_system_set(
+                SystemSet::on_enter(states::AppState::Play)
+                    .with_system(reset_control_mode.system())
+            )
+            .add_system_set(
+                SystemSet::on_update(states::AppState::Play)
+                    //animation has to be ordered explicitly, throws depend on it and same seed must give same result
+                    .with_system(handle_actor_action_start.system()
+                        .label(ActorLabel::ActionStart)
+                        .after(collision::CollisionLabel::HandleCollisionEvents)
+                        .after(animation::AnimationLabel::Animate)
+                    )
+                    .with_system(handle_actors_refresh_action.system()
+                        .label(ActorLabel::RefreshAction)
+                        .after(ActorLabel::ActionStart)
+                    )
+                    .with_system(evaluate_conditional_orders.system()
+                        .label(ActorLabel::EvaluateConditions)
+                        .after(ActorLabel::RefreshAction)
+                    )
+                    .with_system(handle_actor_events.system()
+                        .label(ActorLabel::HandleEvents)
+                        .after(ActorLabel::EvaluateConditions)
+                    )
+                    .with_system(update_helpers.system()
+                        .after(matchup::MatchupLabel::HandleEvents)
+                    )
+            )
+            .add_system_set(
+                SystemSet::on_exit(states::AppState::Play)
+                    .with_system(after_round_reset.system().label(ActorLabel::AfterRoundReset))
+                    .with_system(handle_actor_action_start.system()
+                        .after(ActorLabel::AfterRoundReset)
+                    )
+            );
+    }
+}
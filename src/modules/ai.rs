@@ -1,17 +1,51 @@
-use core::f32;
-use std::{cell::Cell, cmp::Ordering};
+use std::{cmp::Ordering, f32::consts::FRAC_PI_2};
 
 use bevy::prelude::*;
-use bevy_rapier2d::{na::{Isometry, Isometry2, Point2, Vector2}, rapier::parry::{self, query::{Ray, RayCast}}};
+use bevy_rapier2d::{na::{Isometry2, Point2, Vector2}, rapier::parry::{self, query::{Ray, RayCast}}};
 use rand::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::modules::utils::get_rotated_vector;
 
-use super::{actor, arena, ball, helpers, round, team, utils};
+use super::{actor, arena, ball, helpers, injury, passing, referee, rng, round, states, team};
+
+pub struct AiPlugin;
+
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum AiLabel {
+    Process,
+}
 pub struct PlayerControlled {}
 
 const AI_FORWARD_MOMENTUM: f32 = 100.0;
 const AI_WING_MARGIN: f32 = 100.0;
+//free way is searched by turning away from the target by this angle to both sides
+const AI_RAY_STEP: f32 = 0.1;
+//base utilities of team tasks, distance to the task scales them down
+const AI_SCORE_UTILITY: f32 = 10.0;
+const AI_LOOSE_BALL_UTILITY: f32 = 3.0;
+const AI_INTERCEPT_UTILITY: f32 = 2.5;
+const AI_MARK_UTILITY: f32 = 2.0;
+const AI_DEFEND_GOAL_UTILITY: f32 = 1.5;
+const AI_SUPPORT_UTILITY: f32 = 1.5;
+const AI_WING_UTILITY: f32 = 1.0;
+const AI_FORWARD_UTILITY: f32 = 0.8;
+//added to the tasks preferred by the focus of the actor
+const AI_FOCUS_BONUS: f32 = 0.5;
+//marking actor stands between the threat and own goal, this deep inside the threat's guard zone
+const AI_MARK_DEPTH: f32 = 20.0;
+//actor going for a ball the opponent reaches first stops this deep inside the opponent's guard zone
+const AI_LOOSE_BALL_DEPTH: f32 = 10.0;
+//goal defender holds position in front of own goal
+const AI_GOAL_GUARD_DISTANCE: f32 = 80.0;
+//actor this close to its target is already there
+const AI_TARGET_TOLERANCE: f32 = 1.0;
+//pass has to gain this much more ground than the carrier's run, so the ball isn't thrown around for nothing
+const AI_PASS_MARGIN: f32 = 20.0;
+//intercepted pass costs as much as losing this much ground
+const AI_TURNOVER_COST: f32 = 100.0;
+pub const DEFAULT_OFFENSE_FOCUS: AiFocus = AiFocus::GuardBallCarrier;
+pub const DEFAULT_DEFENSE_FOCUS: AiFocus = AiFocus::DefendGoalPost;
 #[derive(Debug)]
 pub struct AiControlled {
     pub role: Option<AiRole>,
@@ -19,14 +53,19 @@ pub struct AiControlled {
     defense_focus: AiFocus,
 }
 impl AiControlled {
-    pub fn get_focus(&self, intent: &AiTeamIntent) -> AiFocus {
-        let mut rng = thread_rng();
+    pub fn get_focus(&self, intent: &AiTeamIntent, game_rng: &mut rng::GameRng) -> AiFocus {
         match intent {
             AiTeamIntent::Offense => self.offense_focus,
             AiTeamIntent::Defense => self.defense_focus,
-            AiTeamIntent::Undecided => *vec![self.offense_focus, self.defense_focus].iter().choose(&mut rng).unwrap()
+            AiTeamIntent::Undecided => *vec![self.offense_focus, self.defense_focus].iter().choose(&mut game_rng.0).unwrap()
         }
     }
+    pub fn get_offense_focus(&self) -> AiFocus {
+        self.offense_focus
+    }
+    pub fn get_defense_focus(&self) -> AiFocus {
+        self.defense_focus
+    }
     pub fn assign(&mut self, role: AiRole) {
         self.role = Some(role);
     }
@@ -52,11 +91,53 @@ impl AiControlled {
 #[derive(Debug, Clone, Copy)]
 pub enum AiRole {
     Move { x: f32, y: f32 },
-    Guard,
+    //runs to the target and tackles opposing ball carrier who gets in range
+    Tackle { x: f32, y: f32 },
+    //runs to the target and holds position there
+    Guard { x: f32, y: f32 },
+    Throw { x: f32, y: f32, pass_type: passing::PassType },
+}
+
+//task given to one AI actor by the team planner for the coming round
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum AiTask {
+    Score,
+    GetLooseBall,
+    InterceptCarrier,
+    MarkThreat(Entity),
+    SupportCarrier,
+    MoveOnWing,
+    GoForward,
+    DefendGoal,
+}
+impl AiTask {
+    //how many actors of one team can take the task, so they don't all run after the same thing
+    fn get_capacity(&self) -> usize {
+        match self {
+            AiTask::SupportCarrier | AiTask::MoveOnWing | AiTask::GoForward => 2,
+            _ => 1
+        }
+    }
+    fn get_focus_bonus(&self, focus: AiFocus) -> f32 {
+        match (self, focus) {
+            (AiTask::GetLooseBall, AiFocus::GetBall)
+            | (AiTask::InterceptCarrier, AiFocus::GetBall)
+            | (AiTask::InterceptCarrier, AiFocus::InterceptBallCarrier)
+            | (AiTask::GoForward, AiFocus::Score)
+            | (AiTask::GoForward, AiFocus::StayForward)
+            | (AiTask::SupportCarrier, AiFocus::GuardBallCarrier)
+            | (AiTask::MoveOnWing, AiFocus::MoveOnWings)
+            | (AiTask::DefendGoal, AiFocus::DefendGoalPost) => AI_FOCUS_BONUS,
+            //eager scorer goes for the loose ball too, goal defender marks opponents close to the goal
+            (AiTask::GetLooseBall, AiFocus::Score)
+            | (AiTask::MarkThreat(_), AiFocus::DefendGoalPost) => AI_FOCUS_BONUS / 2.0,
+            _ => 0.0
+        }
+    }
 }
 
 //TODO: some better mechanism to distinguish between offense/defense?
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum AiFocus {
     //always valid
     GetBall,
@@ -80,48 +161,113 @@ pub enum AiTeamIntent {
 
 #[derive(Debug, Clone, Copy)]
 struct ActorWithBall {
-    entity: Entity,
-    is_ai: bool,
+    is_own_team: bool,
     position: Vec2,
     target_position: Option<Vec2>
 }
 struct AiActorData {
     entity: Entity,
     position: Vec2,
-    role: Option<AiRole>,
+    focus: AiFocus,
     has_ball: bool,
+    run_distance: f32,
+    throw_range: f32,
+    catching: f32,
 }
-struct PlayerActorData {
+struct OpponentActorData {
     entity: Entity,
-    action: actor::ActorAction,
     position: Vec2,
-    has_ball: bool
+    has_ball: bool,
+    guard_radius: f32,
+}
+//what the whole team knows when planning the round
+struct TeamSituation {
+    intent: AiTeamIntent,
+    carrier: Option<ActorWithBall>,
+    loose_ball_position: Option<Vec2>,
+    own_goal_positions: Vec<Vec2>,
+    target_goal_positions: Vec<Vec2>,
+}
+impl TeamSituation {
+    //arena can have more goals per team, the one nearest to the action counts
+    fn get_own_goal(&self, position: Vec2) -> Vec2 {
+        get_nearest_position(&self.own_goal_positions, position)
+    }
+    fn get_target_goal(&self, position: Vec2) -> Vec2 {
+        get_nearest_position(&self.target_goal_positions, position)
+    }
+}
+struct AiCandidate {
+    actor_index: usize,
+    task: AiTask,
+    utility: f32,
+}
+struct PassOption {
+    receiver_index: usize,
+    position: Vec2,
+    pass_type: passing::PassType,
+    //ground gained towards the goal, weighted by the chance the pass gets there
+    value: f32,
 }
 
+//1.0 right at the target, half when it takes the whole round to get there
+fn get_closeness(distance: f32) -> f32 {
+    let reach = actor::PLAYER_RUN_SPEED * round::ROUND_TIME;
+    reach / (reach + distance)
+}
 
-fn get_closest_from_query(
-    ball_position: &Vec3,
-    query: &Query<(Entity, &Transform, Option<&AiControlled>, Option<&PlayerControlled>), With<actor::Actor>>,
-) -> ((Entity, f32), (Entity, f32)) {
-    let mut closest_ai_actor: Option<(Entity, f32)> = None;
-    let mut closest_player_actor: Option<(Entity, f32)> = None;
+//1.0 at the goal, 0.0 an arena width away from it
+fn get_goal_proximity(position: Vec2, goal_position: Vec2, arena: &arena::Arena) -> f32 {
+    (1.0 - position.distance(goal_position) / arena.width).max(0.0)
+}
 
-    for (entity, transform, ai_controlled, player_controller) in query.iter()  {
-        let distance = Vec2::from(transform.translation).distance(Vec2::from(*ball_position));
-        if ai_controlled.is_some() && (closest_ai_actor.is_none() || closest_ai_actor.unwrap().1 > distance) {
-            closest_ai_actor = Some((entity, distance));
-        }
-        if player_controller.is_some() && (closest_player_actor.is_none() || closest_player_actor.unwrap().1 > distance) {
-            closest_player_actor = Some((entity, distance));
-        }
-    }
-    (closest_ai_actor.unwrap(), closest_player_actor.unwrap())
+//arena layouts are checked to have a goal for each team
+fn get_nearest_position(positions: &[Vec2], position: Vec2) -> Vec2 {
+    positions
+        .iter()
+        .cloned()
+        .min_by(|a, b| a.distance(position).partial_cmp(&b.distance(position)).unwrap_or(Ordering::Equal))
+        .expect("Cannot get goalpost for AI!")
+}
+
+fn get_closest_opponent(position: Vec2, opponent_actors: &[OpponentActorData]) -> Option<&OpponentActorData> {
+    opponent_actors
+        .iter()
+        .min_by(|a, b| a.position.distance(position).partial_cmp(&b.position.distance(position)).unwrap_or(Ordering::Equal))
+}
+
+fn get_closest_opponent_distance(position: Vec2, opponent_actors: &[OpponentActorData]) -> f32 {
+    get_closest_opponent(position, opponent_actors).map_or(f32::INFINITY, |opponent| opponent.position.distance(position))
+}
+
+//opposing carrier is expected to run for the goal, interceptor cuts him off on the way
+fn get_intercept_position(carrier_position: Vec2, own_goal_position: Vec2) -> Vec2 {
+    let distance = carrier_position.distance(own_goal_position).min(actor::PLAYER_RUN_SPEED * round::ROUND_TIME);
+    carrier_position + actor::get_direction(carrier_position, own_goal_position) * distance
 }
 
-fn get_free_vector(ai_actor_position: &Vec2, player_actors: &Vec<PlayerActorData>, zone: &parry::shape::Ball, ray_direction: &Vector2<f32>) -> Option<Vec2> {
+fn get_mark_position(opponent: &OpponentActorData, own_goal_position: Vec2) -> Vec2 {
+    let distance = (opponent.guard_radius - AI_MARK_DEPTH).max(0.0);
+    opponent.position + actor::get_direction(opponent.position, own_goal_position) * distance
+}
+
+//goal closest to the carrier is in danger, without one the defender guards the goal closest to himself
+fn get_goal_guard_position(situation: &TeamSituation, position: Vec2) -> Vec2 {
+    let own_goal_position = situation.get_own_goal(situation.carrier.map_or(position, |carrier| carrier.position));
+    own_goal_position + actor::get_direction(own_goal_position, situation.get_target_goal(own_goal_position)) * AI_GOAL_GUARD_DISTANCE
+}
+
+fn get_wing_distance(position: Vec2, arena: &arena::Arena) -> f32 {
+    let top_wing = arena.top - AI_WING_MARGIN / 2.0;
+    let bottom_wing = arena.bottom + AI_WING_MARGIN / 2.0;
+    (top_wing - position.y).abs().min((position.y - bottom_wing).abs())
+}
+
+fn get_free_vector(ai_actor_position: &Vec2, opponent_actors: &[OpponentActorData], ray_direction: &Vector2<f32>) -> Option<Vec2> {
     let ray = Ray::new(Point2::new(ai_actor_position.x, ai_actor_position.y), *ray_direction);
-    let blocked = player_actors.iter().any(|player_actor_data| {
-        let transform = Isometry2::new(Vector2::new(player_actor_data.position.x, player_actor_data.position.y), 0.0);
+    let blocked = opponent_actors.iter().any(|opponent_actor_data| {
+        let zone = parry::shape::Ball::new(opponent_actor_data.guard_radius);
+        let transform = Isometry2::new(Vector2::new(opponent_actor_data.position.x, opponent_actor_data.position.y), 0.0);
         zone.intersects_ray(&transform, &ray, round::ROUND_TIME)
     });
 
@@ -132,164 +278,426 @@ fn get_free_vector(ai_actor_position: &Vec2, player_actors: &Vec<PlayerActorData
     }
 }
 
+//straight line to the target first, then gradually deviating to both sides
+fn get_search_angles(from: Vec2, target_position: Vec2) -> Vec<f32> {
+    let start_angle = (target_position.y - from.y).atan2(target_position.x - from.x);
+    let mut angles = vec![start_angle];
+    let mut total_increment = AI_RAY_STEP;
+    while total_increment < FRAC_PI_2 {
+        angles.push(start_angle + total_increment);
+        angles.push(start_angle - total_increment);
+        total_increment += AI_RAY_STEP;
+    }
+    angles
+}
+
+//all places the actor can run to within the round without crossing opponents' guard zones
+fn get_free_movements(ai_actor_data: &AiActorData, target_position: Vec2, opponent_actors: &[OpponentActorData]) -> Vec<Vec2> {
+    let distance = ai_actor_data.position.distance(target_position).min(ai_actor_data.run_distance);
+    if distance == 0.0 {
+        return vec![];
+    }
+    get_search_angles(ai_actor_data.position, target_position)
+        .into_iter()
+        .filter_map(|angle| {
+            let ray_direction = get_rotated_vector(angle).normalize() * (distance / round::ROUND_TIME);
+            get_free_vector(&ai_actor_data.position, opponent_actors, &ray_direction)
+        })
+        .map(|direction| ai_actor_data.position + direction * distance)
+        .collect()
+}
+
+//the most direct free way to the target
+fn get_free_movement(ai_actor_data: &AiActorData, target_position: Vec2, opponent_actors: &[OpponentActorData]) -> Option<Vec2> {
+    get_free_movements(ai_actor_data, target_position, opponent_actors).into_iter().next()
+}
+
+//chance that an opponent whose guard zone covers the catchable part of the flight takes the ball
+fn get_lane_risk(from: Vec2, to: Vec2, pass_type: passing::PassType, opponent_actors: &[OpponentActorData]) -> f32 {
+    let start = from + (to - from) * passing::get_catchable_start(pass_type);
+    let ray = Ray::new(Point2::new(start.x, start.y), Vector2::new(to.x - start.x, to.y - start.y));
+    let distance = from.distance(to);
+    let safe_chance: f32 = opponent_actors
+        .iter()
+        .filter(|opponent_actor_data| {
+            let zone = parry::shape::Ball::new(opponent_actor_data.guard_radius);
+            let transform = Isometry2::new(Vector2::new(opponent_actor_data.position.x, opponent_actor_data.position.y), 0.0);
+            zone.intersects_ray(&transform, &ray, 1.0)
+        })
+        .map(|_opponent_actor_data| 1.0 - passing::get_catch_chance(pass_type, distance, true, 0, 1.0))
+        .product();
+    1.0 - safe_chance
+}
+
+//receiver either stays or runs into a free spot, the ball is thrown where he will be
+fn get_best_pass(carrier: &AiActorData, ai_actors: &[AiActorData], situation: &TeamSituation, opponent_actors: &[OpponentActorData]) -> Option<PassOption> {
+    let mut best_pass: Option<PassOption> = None;
+    //progress is measured to the goal the carrier goes for
+    let target_goal_position = situation.get_target_goal(carrier.position);
+    for (receiver_index, receiver) in ai_actors.iter().enumerate().filter(|(_receiver_index, receiver)| !receiver.has_ball) {
+        let spots = std::iter::once(receiver.position).chain(get_free_movements(receiver, target_goal_position, opponent_actors));
+        for spot in spots {
+            let distance = carrier.position.distance(spot);
+            let progress = carrier.position.distance(target_goal_position) - spot.distance(target_goal_position);
+            if progress <= 0.0 {
+                continue;
+            }
+            let run_time = receiver.position.distance(spot) / receiver.run_distance * round::ROUND_TIME;
+            let pressure = opponent_actors.iter().filter(|opponent_actor_data| opponent_actor_data.position.distance(spot) <= passing::PRESSURE_RADIUS).count();
+            for pass_type in [passing::PassType::Lob, passing::PassType::Bullet].iter() {
+                let pass_type = *pass_type;
+                //receiver has to get there before the ball
+                if distance > passing::get_pass_range(pass_type, carrier.throw_range)
+                    || run_time > actor::PLAYER_THROWING_TIME + distance / passing::get_pass_speed(pass_type) {
+                    continue;
+                }
+                let risk = get_lane_risk(carrier.position, spot, pass_type, opponent_actors);
+                let catch_chance = passing::get_catch_chance(pass_type, distance, false, pressure, receiver.catching);
+                let value = progress * catch_chance * (1.0 - risk) - risk * AI_TURNOVER_COST;
+                if best_pass.as_ref().map_or(true, |best_pass| value > best_pass.value) {
+                    best_pass = Some(PassOption { receiver_index, position: spot, pass_type, value });
+                }
+            }
+        }
+    }
+    best_pass
+}
+
+//carrier throws when a teammate in an open lane gets the ball further than he can run,
+//the others leave lanes blocked by opponents, so they can get the ball next turn
+fn plan_passes(roles: &mut [Option<AiRole>], ai_actors: &[AiActorData], situation: &TeamSituation, opponent_actors: &[OpponentActorData]) {
+    let carrier_index = match ai_actors.iter().position(|ai_actor_data| ai_actor_data.has_ball) {
+        Some(carrier_index) => carrier_index,
+        None => return
+    };
+    let carrier = &ai_actors[carrier_index];
+    let run_target = match roles[carrier_index] {
+        Some(AiRole::Move { x, y }) => Vec2::new(x, y),
+        _ => carrier.position
+    };
+    let target_goal_position = situation.get_target_goal(carrier.position);
+    let run_value = carrier.position.distance(target_goal_position) - run_target.distance(target_goal_position);
+
+    if let Some(pass) = get_best_pass(carrier, ai_actors, situation, opponent_actors) {
+        if pass.value > run_value + AI_PASS_MARGIN {
+            roles[carrier_index] = Some(AiRole::Throw { x: pass.position.x, y: pass.position.y, pass_type: pass.pass_type });
+            roles[pass.receiver_index] = Some(AiRole::Move { x: pass.position.x, y: pass.position.y });
+            return;
+        }
+    }
+
+    for (receiver_index, receiver) in ai_actors.iter().enumerate() {
+        let target_position = match roles[receiver_index] {
+            Some(AiRole::Move { x, y }) if !receiver.has_ball => Vec2::new(x, y),
+            _ => continue
+        };
+        if get_lane_risk(run_target, target_position, passing::PassType::Bullet, opponent_actors) == 0.0 {
+            continue;
+        }
+        let open_spot = get_free_movements(receiver, target_position, opponent_actors)
+            .into_iter()
+            .find(|spot| get_lane_risk(run_target, *spot, passing::PassType::Bullet, opponent_actors) == 0.0);
+        if let Some(spot) = open_spot {
+            roles[receiver_index] = Some(AiRole::Move { x: spot.x, y: spot.y });
+        }
+    }
+}
+
 fn get_ai_team_intent(actor_with_ball: Option<ActorWithBall>) -> AiTeamIntent {
     if actor_with_ball.is_none() {
         return AiTeamIntent::Undecided;
     }
     let actor_with_ball = actor_with_ball.unwrap();
-    if actor_with_ball.is_ai {
+    if actor_with_ball.is_own_team {
         AiTeamIntent::Offense
     } else {
         AiTeamIntent::Defense
     }
 }
 
+fn get_goal_post_positions(query_goal_posts: &Query<(&Transform, &arena::GoalPost)>, team: team::Team) -> Vec<Vec2> {
+    query_goal_posts
+        .iter()
+        .filter(|(_transform, goal_post)| goal_post.team == team && goal_post.kind == arena::GoalKind::Goal)
+        .map(|(transform, _goal_post)| Vec2::from(transform.translation))
+        .collect()
+}
+
+//every task the actor could take this round with its utility for the team
+fn get_candidates(
+    actor_index: usize,
+    ai_actor_data: &AiActorData,
+    situation: &TeamSituation,
+    opponent_actors: &[OpponentActorData],
+    arena: &arena::Arena,
+) -> Vec<AiCandidate> {
+    let mut tasks: Vec<(AiTask, f32)> = vec![];
+    if ai_actor_data.has_ball {
+        tasks.push((AiTask::Score, AI_SCORE_UTILITY));
+    } else {
+        if let Some(ball_position) = situation.loose_ball_position {
+            let distance = ai_actor_data.position.distance(ball_position);
+            let opponent_distance = get_closest_opponent_distance(ball_position, opponent_actors);
+            let race = if distance < opponent_distance { 1.0 } else { 0.5 };
+            tasks.push((AiTask::GetLooseBall, AI_LOOSE_BALL_UTILITY * race * get_closeness(distance)));
+        }
+        match situation.carrier {
+            Some(carrier) if carrier.is_own_team => {
+                tasks.push((AiTask::SupportCarrier, AI_SUPPORT_UTILITY * get_closeness(ai_actor_data.position.distance(carrier.position))));
+                tasks.push((AiTask::MoveOnWing, AI_WING_UTILITY * get_closeness(get_wing_distance(ai_actor_data.position, arena))));
+            },
+            Some(carrier) => {
+                let intercept_position = get_intercept_position(carrier.position, situation.get_own_goal(carrier.position));
+                tasks.push((AiTask::InterceptCarrier, AI_INTERCEPT_UTILITY * get_closeness(ai_actor_data.position.distance(intercept_position))));
+            },
+            None => ()
+        }
+        if !matches!(situation.intent, AiTeamIntent::Offense) {
+            //opponents close to own goal are the most dangerous receivers
+            for opponent in opponent_actors.iter().filter(|opponent| !opponent.has_ball) {
+                let threat = get_goal_proximity(opponent.position, situation.get_own_goal(opponent.position), arena);
+                tasks.push((AiTask::MarkThreat(opponent.entity), AI_MARK_UTILITY * threat * get_closeness(ai_actor_data.position.distance(opponent.position))));
+            }
+            tasks.push((AiTask::DefendGoal, AI_DEFEND_GOAL_UTILITY * get_closeness(ai_actor_data.position.distance(get_goal_guard_position(situation, ai_actor_data.position)))));
+        }
+        tasks.push((AiTask::GoForward, AI_FORWARD_UTILITY * get_goal_proximity(ai_actor_data.position, situation.get_target_goal(ai_actor_data.position), arena)));
+    }
+    tasks
+        .into_iter()
+        .map(|(task, utility)| AiCandidate { actor_index, task, utility: utility + task.get_focus_bonus(ai_actor_data.focus) })
+        .collect()
+}
+
+//the best candidates win, each actor gets one task and each task is taken only by as many actors as it needs
+fn assign_tasks(mut candidates: Vec<AiCandidate>) -> Vec<(usize, AiTask)> {
+    //sort is stable, so equal utilities keep the order of actors and the plan stays deterministic
+    candidates.sort_by(|a, b| b.utility.partial_cmp(&a.utility).unwrap_or(Ordering::Equal));
+    let mut assignments: Vec<(usize, AiTask)> = vec![];
+    for candidate in candidates.iter() {
+        let is_assigned = assignments.iter().any(|(actor_index, _task)| *actor_index == candidate.actor_index);
+        let taken = assignments.iter().filter(|(_actor_index, task)| *task == candidate.task).count();
+        if !is_assigned && taken < candidate.task.get_capacity() {
+            assignments.push((candidate.actor_index, candidate.task));
+        }
+    }
+    //more actors than tasks, the rest does what suits it best
+    for candidate in candidates.iter() {
+        if !assignments.iter().any(|(actor_index, _task)| *actor_index == candidate.actor_index) {
+            assignments.push((candidate.actor_index, candidate.task));
+        }
+    }
+    assignments
+}
+
 pub fn process_ai(
     mut commands: Commands,
-    helper_materials: Res<helpers::HelperMaterials>,
+    helper_materials: Option<Res<helpers::HelperMaterials>>,
     mut query_actors: QuerySet<(
-        Query<(Entity, &Transform, Option<&AiControlled>, Option<&PlayerControlled>), With<actor::Actor>>,
-        Query<(Entity, &mut actor::Actor, &Transform, &mut AiControlled)>,
-        Query<(Entity, &actor::Actor, &Transform), With<PlayerControlled>>,
-        Query<(&actor::Actor, &Transform, &AiControlled)>,
+        Query<(&team::Team, Option<&AiControlled>), With<actor::Actor>>,
+        Query<(Entity, &Transform, &mut AiControlled, &team::Team, &actor::ActorStats, &injury::Health, Option<&referee::SinBin>), With<actor::Actor>>,
+        Query<(Entity, &Transform, &team::Team, &actor::ActorStats, &injury::Health, Option<&referee::SinBin>), With<actor::Actor>>,
     )>,
     query_ball: Query<&Transform, With<ball::Ball>>,
-    query_goal_posts: QuerySet<(
-        Query<&Transform, (With<arena::GoalPost>, With<AiControlled>)>,
-        Query<&Transform, (With<arena::GoalPost>, With<PlayerControlled>)>,
-    )>,
+    query_goal_posts: Query<(&Transform, &arena::GoalPost)>,
     ball_possession: Res<ball::BallPossession>,
     arena: Res<arena::Arena>,
+    mut game_rng: ResMut<rng::GameRng>,
+    mut event_orders: EventWriter<actor::ActorOrderEvent>,
 ) {
-    //TOOD data structures for actor carrying ball and ai team intent are not atomic - this needs to be looked at
-    let mut rng = thread_rng();
-
-    let mut actor_with_ball: Cell<Option<ActorWithBall>> = Cell::new(None);
-    let ball_transform = query_ball.single();
-    let player_goalpost_position = Vec2::from(query_goal_posts.q1().single().expect("Cannot get player_goalpost!").translation);
-    let ai_goalpost_position = Vec2::from(query_goal_posts.q0().single().expect("Cannot get player_goalpost!").translation);
-
-    if ball_possession.is_free() && ball_transform.is_ok() && actor_with_ball.get().is_none() {
-        let ball_position = ball_transform.unwrap().translation;
-        let (
-            (closest_ai_entity, closest_ai_distance),
-            (_closest_player_entity, closest_player_distance)
-        ) = get_closest_from_query(&ball_position, query_actors.q0());
-        let closest_ai_guard_distance = closest_ai_distance - (actor::PLAYER_GUARD_RADIUS - 10.0);
-
-        let (_entity, mut _actor, transform, mut ai) = query_actors.q1_mut().get_mut(closest_ai_entity).unwrap();
-        // println!("Distance AI and Player => {} < {}", closest_ai_distance, closest_player_distance);
-        if closest_ai_distance < closest_player_distance {
-            //WOULD TAKE THE BALL FIRST
-            ai.assign(AiRole::Move { x: ball_position.x, y: ball_position.y });
-            actor_with_ball.set(Some(ActorWithBall {
-                entity: closest_ai_entity,
-                is_ai: true,
-                position: Vec2::from(transform.translation),
-                target_position: Some(Vec2::new(ball_position.x, ball_position.y))
-            }));
-        } else if closest_ai_guard_distance < closest_player_distance {
-            //WILL BE IN GUARD DISTANCE AT END OF THE ROUND
-            let ai_position = Vec2::from(transform.translation);
-            let ratio = closest_ai_guard_distance / closest_ai_distance;
-            let position = ((Vec2::from(ball_position) - ai_position) * ratio) + ai_position;
-            ai.assign(AiRole::Move { x: position.x, y: position.y });
+    //each team that has at least one AI actor is planned separately, so AI can play against player or against another AI
+    for ai_team in [team::Team::Home, team::Team::Away].iter() {
+        let ai_team = *ai_team;
+        let has_ai_actors = query_actors.q0().iter().any(|(team, ai_controlled)| *team == ai_team && ai_controlled.is_some());
+        if !has_ai_actors {
+            continue;
         }
+        process_ai_team(ai_team, &mut query_actors, &query_ball, &query_goal_posts, &ball_possession, &arena, &mut game_rng);
     }
 
-    let mut ai_actors: Vec<AiActorData> = query_actors
-        .q1_mut()
-        .iter_mut()
-        .map(|(entity, _actor, transform, ai)| -> AiActorData {
-            let mut has_ball = false;
-            if let Some(entity_with_ball) = ball_possession.get() {
-                has_ball = entity_with_ball == entity;
-            }
-            if actor_with_ball.get().is_some() && has_ball {
-                panic!("Actor {:?} has ball already - this shouldn't happen!", actor_with_ball.get().unwrap().entity);
-            }
-            let target_position = if let Some(ai_role) = ai.role {
-                match ai_role {
-                    AiRole::Move { x, y } => Some(Vec2::new(x, y)),
-                    _ => None
+    for (entity, transform, ai, _team, stats, _health, _sin_bin) in query_actors.q1_mut().iter_mut() {
+        let role = match ai.role {
+            Some(role) => role,
+            None => continue
+        };
+        let position = Vec2::from(transform.translation);
+        let (target, htype) = match role {
+            AiRole::Move { x, y } | AiRole::Tackle { x, y } | AiRole::Guard { x, y } => {
+                //receiver staying where he is only waits for the ball, there is nowhere to run
+                let is_running = position.distance(Vec2::new(x, y)) > AI_TARGET_TOLERANCE;
+                if is_running {
+                    event_orders.send(actor::ActorOrderEvent {
+                        entity,
+                        order: actor::ActorOrder::Set(actor::ActorAction::Running { x, y }),
+                    });
+                }
+                match role {
+                    AiRole::Tackle { x: _, y: _ } => event_orders.send(actor::ActorOrderEvent {
+                        entity,
+                        order: actor::ActorOrder::Conditional(actor::ConditionalOrder {
+                            condition: actor::ActorCondition::CarrierInRange(stats.get_tackle_radius()),
+                            reaction: actor::ActorReaction::TackleCarrier,
+                        }),
+                    }),
+                    AiRole::Guard { x: _, y: _ } => event_orders.send(actor::ActorOrderEvent {
+                        entity,
+                        order: if is_running {
+                            actor::ActorOrder::Queue(actor::ActorAction::HoldPosition { x, y })
+                        } else {
+                            actor::ActorOrder::Set(actor::ActorAction::HoldPosition { x, y })
+                        },
+                    }),
+                    _ => ()
+                };
+                if !is_running {
+                    continue;
                 }
-            } else {
-                None
-            };
-            if has_ball {
-                actor_with_ball.set(Some(ActorWithBall {
+                (Vec2::new(x, y), helpers::HelperType::Run)
+            },
+            AiRole::Throw { x, y, pass_type } => {
+                event_orders.send(actor::ActorOrderEvent {
                     entity,
-                    is_ai: true,
-                    position: Vec2::from(transform.translation),
-                    target_position
-                }));
-            }
-            AiActorData {
-                entity,
-                position: Vec2::from(transform.translation),
-                role: ai.role,
-                has_ball,
-            }
-        })
-        .collect();
-    let player_actors: Vec<PlayerActorData> = query_actors
+                    order: actor::ActorOrder::Set(actor::ActorAction::Throwing { x, y, pass_type }),
+                });
+                (passing::clamp_to_range(position, Vec2::new(x, y), pass_type, stats.get_throw_range()), helpers::HelperType::Throw)
+            },
+        };
+
+        let helper_materials = match helper_materials.as_ref() {
+            Some(hm) => hm,
+            None => continue
+        };
+        let he = helpers::spawn_movement_helper(
+            &mut commands,
+            helper_materials,
+            target,
+            Vec2::new(transform.translation.x, transform.translation.y),
+            entity.clone(),
+            htype
+        );
+        commands.entity(he).insert(AiControlled::default());
+    }
+}
+
+//scores tasks for all actors of the team and gives them complementary roles, e.g. one intercepts the carrier while others mark receivers
+fn process_ai_team(
+    ai_team: team::Team,
+    query_actors: &mut QuerySet<(
+        Query<(&team::Team, Option<&AiControlled>), With<actor::Actor>>,
+        Query<(Entity, &Transform, &mut AiControlled, &team::Team, &actor::ActorStats, &injury::Health, Option<&referee::SinBin>), With<actor::Actor>>,
+        Query<(Entity, &Transform, &team::Team, &actor::ActorStats, &injury::Health, Option<&referee::SinBin>), With<actor::Actor>>,
+    )>,
+    query_ball: &Query<&Transform, With<ball::Ball>>,
+    query_goal_posts: &Query<(&Transform, &arena::GoalPost)>,
+    ball_possession: &Res<ball::BallPossession>,
+    arena: &Res<arena::Arena>,
+    game_rng: &mut rng::GameRng,
+) {
+    //injured and sent off actors lie down for the whole round, they neither take tasks nor threaten anybody
+    let opponent_actors: Vec<OpponentActorData> = query_actors
         .q2()
         .iter()
-        .map(|(entity, actor, transform)| -> PlayerActorData {
-            let mut has_ball = false;
-            if let Some(entity_with_ball) = ball_possession.get() {
-                has_ball = entity_with_ball == entity;
-            }
-            if actor_with_ball.get().is_some() && has_ball {
-                panic!("Actor {:?} has ball already - this shouldn't happen!", actor_with_ball.get().unwrap().entity);
-            }
-            if has_ball {
-                actor_with_ball.set(Some(ActorWithBall {
-                    entity,
-                    is_ai: false,
-                    position: Vec2::from(transform.translation),
-                    target_position: None
-                }));
-            }
-            PlayerActorData {
-                entity,
-                action: actor.act_action,
-                position: Vec2::from(transform.translation),
-                has_ball
-            }
+        .filter(|(_entity, _transform, team, _stats, health, sin_bin)| **team != ai_team && !health.is_injured() && sin_bin.is_none())
+        .map(|(entity, transform, _team, stats, _health, _sin_bin)| OpponentActorData {
+            entity,
+            position: Vec2::from(transform.translation),
+            has_ball: ball_possession.get() == Some(entity),
+            guard_radius: stats.get_guard_radius(),
+        })
+        .collect();
+    let carrier = ball_possession.get()
+        .and_then(|carrier_entity| query_actors.q2().get(carrier_entity).ok())
+        .map(|(_entity, transform, team, _stats, _health, _sin_bin)| ActorWithBall {
+            is_own_team: *team == ai_team,
+            position: Vec2::from(transform.translation),
+            target_position: None,
+        });
+    let loose_ball_position = match query_ball.single() {
+        Ok(ball_transform) if ball_possession.is_free() => Some(Vec2::from(ball_transform.translation)),
+        _ => None
+    };
+    let mut situation = TeamSituation {
+        intent: get_ai_team_intent(carrier),
+        carrier,
+        loose_ball_position,
+        own_goal_positions: get_goal_post_positions(query_goal_posts, ai_team),
+        target_goal_positions: get_goal_post_positions(query_goal_posts, team::get_oposing_team(ai_team)),
+    };
+    let ai_actors: Vec<AiActorData> = query_actors
+        .q1_mut()
+        .iter_mut()
+        .filter(|(_entity, _transform, _ai, team, _stats, health, sin_bin)| **team == ai_team && !health.is_injured() && sin_bin.is_none())
+        .map(|(entity, transform, ai, _team, stats, _health, _sin_bin)| AiActorData {
+            entity,
+            position: Vec2::from(transform.translation),
+            focus: ai.get_focus(&situation.intent, game_rng),
+            has_ball: ball_possession.get() == Some(entity),
+            run_distance: stats.get_run_speed() * round::ROUND_TIME,
+            throw_range: stats.get_throw_range(),
+            catching: stats.catching,
         })
         .collect();
-    ai_actors.sort_by(|a, b| {
-        if a.role.is_some() && b.role.is_none() {
-            return Ordering::Greater;
-        }
-        if a.role.is_none() && b.role.is_some() {
-            return Ordering::Less;
-        }
-        return Ordering::Equal;
-    });
-    let ai_team_intent = get_ai_team_intent(actor_with_ball.get());
-    let default_target_position = player_goalpost_position;
-    // println!("AI team intent is {:?} -> actor with ball is {:?}", ai_team_intent, actor_with_ball.get());
-    //raytracing, start with straight line and gruadually deviate by some margin, find suitable vector
-    //this works lot better, but need to somehow figure out how to steer actor to center of net
-    //(i.e. dont start with 0 rotation but with rotation based on goal post center and then work around that)
-    //instead of goal post center it can be also another target, e.g. some wing position or position near ball carrier
-
-
-    for ai_actor_data in ai_actors.iter() {
-        let signum = (player_goalpost_position.x - ai_actor_data.position.x).signum();
-        if ai_actor_data.role.is_some() {
-            continue;
-        }
 
-        //TODO now assign and target positions based on focus, which is based on team intent
-        let ai = query_actors.q1_mut().get_component_mut::<AiControlled> (ai_actor_data.entity).expect("Cannot get AI actor!");
-        let focus = if ai_actor_data.has_ball { AiFocus::Score } else { ai.get_focus(&ai_team_intent) };
-        let target_position = match focus {
-            //offsense
-            AiFocus::MoveOnWings => {
-                //TODO handle state when closing to players goalpost
+    let candidates: Vec<AiCandidate> = ai_actors
+        .iter()
+        .enumerate()
+        .flat_map(|(actor_index, ai_actor_data)| get_candidates(actor_index, ai_actor_data, &situation, &opponent_actors, arena))
+        .collect();
+    let mut roles: Vec<Option<AiRole>> = vec![None; ai_actors.len()];
+    //carrier has the highest utility, so his target is known before supporting actors pick theirs
+    for (actor_index, task) in assign_tasks(candidates) {
+        let ai_actor_data = &ai_actors[actor_index];
+        let target_goal_position = situation.get_target_goal(ai_actor_data.position);
+        let role = match task {
+            AiTask::Score => {
+                let target_position = get_free_movement(ai_actor_data, target_goal_position, &opponent_actors);
+                if let Some(carrier) = situation.carrier.as_mut() {
+                    carrier.target_position = target_position;
+                }
+                target_position.map(|target_position| AiRole::Move { x: target_position.x, y: target_position.y })
+            },
+            AiTask::GetLooseBall => situation.loose_ball_position.map(|ball_position| {
+                let distance = ai_actor_data.position.distance(ball_position);
+                match get_closest_opponent(ball_position, &opponent_actors) {
+                    //opponent takes the ball first, so stop within his guard distance and tackle him
+                    Some(opponent) if opponent.position.distance(ball_position) <= distance => {
+                        let guard_distance = (distance - (opponent.guard_radius - AI_LOOSE_BALL_DEPTH)).max(0.0);
+                        let position = ai_actor_data.position + actor::get_direction(ai_actor_data.position, ball_position) * guard_distance;
+                        AiRole::Tackle { x: position.x, y: position.y }
+                    },
+                    _ => AiRole::Move { x: ball_position.x, y: ball_position.y }
+                }
+            }),
+            AiTask::InterceptCarrier => situation.carrier.map(|carrier| {
+                let position = get_intercept_position(carrier.position, situation.get_own_goal(carrier.position));
+                AiRole::Tackle { x: position.x, y: position.y }
+            }),
+            AiTask::MarkThreat(opponent_entity) => opponent_actors
+                .iter()
+                .find(|opponent| opponent.entity == opponent_entity)
+                .map(|opponent| {
+                    let position = get_mark_position(opponent, situation.get_own_goal(opponent.position));
+                    AiRole::Tackle { x: position.x, y: position.y }
+                }),
+            AiTask::SupportCarrier => {
+                let target_position = match situation.carrier {
+                    Some(carrier) => match carrier.target_position {
+                        Some(tp) => {
+                            let signum_x = (ai_actor_data.position.x - target_goal_position.x).signum();
+                            let signum_y = (ai_actor_data.position.y - tp.y).signum();
+
+                            let offset_x = game_rng.0.gen_range(0.0..20.0) * signum_x;
+                            let offset_y = game_rng.0.gen_range(30.0..60.0) * signum_y;
+
+                            Vec2::new(tp.x + offset_x, tp.y + offset_y)
+                        },
+                        None => carrier.position
+                    },
+                    None => target_goal_position
+                };
+                get_free_movement(ai_actor_data, target_position, &opponent_actors).map(|p| AiRole::Move { x: p.x, y: p.y })
+            },
+            AiTask::MoveOnWing => {
+                let signum = (target_goal_position.x - ai_actor_data.position.x).signum();
                 let distance_to_top = (arena.top - ai_actor_data.position.y).abs();
                 let distance_to_bottom = (arena.bottom - ai_actor_data.position.y).abs();
                 let (y_min, y_max) = if distance_to_top <= distance_to_bottom {
@@ -297,98 +705,31 @@ pub fn process_ai(
                 } else {
                     (arena.bottom, arena.bottom+AI_WING_MARGIN)
                 };
-                Vec2::new(ai_actor_data.position.x + AI_FORWARD_MOMENTUM*signum, rng.gen_range(y_min..y_max))
-            }
-            AiFocus::GuardBallCarrier => {
-                if let Some(bc) = actor_with_ball.get() {
-                    if let Some(tp) = bc.target_position {
-                        let signum_x = (ai_actor_data.position.x - player_goalpost_position.x ).signum();
-                        let signum_y = (ai_actor_data.position.y - tp.y).signum();
-
-                        let offset_x  = rng.gen_range(0.0..20.0) * signum_x;
-                        let offset_y = rng.gen_range(30.0..60.0) * signum_y;
-
-                        Vec2::new(tp.x + offset_x, tp.y + offset_y)
-                    } else {
-                        bc.position
-                    }
+                //wing runner close to the goal line turns in towards the goal
+                let target_position = if (target_goal_position.x - ai_actor_data.position.x).abs() < AI_FORWARD_MOMENTUM {
+                    target_goal_position
                 } else {
-                    default_target_position
-                }
+                    Vec2::new(ai_actor_data.position.x + AI_FORWARD_MOMENTUM*signum, game_rng.0.gen_range(y_min..y_max))
+                };
+                get_free_movement(ai_actor_data, target_position, &opponent_actors).map(|p| AiRole::Move { x: p.x, y: p.y })
+            },
+            AiTask::GoForward => get_free_movement(ai_actor_data, target_goal_position, &opponent_actors).map(|p| AiRole::Move { x: p.x, y: p.y }),
+            AiTask::DefendGoal => {
+                let position = get_goal_guard_position(&situation, ai_actor_data.position);
+                Some(AiRole::Guard { x: position.x, y: position.y })
             },
-            AiFocus::Score => player_goalpost_position,
-            //defense
-            AiFocus::StayForward => default_target_position,
-            AiFocus::DefendGoalPost => ai_goalpost_position,
-            AiFocus::InterceptBallCarrier => default_target_position,
-            _ => default_target_position
         };
-
-
-
-        let b = parry::shape::Ball::new(actor::PLAYER_GUARD_RADIUS); //sometimes ai ends in the player actor guard range regardless so add little bit leaway
-        // let signum = (player_goalpost_position.x - ai_actor_data.position.x).signum();
-
-        let mut chosen_movement: Option<Vec2> = None;
-        let step = 0.1;
-        let start_angle = (target_position.y - ai_actor_data.position.y).atan2(target_position.x -  ai_actor_data.position.x);
-        let mut total_increment = 0.0;
-
-        while total_increment < f32::consts::FRAC_PI_2 && chosen_movement.is_none() {
-            let ray_direction = get_rotated_vector(start_angle + total_increment).normalize() * (actor::PLAYER_RUN_SPEED / round::ROUND_TIME);
-            chosen_movement = get_free_vector(&ai_actor_data.position, &player_actors, &b, &ray_direction);
-
-            if chosen_movement.is_none() && total_increment != 0.0 {
-                let ray_direction = get_rotated_vector(-(start_angle + total_increment)).normalize() * (actor::PLAYER_RUN_SPEED / round::ROUND_TIME);
-                chosen_movement = get_free_vector(&ai_actor_data.position, &player_actors, &b, &ray_direction);
-            }
-            total_increment += step;
-        }
-
-        if let Some(chm) = chosen_movement {
-            //in this vector, find one where we don't go to much away from goalpost (e.g. too much up or down)
-            let chm = ai_actor_data.position + (chm * actor::PLAYER_RUN_SPEED);
-            if let Ok(mut ai) = query_actors.q1_mut().get_component_mut::<AiControlled> (ai_actor_data.entity) {
-                ai.role = Some(AiRole::Move { x: chm.x, y: chm.y });
-                if let Some(bc) = actor_with_ball.get_mut() {
-                    if bc.entity == ai_actor_data.entity {
-                        bc.target_position = Some(Vec2::new(chm.x, chm.y));
-                    }
-                }
-            }
-        }
+        roles[actor_index] = role;
     }
+    plan_passes(&mut roles, &ai_actors, &situation, &opponent_actors);
 
-    for (entity, mut actor, transform, ai) in query_actors.q1_mut().iter_mut() {
-        if let Some(role) = ai.role {
-            match role {
-                AiRole::Move { x, y } => {
-                    actor.set_action(actor::ActorAction::Running { x, y });
-                },
-                AiRole::Guard => ()
-            };
-        }
-
-        match actor.act_action {
-            actor::ActorAction::Running { x, y } => {
-                let he = helpers::spawn_movement_helper(
-                    &mut commands,
-                    &helper_materials,
-                    Vec2::new(x, y),
-                    Vec2::new(transform.translation.x, transform.translation.y),
-                    entity.clone(),
-                    helpers::HelperType::Run
-                );
-                commands.entity(he).insert(AiControlled::default());
+    for (ai_actor_data, role) in ai_actors.iter().zip(roles.into_iter()) {
+        if let Some(role) = role {
+            if let Ok(mut ai) = query_actors.q1_mut().get_component_mut::<AiControlled>(ai_actor_data.entity) {
+                ai.assign(role);
             }
-            _ => ()
-        };
+        }
     }
-
-    //what about throws? if we somehow determine that it would be benefical to throw then throw
-    //(by comparing movements across ai actors, if there is possibility that some other ai actor would be able to move more forward, then pass the ball)
-    //problem with throws is unlimited range (= implement range on throws, and/or moving idle actors to intercept ball)
-    //or don't allow to score with throw (but this isn't probably good idea)
 }
 
 pub fn reset_ai_roles(
@@ -397,4 +738,20 @@ pub fn reset_ai_roles(
     for mut ai in query.iter_mut() {
         ai.reset();
     }
-}
\ No newline at end of file
+}
+
+impl Plugin for AiPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .add_system_set(
+                SystemSet::on_enter(states::AppState::Plan)
+                    .with_system(process_ai.system()
+                        .label(AiLabel::Process)
+                    )
+            )
+            .add_system_set(
+                SystemSet::on_exit(states::AppState::Play)
+                    .with_system(reset_ai_roles.system())
+            );
+    }
+}
@@ -1,5 +1,13 @@
 use bevy::prelude::*;
 use std::cmp;
+use super::clock;
+
+pub struct AnimationPlugin;
+
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum AnimationLabel {
+    Animate,
+}
 
 pub struct AnimationTimer(pub Timer);
 pub struct Animation {
@@ -47,14 +55,23 @@ impl Animation {
 }
 
 pub fn animate_sprite(
-    time: Res<Time>,
-    mut query: Query<(&mut AnimationTimer, &mut TextureAtlasSprite, &mut Animation)>,
+    clock: Res<clock::GameClock>,
+    mut query: Query<(&mut AnimationTimer, Option<&mut TextureAtlasSprite>, &mut Animation)>,
 ) {
-    for (mut timer, mut sprite, mut animation) in query.iter_mut() {
-        timer.0.tick(time.delta());
+    for (mut timer, sprite, mut animation) in query.iter_mut() {
+        timer.0.tick(clock.delta());
         if timer.0.finished() {
             animation.update();
-            sprite.index = animation.get_sprite_index();
+            //headless actors have no sprite, but action flow still depends on animation being finished
+            if let Some(mut sprite) = sprite {
+                sprite.index = animation.get_sprite_index();
+            }
         }
     }
-}
\ No newline at end of file
+}
+
+impl Plugin for AnimationPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system(animate_sprite.system().label(AnimationLabel::Animate));
+    }
+}
@@ -1,8 +1,16 @@
-use bevy::prelude::*;
+use std::fs;
+use bevy::{
+    prelude::*,
+    render::{mesh::Indices, pipeline::PrimitiveTopology},
+};
+use serde::{Deserialize, Serialize};
 
-use super::{ai, collision, physics, team, utils};
+use super::{ai, collision, physics, team, zone};
 
+pub const ARENAS_PATH: &str = "assets/arenas";
+pub const DEFAULT_ARENA: &str = "default";
 
+//playing area of the spawned arena, walls and obstacles can still take parts of it
 pub struct Arena {
     pub width: f32,
     pub height: f32,
@@ -10,18 +18,183 @@ pub struct Arena {
     pub bottom: f32,
     pub left: f32,
     pub right: f32,
+    pub ball_home_position: Vec2,
+    pub ball_away_position: Vec2,
+}
+impl Arena {
+    //where the ball is put when the team serves
+    pub fn get_ball_position(&self, serving_side: team::Team) -> Vec2 {
+        match serving_side {
+            team::Team::Home => self.ball_home_position,
+            team::Team::Away => self.ball_away_position,
+        }
+    }
 }
 pub struct ArenaWall {}
+//goal counts the ball or ball carrier, end zone counts only ball carrier (touchdown)
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum GoalKind {
+    Goal,
+    EndZone,
+}
+//team is the one defending it, there can be any number of them per team
 #[derive(PartialEq)]
 pub struct GoalPost {
-    pub team: team::Team
+    pub team: team::Team,
+    pub kind: GoalKind,
+}
+
+//walls given as lines are built from segments this thick
+const LINE_WALL_THICKNESS: f32 = 20.0;
+const CIRCLE_SEGMENTS: usize = 24;
+const WALL_RESTITUTION: f32 = 0.1;
+//bumper gives back more than it gets
+const BUMPER_RESTITUTION: f32 = 1.5;
+
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum WallKind {
+    Wall,
+    Pillar,
+    Bumper,
+}
+
+//coordinates are in world space, (0, 0) is the center of the window
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum Shape {
+    //x, y is the top left corner
+    Rect { x: f32, y: f32, w: f32, h: f32 },
+    Circle { x: f32, y: f32, radius: f32 },
+    //convex, points go around it in order
+    Polygon(Vec<Vec2>),
+    //chain of wall segments, e.g. for slanted or rounded outer walls of any shape
+    Line(Vec<Vec2>),
+}
+impl Shape {
+    //lines are split into segments with round joints, other shapes stay as they are
+    fn get_parts(&self) -> Vec<Shape> {
+        match self {
+            Shape::Line(points) => {
+                let mut parts: Vec<Shape> = points.windows(2).filter_map(|segment| {
+                    let direction = segment[1] - segment[0];
+                    if direction.length_squared() == 0.0 {
+                        return None;
+                    }
+                    let side = Vec2::new(-direction.y, direction.x).normalize() * LINE_WALL_THICKNESS / 2.0;
+                    Some(Shape::Polygon(vec![segment[0] - side, segment[1] - side, segment[1] + side, segment[0] + side]))
+                }).collect();
+                if points.len() > 2 {
+                    parts.extend(points[1..points.len() - 1].iter().map(|point| Shape::Circle { x: point.x, y: point.y, radius: LINE_WALL_THICKNESS / 2.0 }));
+                }
+                parts
+            },
+            _ => vec![self.clone()],
+        }
+    }
+
+    //position of the entity and collider around it
+    fn get_body(&self) -> (Vec2, physics::WallShape) {
+        match self {
+            Shape::Rect { x, y, w, h } => (Vec2::new(x + w/2.0, y - h/2.0), physics::WallShape::Cuboid { w: *w, h: *h }),
+            Shape::Circle { x, y, radius } => (Vec2::new(*x, *y), physics::WallShape::Ball { radius: *radius }),
+            Shape::Polygon(points) => {
+                let center = points.iter().fold(Vec2::ZERO, |sum, point| sum + *point) / points.len() as f32;
+                (center, physics::WallShape::ConvexPolygon(points.iter().map(|point| *point - center).collect()))
+            },
+            Shape::Line(_) => panic!("Line has to be split into parts before it is spawned"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Obstacle {
+    pub kind: WallKind,
+    pub shape: Shape,
+}
+
+//part of the ground that changes how actors run and ball rolls, it can't be a line
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ZoneLayout {
+    pub kind: zone::ZoneKind,
+    pub shape: Shape,
+}
+
+//x, y is the top left corner, end zones are left out when the ruleset has no touchdowns
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GoalPostLayout {
+    pub team: team::Team,
+    pub kind: GoalKind,
+    pub x: f32,
+    pub y: f32,
+    pub w: f32,
+    pub h: f32,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct Bounds {
+    pub left: f32,
+    pub right: f32,
+    pub top: f32,
+    pub bottom: f32,
+}
+
+//bounds are the playing area inside the outer walls, AI keeps to them
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ArenaLayout {
+    pub name: String,
+    pub bounds: Bounds,
+    pub ball_home: Vec2,
+    pub ball_away: Vec2,
+    pub walls: Vec<Shape>,
+    #[serde(default)]
+    pub obstacles: Vec<Obstacle>,
+    #[serde(default)]
+    pub zones: Vec<ZoneLayout>,
+    pub goal_posts: Vec<GoalPostLayout>,
+}
+
+impl ArenaLayout {
+    pub fn load(name: &str) -> Self {
+        let path = format!("{}/{}.ron", ARENAS_PATH, name);
+        let data = fs::read_to_string(&path).expect(format!("Cannot read arena '{}'", path).as_str());
+        let layout: ArenaLayout = ron::from_str(&data).expect(format!("Cannot parse arena '{}'", path).as_str());
+        if layout.bounds.left >= layout.bounds.right || layout.bounds.bottom >= layout.bounds.top {
+            panic!("Arena '{}' has empty bounds", path);
+        }
+        if layout.zones.iter().any(|zone| matches!(zone.shape, Shape::Line(_))) {
+            panic!("Arena '{}' has zone given as line", path);
+        }
+        for team in [team::Team::Home, team::Team::Away].iter() {
+            if !layout.goal_posts.iter().any(|goal_post| goal_post.team == *team && goal_post.kind == GoalKind::Goal) {
+                panic!("Arena '{}' has no goal for {:?} team", path, team);
+            }
+        }
+        let shapes = layout.walls.iter()
+            .chain(layout.obstacles.iter().map(|obstacle| &obstacle.shape))
+            .chain(layout.zones.iter().map(|zone| &zone.shape));
+        for shape in shapes {
+            match shape {
+                Shape::Polygon(points) if points.len() < 3 => panic!("Arena '{}' has polygon with less than 3 points", path),
+                Shape::Line(points) if points.len() < 2 => panic!("Arena '{}' has line with less than 2 points", path),
+                _ => ()
+            }
+        }
+        layout
+    }
+}
+
+
 pub struct ArenaMaterials {
     pub wall: Handle<ColorMaterial>,
+    pub bumper: Handle<ColorMaterial>,
     pub ground: Handle<ColorMaterial>,
+    pub mud: Handle<ColorMaterial>,
+    pub ice: Handle<ColorMaterial>,
+    pub boost: Handle<ColorMaterial>,
+    pub deflector: Handle<ColorMaterial>,
     pub blue_goal_post: Handle<ColorMaterial>,
     pub red_goal_post: Handle<ColorMaterial>,
+    pub blue_end_zone: Handle<ColorMaterial>,
+    pub red_end_zone: Handle<ColorMaterial>,
 }
 
 pub fn setup_arena_materials(
@@ -30,50 +203,155 @@ pub fn setup_arena_materials(
 ) {
     commands.insert_resource(ArenaMaterials {
         wall: materials.add(Color::rgb(0.65, 0.65, 0.65).into()),
+        bumper: materials.add(Color::rgb(0.85, 0.6, 0.2).into()),
         ground: materials.add(Color::rgb(0.28, 0.44, 0.28).into()),
+        mud: materials.add(Color::rgba(0.45, 0.3, 0.15, 0.6).into()),
+        ice: materials.add(Color::rgba(0.75, 0.9, 1.0, 0.5).into()),
+        boost: materials.add(Color::rgba(1.0, 0.85, 0.2, 0.4).into()),
+        deflector: materials.add(Color::rgba(0.7, 0.3, 0.9, 0.4).into()),
         blue_goal_post: materials.add(Color::rgb(0.5, 0.5, 1.0).into()),
         red_goal_post: materials.add(Color::rgb(1.0, 0.5, 0.5).into()),
+        blue_end_zone: materials.add(Color::rgba(0.5, 0.5, 1.0, 0.15).into()),
+        red_end_zone: materials.add(Color::rgba(1.0, 0.5, 0.5, 0.15).into()),
     });
 }
 
+//triangle fan around the first point, points are relative to the entity
+fn create_polygon_mesh(points: &[Vec2]) -> Mesh {
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    let positions: Vec<[f32; 3]> = points.iter().map(|point| [point.x, point.y, 0.0]).collect();
+    let indices = (1..points.len() as u32 - 1).flat_map(|i| vec![0, i, i + 1]).collect();
+    mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.set_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0, 0.0, 1.0]; points.len()]);
+    mesh.set_attribute(Mesh::ATTRIBUTE_UV_0, vec![[0.0, 0.0]; points.len()]);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
+
+fn get_circle_points(radius: f32) -> Vec<Vec2> {
+    (0..CIRCLE_SEGMENTS).map(|i| {
+        let angle = i as f32 / CIRCLE_SEGMENTS as f32 * std::f32::consts::TAU;
+        Vec2::new(angle.cos(), angle.sin()) * radius
+    }).collect()
+}
+
+//sprite of the shape, meshes are needed only for shapes that are not rectangles
+fn get_shape_sprite(
+    material: Handle<ColorMaterial>,
+    meshes: &mut Assets<Mesh>,
+    wall_shape: &physics::WallShape,
+    transform: Transform,
+) -> SpriteBundle {
+    let (sprite, mesh) = match wall_shape {
+        physics::WallShape::Cuboid { w, h } => (Sprite::new(Vec2::new(*w, *h)), None),
+        physics::WallShape::Ball { radius } => (Sprite::new(Vec2::ONE), Some(create_polygon_mesh(&get_circle_points(*radius)))),
+        physics::WallShape::ConvexPolygon(points) => (Sprite::new(Vec2::ONE), Some(create_polygon_mesh(points))),
+    };
+    let bundle = SpriteBundle {
+        material,
+        sprite,
+        transform,
+        ..Default::default()
+    };
+    match mesh {
+        Some(mesh) => SpriteBundle { mesh: meshes.add(mesh), ..bundle },
+        None => bundle,
+    }
+}
+
 pub fn spawn_wall(
     commands: &mut Commands,
-    arena_materials: &Res<ArenaMaterials>,
-    x: f32, y: f32, w: f32, h: f32
+    arena_materials: Option<&ArenaMaterials>,
+    mut meshes: Option<&mut Assets<Mesh>>,
+    shape: &Shape,
+    kind: WallKind,
 ) {
-    let wall_entity = commands
-        .spawn_bundle(SpriteBundle {
-            material: arena_materials.wall.clone(),
-            sprite: Sprite::new(Vec2::new(w, h)),
-            ..Default::default()
-        })
-        .insert(ArenaWall {})
-        .insert(collision::ColliderType::Wall)
+    for part in shape.get_parts() {
+        let (position, wall_shape) = part.get_body();
+        let transform = Transform::from_translation(Vec3::new(position.x, position.y, 0.0));
+        let mut wall = commands.spawn();
+        match (arena_materials, meshes.as_deref_mut()) {
+            (Some(arena_materials), Some(meshes)) => {
+                let material = match kind {
+                    WallKind::Bumper => arena_materials.bumper.clone(),
+                    _ => arena_materials.wall.clone(),
+                };
+                wall.insert_bundle(get_shape_sprite(material, meshes, &wall_shape, transform))
+            },
+            _ => wall.insert_bundle((transform, GlobalTransform::identity())),
+        };
+        let wall_entity = wall
+            .insert(ArenaWall {})
+            .insert(collision::ColliderType::Wall)
+            .id();
+
+        let restitution = if kind == WallKind::Bumper { BUMPER_RESTITUTION } else { WALL_RESTITUTION };
+        physics::create_physics_wall(commands, wall_entity, position, &wall_shape, restitution);
+    }
+}
+
+pub fn spawn_zone(
+    commands: &mut Commands,
+    arena_materials: Option<&ArenaMaterials>,
+    meshes: Option<&mut Assets<Mesh>>,
+    index: usize,
+    zone_layout: &ZoneLayout,
+) {
+    let (position, wall_shape) = zone_layout.shape.get_body();
+    let transform = Transform::from_translation(Vec3::new(position.x, position.y, 0.0));
+    let mut zone = commands.spawn();
+    match (arena_materials, meshes) {
+        (Some(arena_materials), Some(meshes)) => {
+            let material = match zone_layout.kind {
+                zone::ZoneKind::Mud => arena_materials.mud.clone(),
+                zone::ZoneKind::Ice => arena_materials.ice.clone(),
+                zone::ZoneKind::Boost => arena_materials.boost.clone(),
+                zone::ZoneKind::Deflector => arena_materials.deflector.clone(),
+            };
+            zone.insert_bundle(get_shape_sprite(material, meshes, &wall_shape, transform))
+        },
+        _ => zone.insert_bundle((transform, GlobalTransform::identity())),
+    };
+    let zone_entity = zone
+        .insert(zone::Zone { index, kind: zone_layout.kind })
+        .insert(collision::ColliderType::Zone)
         .id();
 
-    physics::create_physics_wall(commands, wall_entity, Vec2::new(x + w/2.0, y - h/2.0), w, h);
+    physics::create_physics_zone(commands, zone_entity, position, &wall_shape);
 }
 
 pub fn spawn_goal_post(
     commands: &mut Commands,
-    arena_materials: &Res<ArenaMaterials>,
+    arena_materials: Option<&ArenaMaterials>,
     team: team::Team,
+    kind: GoalKind,
     is_player_controller: bool,
     x: f32, y: f32, w: f32, h: f32
 ) {
-    let material = match team {
-        team::Team::Home => arena_materials.blue_goal_post.clone(),
-        team::Team::Away => arena_materials.red_goal_post.clone(),
+    let position = Vec2::new(x + w/2.0, y - h/2.0);
+    let transform = Transform::from_translation(Vec3::new(position.x, position.y, 0.0));
+    let mut goal_post = commands.spawn();
+    match arena_materials {
+        Some(arena_materials) => {
+            let material = match (team, kind) {
+                (team::Team::Home, GoalKind::Goal) => arena_materials.blue_goal_post.clone(),
+                (team::Team::Away, GoalKind::Goal) => arena_materials.red_goal_post.clone(),
+                (team::Team::Home, GoalKind::EndZone) => arena_materials.blue_end_zone.clone(),
+                (team::Team::Away, GoalKind::EndZone) => arena_materials.red_end_zone.clone(),
+            };
+            goal_post.insert_bundle(SpriteBundle {
+                material,
+                sprite: Sprite::new(Vec2::new(w, h)),
+                transform,
+                ..Default::default()
+            })
+        },
+        None => goal_post.insert_bundle((transform, GlobalTransform::identity())),
     };
-    let gp_entity = commands
-    .spawn_bundle(SpriteBundle {
-        material,
-        sprite: Sprite::new(Vec2::new(w, h)),
-        ..Default::default()
-    })
-    .insert(GoalPost { team })
-    .insert(collision::ColliderType::GoalPost)
-    .id();
+    let gp_entity = goal_post
+        .insert(GoalPost { team, kind })
+        .insert(collision::ColliderType::GoalPost)
+        .id();
 
     if is_player_controller {
         commands.entity(gp_entity).insert(ai::PlayerControlled {});
@@ -81,50 +359,42 @@ pub fn spawn_goal_post(
         commands.entity(gp_entity).insert(ai::AiControlled::default());
     }
 
-    physics::create_physics_goalpost(commands, gp_entity, Vec2::new(x + w/2.0, y - h/2.0), w, h);
+    physics::create_physics_goalpost(commands, gp_entity, position, w, h);
 }
 
-pub fn create_simple(
+pub fn create_from_layout(
     commands: &mut Commands,
-    arena_materials: &Res<ArenaMaterials>,
-    w: f32,
-    h: f32,
-    offset_x: f32,
-    offset_y: f32,
-    player_team: team::Team,
+    arena_materials: Option<&ArenaMaterials>,
+    mut meshes: Option<&mut Assets<Mesh>>,
+    layout: &ArenaLayout,
+    player_team: Option<team::Team>,
+    has_end_zones: bool,
 ) {
-    let wall_thickness = 20.0;
-    let goal_post_size = 100.0;
-
-    let left = -(w+offset_x)/2.0 + offset_x;
-    let top = (h+offset_y)/2.0 - offset_y;
-    let right = left + w - wall_thickness;
-    let bottom = top - h + wall_thickness;
-    let vertical_section_size = (utils::WIN_H - offset_y - 2.0*wall_thickness - goal_post_size) / 2.0;
-
+    let bounds = layout.bounds;
     commands.insert_resource(Arena {
-        width: w,
-        height: h,
-        left: left + wall_thickness,
-        right: right,
-        top: top - wall_thickness,
-        bottom: bottom,
+        width: bounds.right - bounds.left,
+        height: bounds.top - bounds.bottom,
+        left: bounds.left,
+        right: bounds.right,
+        top: bounds.top,
+        bottom: bounds.bottom,
+        ball_home_position: layout.ball_home,
+        ball_away_position: layout.ball_away,
     });
 
-    spawn_wall(commands, arena_materials, left, top, utils::WIN_W, wall_thickness); // top horizontal secion
-    spawn_wall(commands, arena_materials, left, bottom, utils::WIN_W, wall_thickness); // bottom horizontal secion
-
-    let mut y = top - wall_thickness;
-    spawn_wall(commands, arena_materials, left, y, wall_thickness, vertical_section_size); // left upper section above goalpost
-    y -= vertical_section_size;
-    spawn_goal_post(commands, arena_materials, team::Team::Home, team::Team::Home == player_team, left, y, wall_thickness, goal_post_size);
-    y -= goal_post_size;
-    spawn_wall(commands, arena_materials, left, y, wall_thickness, vertical_section_size); // left lower section below goalpost
-
-    y = top - wall_thickness;
-    spawn_wall(commands, arena_materials, right, y, wall_thickness, vertical_section_size); // right upper section above goalpost
-    y -= vertical_section_size;
-    spawn_goal_post(commands, arena_materials, team::Team::Away, team::Team::Away == player_team, right, y, wall_thickness, goal_post_size);
-    y -= goal_post_size;
-    spawn_wall(commands, arena_materials, right, y, wall_thickness, vertical_section_size); // right lower section below goalpost
-}
\ No newline at end of file
+    for shape in layout.walls.iter() {
+        spawn_wall(commands, arena_materials, meshes.as_deref_mut(), shape, WallKind::Wall);
+    }
+    for obstacle in layout.obstacles.iter() {
+        spawn_wall(commands, arena_materials, meshes.as_deref_mut(), &obstacle.shape, obstacle.kind);
+    }
+    for (index, zone_layout) in layout.zones.iter().enumerate() {
+        spawn_zone(commands, arena_materials, meshes.as_deref_mut(), index, zone_layout);
+    }
+    for goal_post in layout.goal_posts.iter() {
+        if goal_post.kind == GoalKind::EndZone && !has_end_zones {
+            continue;
+        }
+        spawn_goal_post(commands, arena_materials, goal_post.team, goal_post.kind, player_team == Some(goal_post.team), goal_post.x, goal_post.y, goal_post.w, goal_post.h);
+    }
+}
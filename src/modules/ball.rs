@@ -1,3 +1,4 @@
+use std::time::Duration;
 use bevy::prelude::*;
 use bevy_rapier2d::{
     physics::RigidBodyHandleComponent,
@@ -5,18 +6,28 @@ use bevy_rapier2d::{
         dynamics::{RigidBodySet},
     }
 };
-use super::{actor, animation, collision, matchup, physics, team, utils};
+use super::{actor, animation, arena, clock, collision, matchup, passing, physics, states, team, utils, zone};
+
+pub struct BallPlugin;
+
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum BallLabel {
+    HandleEvents,
+    UpdateThrown,
+}
 
-pub const BALL_SPEED: f32 = 250.0;
 pub struct Ball {}
 
 pub struct BallPossession {
     actor: Option<Entity>,
+    //kept after the ball is thrown or lost, to know who scored
+    last_actor: Option<Entity>,
 }
 impl BallPossession {
     pub fn new() -> Self {
         Self {
             actor: None,
+            last_actor: None,
         }
     }
     pub fn has_actor_ball(&self, actor: Entity) -> bool {
@@ -32,26 +43,72 @@ impl BallPossession {
     pub fn is_free(&self) -> bool {
         self.actor.is_none()
     }
+    pub fn get_last(&self) -> Option<Entity> {
+        self.last_actor
+    }
     pub fn set(&mut self, actor: Entity) {
         self.actor = Some(actor);
+        self.last_actor = Some(actor);
     }
     pub fn clear(&mut self) {
         self.actor = None;
     }
 }
 
-pub struct AirTime(Timer);
+//flight of a thrown ball, team is the one of the thrower
+#[derive(Clone)]
+pub struct AirTime {
+    timer: Timer,
+    pub pass_type: passing::PassType,
+    pub team: team::Team,
+}
+impl AirTime {
+    pub fn new(duration: f32, elapsed: f32, pass_type: passing::PassType, team: team::Team) -> Self {
+        let mut timer = Timer::from_seconds(duration, false);
+        timer.set_elapsed(Duration::from_secs_f32(elapsed));
+        Self {
+            timer,
+            pass_type,
+            team,
+        }
+    }
+    pub fn duration_secs(&self) -> f32 {
+        self.timer.duration().as_secs_f32()
+    }
+    pub fn elapsed_secs(&self) -> f32 {
+        self.timer.elapsed_secs()
+    }
+    pub fn remaining_secs(&self) -> f32 {
+        (self.duration_secs() - self.elapsed_secs()).max(0.0)
+    }
+    pub fn is_in_flight(&self) -> bool {
+        self.remaining_secs() > 0.0
+    }
+    //ball high in the air flies over actors
+    pub fn is_high(&self) -> bool {
+        self.is_in_flight() && !passing::is_catchable(self.pass_type, self.timer.percent())
+    }
+    //time until the ball comes down low enough to be caught
+    pub fn high_secs_left(&self) -> f32 {
+        (self.duration_secs() * passing::get_catchable_start(self.pass_type) - self.elapsed_secs()).max(0.0)
+    }
+    pub fn get_pass_length(&self) -> f32 {
+        self.duration_secs() * passing::get_pass_speed(self.pass_type)
+    }
+}
 
 pub enum BallEvent {
     Pickup { actor_entity: Entity, ball_entity: Entity },
     Drop { entity: Entity, position: Vec2, velocity_vector: Vec2 },
-    Throw { entity: Entity, position: Vec2, throw_target: Vec2, power: f32 },
+    Throw { entity: Entity, position: Vec2, throw_target: Vec2, pass_type: passing::PassType },
+    //ball in flight that was touched, but not caught
+    Deflect { ball_entity: Entity },
     WallBounce { ball_entity: Entity },
 }
 
 pub struct BallTexture(Handle<TextureAtlas>);
 
-const BALL_LINEAR_DAMPING_DROPPED: f32 = 1.5;
+pub const BALL_LINEAR_DAMPING_DROPPED: f32 = 1.5;
 const BALL_LINEAR_DAMPING_BOUNCED: f32 = 0.5;
 
 pub fn setup_ball_material(
@@ -68,57 +125,101 @@ pub fn setup_ball_material(
 pub fn add_ball_to_arena(
     mut commands: Commands,
     query_ball: Query<Entity, With<Ball>>,
-    ball_sprite: Res<BallTexture>,
+    ball_sprite: Option<Res<BallTexture>>,
     matchup: Res<matchup::Matchup>,
+    arena: Res<arena::Arena>,
     mut ball_possession: ResMut<BallPossession>,
 ) {
     if let Ok(entity) = query_ball.single() {
         commands.entity(entity).despawn_recursive();
     }
 
-    let position = match matchup.serving_side {
-        team::Team::Home => matchup.ball_home_position,
-        team::Team::Away => matchup.ball_away_position
-    };
+    let position = arena.get_ball_position(matchup.serving_side);
     ball_possession.clear();
-    spawn_ball(&mut commands, &ball_sprite, position, Vec2::ZERO, 0.0);
+    spawn_ball(&mut commands, ball_sprite.as_deref(), position, Vec2::ZERO, None);
 }
 
 pub fn spawn_ball(
     commands: &mut Commands,
-    ball_sprite: &Res<BallTexture>,
+    ball_sprite: Option<&BallTexture>,
     position: Vec2,
     velocity_vector: Vec2,
-    power: f32,
+    air_time: Option<AirTime>,
 ) {
-    let linear_damping = if power > 0.0 { 0.0 } else { BALL_LINEAR_DAMPING_DROPPED };
-    let e = commands
-        .spawn_bundle(SpriteSheetBundle {
+    let linear_damping = if air_time.is_some() { 0.0 } else { BALL_LINEAR_DAMPING_DROPPED };
+    spawn_ball_with_state(commands, ball_sprite, position, velocity_vector, linear_damping, air_time, zone::Surface::new());
+}
+
+//used directly only when ball state is restored, e.g. from savegame
+//damping has to count with the surface already, new ball counts with it only once it enters the zones
+pub fn spawn_ball_with_state(
+    commands: &mut Commands,
+    ball_sprite: Option<&BallTexture>,
+    position: Vec2,
+    velocity_vector: Vec2,
+    linear_damping: f32,
+    air_time: Option<AirTime>,
+    surface: zone::Surface,
+) {
+    let transform = Transform::from_translation(Vec3::new(position.x, position.y, utils::PLAYING_FIELD_Z));
+    let mut ball = commands.spawn();
+    match ball_sprite {
+        Some(ball_sprite) => ball.insert_bundle(SpriteSheetBundle {
             texture_atlas: ball_sprite.0.clone(),
-            transform: Transform::from_translation(Vec3::new(position.x, position.y, utils::PLAYING_FIELD_Z)),
+            transform,
             ..Default::default()
-        })
+        }),
+        None => ball.insert_bundle((transform, GlobalTransform::identity())),
+    };
+    let e = ball
         .insert(Ball {})
         .insert(animation::Animation::new(vec![0]))
         .insert(animation::AnimationTimer(Timer::from_seconds(1.0/8.0, true)))
         .insert(collision::ColliderType::Ball)
+        .insert(surface)
         .id();
 
-    if power > 0.0 {
-        commands.entity(e).insert(AirTime(Timer::from_seconds(power, false)));
+    let is_high = air_time.as_ref().map_or(false, |air_time| air_time.is_high());
+    if let Some(air_time) = air_time {
+        commands.entity(e).insert(air_time);
     }
-    physics::create_physics_ball(commands, e, position, velocity_vector, linear_damping);
+    physics::create_physics_ball(commands, e, position, velocity_vector, linear_damping, is_high);
+}
+
+//collision groups are set only when collider is created, so ball is replaced when it comes down or stops flying
+//new ball stays in the same zones, physics reports them again but it is already inside
+fn respawn_ball(
+    commands: &mut Commands,
+    ball_sprite: Option<&BallTexture>,
+    ball_entity: Entity,
+    position: Vec2,
+    velocity_vector: Vec2,
+    linear_damping: f32,
+    air_time: Option<AirTime>,
+    surface: &zone::Surface,
+) {
+    commands.entity(ball_entity).despawn_recursive();
+    spawn_ball_with_state(commands, ball_sprite, position, velocity_vector, linear_damping, air_time, surface.clone());
 }
 
 pub fn update_thrown_ball(
-    mut query: Query<(&mut AirTime, &RigidBodyHandleComponent), With<Ball>>,
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut AirTime, &mut Transform, &RigidBodyHandleComponent, &zone::Surface), With<Ball>>,
     mut rigid_body_set: ResMut<RigidBodySet>,
-    time: Res<Time>,
+    clock: Res<clock::GameClock>,
+    ball_sprite: Option<Res<BallTexture>>,
 ) {
-    for (mut air_time, rigid_body_handle) in query.iter_mut() {
-        air_time.0.tick(time.delta());
-        if air_time.0.just_finished() {
-            physics::set_rb_properties(rigid_body_handle, &mut rigid_body_set,  None, None, Some(BALL_LINEAR_DAMPING_BOUNCED*5.0));
+    for (entity, mut air_time, mut transform, rigid_body_handle, surface) in query.iter_mut() {
+        let was_high = air_time.is_high();
+        air_time.timer.tick(clock.delta());
+        transform.scale = Vec3::splat(passing::get_ball_scale(air_time.pass_type, air_time.timer.percent()));
+        if air_time.timer.just_finished() {
+            physics::set_rb_properties(rigid_body_handle, &mut rigid_body_set,  None, None, Some(BALL_LINEAR_DAMPING_BOUNCED*5.0*surface.get_damping_factor()));
+        }
+        if was_high && !air_time.is_high() {
+            let velocity = physics::get_velocity(rigid_body_handle, &rigid_body_set).unwrap_or(Vec2::ZERO);
+            let linear_damping = physics::get_linear_damping(rigid_body_handle, &rigid_body_set).unwrap_or(0.0);
+            respawn_ball(&mut commands, ball_sprite.as_deref(), entity, Vec2::from(transform.translation), velocity, linear_damping, Some(air_time.clone()), surface);
         }
     }
 }
@@ -126,16 +227,16 @@ pub fn update_thrown_ball(
 pub fn handle_ball_events(
     mut commands: Commands,
     mut events: EventReader<BallEvent>,
-    ball_sprite: Res<BallTexture>,
-    mut query_actor: Query<(&mut actor::Actor, &mut animation::Animation)>,
-    query_ball: Query<&RigidBodyHandleComponent, With<Ball>>,
+    ball_sprite: Option<Res<BallTexture>>,
+    mut query_actor: Query<(&mut actor::Actor, &mut animation::Animation, &team::Team, &actor::ActorStats)>,
+    mut query_ball: Query<(&RigidBodyHandleComponent, &mut Transform, Option<&AirTime>, &zone::Surface), With<Ball>>,
     mut rigid_body_set: ResMut<RigidBodySet>,
     mut ball_possession: ResMut<BallPossession>,
 ) {
     for event in events.iter() {
         match *event {
             BallEvent::Drop { entity, position, velocity_vector} => {
-                if let Ok((mut actor, mut animation)) = query_actor.get_mut(entity) {
+                if let Ok((mut actor, mut animation, _team, _stats)) = query_actor.get_mut(entity) {
                     actor::change_ball_possession(&mut actor, &mut animation, false);
                     ball_possession.clear();
                 }
@@ -145,33 +246,75 @@ pub fn handle_ball_events(
                     position.y + norm_vel.y*(utils::TRUE_SPRITE_SIZE/2.0),
                 );
                 let ball_velocity = Vec2::new(velocity_vector.x, velocity_vector.y) * 1.5;
-                spawn_ball(&mut commands, &ball_sprite, ball_position, ball_velocity, 0.0);
+                spawn_ball(&mut commands, ball_sprite.as_deref(), ball_position, ball_velocity, None);
             },
-            BallEvent::Throw { entity, position, throw_target, power} => {
-                if let Ok((mut actor, mut animation)) = query_actor.get_mut(entity) {
-                    actor::change_ball_possession(&mut actor, &mut animation, false);
-                    ball_possession.clear();
-                }
+            BallEvent::Throw { entity, position, throw_target, pass_type} => {
+                let (mut actor, mut animation, team, stats) = query_actor.get_mut(entity).expect("Cannot get actor that threw the ball!");
+                actor::change_ball_possession(&mut actor, &mut animation, false);
+                ball_possession.clear();
+                //ball lands at the target, or at the edge of the range when the target is too far
+                let throw_target = passing::clamp_to_range(position, throw_target, pass_type, stats.get_throw_range());
                 let delta = (throw_target - position).normalize();
                 let ball_position = Vec2::new(
                     position.x + delta.x*utils::TRUE_SPRITE_SIZE,
                     position.y + delta.y*utils::TRUE_SPRITE_SIZE,
                 );
-                let ball_velocity = Vec2::new(delta.x, delta.y) * BALL_SPEED;
-                spawn_ball(&mut commands, &ball_sprite, ball_position, ball_velocity, power);
+                let speed = passing::get_pass_speed(pass_type);
+                let ball_velocity = Vec2::new(delta.x, delta.y) * speed;
+                let duration = ((throw_target - ball_position).length() / speed).max(physics::PHYSICS_STEP);
+                spawn_ball(&mut commands, ball_sprite.as_deref(), ball_position, ball_velocity, Some(AirTime::new(duration, 0.0, pass_type, *team)));
             },
             BallEvent::Pickup { actor_entity, ball_entity} => {
-                if let Ok((mut actor, mut animation)) = query_actor.get_mut(actor_entity) {
+                if let Ok((mut actor, mut animation, _team, _stats)) = query_actor.get_mut(actor_entity) {
                     actor::change_ball_possession(&mut actor, &mut animation, true);
                     ball_possession.set(actor_entity);
                 }
                 commands.entity(ball_entity).despawn();
             },
+            BallEvent::Deflect { ball_entity } => {
+                if let Ok((rigid_body_handle, mut transform, _air_time, surface)) = query_ball.get_mut(ball_entity) {
+                    transform.scale = Vec3::ONE;
+                    commands.entity(ball_entity).remove::<AirTime>();
+                    physics::set_rb_properties(rigid_body_handle, &mut rigid_body_set,  None, None, Some(BALL_LINEAR_DAMPING_DROPPED*surface.get_damping_factor()));
+                }
+            },
+            //bounce ends the flight, high ball has to come down so actors can get it
             BallEvent::WallBounce { ball_entity } => {
-                if let Ok(rigid_body_handle) = query_ball.get(ball_entity) {
-                    physics::set_rb_properties(rigid_body_handle, &mut rigid_body_set,  None, None, Some(BALL_LINEAR_DAMPING_BOUNCED));
+                if let Ok((rigid_body_handle, mut transform, air_time, surface)) = query_ball.get_mut(ball_entity) {
+                    let linear_damping = BALL_LINEAR_DAMPING_BOUNCED * surface.get_damping_factor();
+                    if air_time.map_or(false, |air_time| air_time.is_high()) {
+                        let velocity = physics::get_velocity(rigid_body_handle, &rigid_body_set).unwrap_or(Vec2::ZERO);
+                        respawn_ball(&mut commands, ball_sprite.as_deref(), ball_entity, Vec2::from(transform.translation), velocity, linear_damping, None, surface);
+                    } else {
+                        transform.scale = Vec3::ONE;
+                        commands.entity(ball_entity).remove::<AirTime>();
+                        physics::set_rb_properties(rigid_body_handle, &mut rigid_body_set,  None, None, Some(linear_damping));
+                    }
                 }
             }
         }
     }
-}
\ No newline at end of file
+}
+
+impl Plugin for BallPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .insert_resource(BallPossession::new())
+            .add_event::<BallEvent>()
+            .add_system_set(
+                SystemSet::on_exit(states::AppState::MovingToStartPosition)
+                    .with_system(add_ball_to_arena.system())
+            )
+            .add_system_set(
+                SystemSet::on_update(states::AppState::Play)
+                    .with_system(handle_ball_events.system()
+                        .label(BallLabel::HandleEvents)
+                        .after(actor::ActorLabel::HandleEvents)
+                    )
+                    .with_system(update_thrown_ball.system()
+                        .label(BallLabel::UpdateThrown)
+                        .after(BallLabel::HandleEvents)
+                    )
+            );
+    }
+}
@@ -0,0 +1,204 @@
+use bevy::{
+    input::mouse::MouseWheel,
+    prelude::*,
+    render::camera::OrthographicProjection,
+};
+use super::{arena, ball, states, utils};
+
+//world units per second at scale 1.0, zoomed out camera pans faster
+const PAN_SPEED: f32 = 400.0;
+//one step of the mouse wheel
+const ZOOM_STEP: f32 = 1.1;
+const MIN_SCALE: f32 = 0.5;
+const MAX_SCALE: f32 = 3.0;
+//part of the distance to the ball the camera covers per second
+const FOLLOW_SPEED: f32 = 4.0;
+//outer walls stand around the bounds, they are shown too when the whole arena is fitted in the window
+const ARENA_MARGIN: f32 = 20.0;
+
+pub struct CameraPlugin;
+
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum CameraLabel {
+    HandleInput,
+}
+
+//camera looking at the arena, ui has its own one
+pub struct GameCamera {
+    pub follow_ball: bool,
+}
+impl GameCamera {
+    pub fn new() -> Self {
+        Self {
+            follow_ball: false,
+        }
+    }
+}
+
+pub fn spawn_game_camera(commands: &mut Commands) {
+    commands
+        .spawn_bundle(OrthographicCameraBundle::new_2d())
+        .insert(GameCamera::new());
+}
+
+//cursor position in world coordinates, none when it is outside the window
+pub fn get_cursor_position(
+    windows: &Windows,
+    query_camera: &Query<(&Transform, &OrthographicProjection), With<GameCamera>>,
+) -> Option<Vec2> {
+    let window = windows.get_primary()?;
+    let cursor_position = window.cursor_position()?;
+    let (transform, projection) = query_camera.single().ok()?;
+    Some(utils::transform_pos_window_to_world(
+        cursor_position,
+        Vec2::new(window.width(), window.height()),
+        Vec2::from(transform.translation),
+        projection.scale,
+    ))
+}
+
+//camera center stays over the arena, so it can't get lost
+fn clamp_to_arena(position: Vec2, arena: &arena::Arena) -> Vec2 {
+    Vec2::new(
+        position.x.max(arena.left).min(arena.right),
+        position.y.max(arena.bottom).min(arena.top),
+    )
+}
+
+fn set_camera_position(transform: &mut Transform, position: Vec2, arena: &arena::Arena) {
+    let position = clamp_to_arena(position, arena);
+    transform.translation.x = position.x;
+    transform.translation.y = position.y;
+}
+
+//whole arena with its outer walls in the middle of the window
+fn fit_camera(transform: &mut Transform, projection: &mut OrthographicProjection, window: &Window, arena: &arena::Arena) {
+    let scale = ((arena.width + 2.0 * ARENA_MARGIN) / window.width()).max((arena.height + 2.0 * ARENA_MARGIN) / window.height());
+    projection.scale = scale.max(MIN_SCALE).min(MAX_SCALE);
+    let center = Vec2::new(arena.left + arena.right, arena.top + arena.bottom) / 2.0;
+    set_camera_position(transform, center, arena);
+}
+
+pub fn fit_arena(
+    windows: Res<Windows>,
+    arena: Option<Res<arena::Arena>>,
+    mut query_camera: Query<(&mut Transform, &mut OrthographicProjection), With<GameCamera>>,
+) {
+    let (window, arena) = match (windows.get_primary(), arena) {
+        (Some(window), Some(arena)) => (window, arena),
+        _ => return
+    };
+    for (mut transform, mut projection) in query_camera.iter_mut() {
+        fit_camera(&mut transform, &mut projection, window, &arena);
+    }
+}
+
+//arrows pan, mouse wheel or +/- zoom, F toggles following the ball and Home shows the whole arena again
+pub fn handle_camera_input(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut mouse_wheel: EventReader<MouseWheel>,
+    time: Res<Time>,
+    windows: Res<Windows>,
+    arena: Option<Res<arena::Arena>>,
+    mut query_camera: QuerySet<(
+        Query<(&mut Transform, &mut OrthographicProjection, &mut GameCamera)>,
+        Query<(&Transform, &OrthographicProjection), With<GameCamera>>,
+    )>,
+) {
+    let arena = match arena {
+        Some(arena) => arena,
+        None => return
+    };
+    //zoom keeps the point under the cursor in place, wheel reports lines or pixels, only the direction counts
+    let zoom_steps = mouse_wheel.iter().filter(|event| event.y != 0.0).map(|event| event.y.signum()).sum::<f32>()
+        + if keyboard_input.just_pressed(KeyCode::Equals) { 1.0 } else { 0.0 }
+        - if keyboard_input.just_pressed(KeyCode::Minus) { 1.0 } else { 0.0 };
+    let cursor_position = get_cursor_position(&windows, query_camera.q1());
+
+    let mut direction = Vec2::ZERO;
+    if keyboard_input.pressed(KeyCode::Left) {
+        direction.x -= 1.0;
+    }
+    if keyboard_input.pressed(KeyCode::Right) {
+        direction.x += 1.0;
+    }
+    if keyboard_input.pressed(KeyCode::Up) {
+        direction.y += 1.0;
+    }
+    if keyboard_input.pressed(KeyCode::Down) {
+        direction.y -= 1.0;
+    }
+
+    for (mut transform, mut projection, mut camera) in query_camera.q0_mut().iter_mut() {
+        if keyboard_input.just_pressed(KeyCode::Home) {
+            camera.follow_ball = false;
+            if let Some(window) = windows.get_primary() {
+                fit_camera(&mut transform, &mut projection, window, &arena);
+            }
+            continue;
+        }
+        if keyboard_input.just_pressed(KeyCode::F) {
+            camera.follow_ball = !camera.follow_ball;
+        }
+        let mut position = Vec2::from(transform.translation);
+        if zoom_steps != 0.0 {
+            let scale = (projection.scale * ZOOM_STEP.powf(-zoom_steps)).max(MIN_SCALE).min(MAX_SCALE);
+            if let Some(cursor_position) = cursor_position {
+                position = cursor_position + (position - cursor_position) * scale / projection.scale;
+            }
+            projection.scale = scale;
+        }
+        //manual panning takes over from following
+        if direction != Vec2::ZERO {
+            camera.follow_ball = false;
+            position += direction.normalize() * PAN_SPEED * projection.scale * time.delta_seconds();
+        }
+        set_camera_position(&mut transform, position, &arena);
+    }
+}
+
+//carried ball is despawned, camera follows its carrier then
+pub fn follow_ball(
+    time: Res<Time>,
+    arena: Option<Res<arena::Arena>>,
+    ball_possession: Res<ball::BallPossession>,
+    mut query_camera: Query<(&mut Transform, &GameCamera)>,
+    query_ball: Query<&Transform, (With<ball::Ball>, Without<GameCamera>)>,
+    query_carrier: Query<&Transform, Without<GameCamera>>,
+) {
+    let arena = match arena {
+        Some(arena) => arena,
+        None => return
+    };
+    let target = query_ball.iter().next()
+        .or_else(|| ball_possession.get().and_then(|carrier| query_carrier.get(carrier).ok()))
+        .map(|transform| Vec2::from(transform.translation));
+    let target = match target {
+        Some(target) => target,
+        None => return
+    };
+    for (mut transform, camera) in query_camera.iter_mut() {
+        if !camera.follow_ball {
+            continue;
+        }
+        let position = Vec2::from(transform.translation);
+        let position = position + (target - position) * (FOLLOW_SPEED * time.delta_seconds()).min(1.0);
+        set_camera_position(&mut transform, position, &arena);
+    }
+}
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .add_system_set(
+                SystemSet::on_enter(states::AppState::Introduction)
+                    .with_system(fit_arena.system())
+            )
+            .add_system(handle_camera_input.system()
+                .label(CameraLabel::HandleInput)
+            )
+            .add_system(follow_ball.system()
+                .after(CameraLabel::HandleInput)
+            );
+    }
+}
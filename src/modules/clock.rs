@@ -0,0 +1,48 @@
+use std::time::Duration;
+use bevy::prelude::*;
+use super::physics;
+
+//game logic advances in the same fixed steps as rapier (one step per frame), so timers and the outcome
+//of a round don't depend on frame rate
+pub struct GameClock {
+    delta: Duration,
+    steps: u64,
+}
+impl GameClock {
+    pub fn fixed(step: f32) -> Self {
+        Self {
+            delta: Duration::from_secs_f32(step),
+            steps: 0,
+        }
+    }
+    pub fn delta(&self) -> Duration {
+        self.delta
+    }
+    pub fn delta_seconds(&self) -> f32 {
+        self.delta.as_secs_f32()
+    }
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+}
+impl Default for GameClock {
+    fn default() -> Self {
+        Self::fixed(physics::PHYSICS_STEP)
+    }
+}
+
+pub struct ClockPlugin;
+
+pub fn update_game_clock(
+    mut clock: ResMut<GameClock>,
+) {
+    clock.steps += 1;
+}
+
+impl Plugin for ClockPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .init_resource::<GameClock>()
+            .add_system_to_stage(CoreStage::PreUpdate, update_game_clock.system());
+    }
+}
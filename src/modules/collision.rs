@@ -1,4 +1,5 @@
 use bevy::prelude::*;
+use rand::prelude::*;
 use bevy_rapier2d::{
     physics::{EventQueue},
     rapier::{
@@ -14,8 +15,24 @@ use super::{
     arena,
     ball,
     matchup,
+    passing,
+    rng,
+    rules,
+    stamina,
+    states,
+    tackle,
+    team,
+    zone,
 };
 
+pub struct CollisionPlugin;
+
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum CollisionLabel {
+    GetContactEvents,
+    HandleCollisionEvents,
+}
+
 pub enum RRCollisionEventTypes {
     Contact,
     Intersection
@@ -33,6 +50,7 @@ pub enum ColliderType {
     Ball,
     Wall,
     GoalPost,
+    Zone,
 }
 
 
@@ -79,6 +97,7 @@ pub fn get_contact_events(
     collider_set: Res<ColliderSet>,
     query_type: Query<&ColliderType>,
     mut ev_collision: EventWriter<RRCollisionEvent>,
+    mut ev_zone: EventWriter<zone::ZoneEvent>,
 ) {
     while let Ok(contact_event) = events.contact_events.pop() {
         match contact_event {
@@ -97,12 +116,35 @@ pub fn get_contact_events(
     }
 
     while let Ok(intersection_event) = events.intersection_events.pop() {
+        let (idxl, idxr) = (intersection_event.collider1, intersection_event.collider2);
+        //collider of despawned ball doesn't have to leave anything
+        if !intersection_event.intersecting && (collider_set.get(idxl).is_none() || collider_set.get(idxr).is_none()) {
+            continue;
+        }
+        //zone events are sent right here, so entering and leaving keep the order of physics steps
+        let zone_pair = match (get_entity_info_from_collider(&collider_set, &query_type, idxl), get_entity_info_from_collider(&collider_set, &query_type, idxr)) {
+            (Some((e1, e1_type)), Some((e2, e2_type))) => match_entity_pair_to_colliders(e1, e1_type, e2, e2_type, ColliderType::Zone, ColliderType::Actor)
+                .or_else(|| match_entity_pair_to_colliders(e1, e1_type, e2, e2_type, ColliderType::Zone, ColliderType::Ball)),
+            _ => continue
+        };
+        if let Some((zone_entity, entity)) = zone_pair {
+            ev_zone.send(if intersection_event.intersecting {
+                zone::ZoneEvent::Entered { entity, zone_entity }
+            } else {
+                zone::ZoneEvent::Left { entity, zone_entity }
+            });
+            continue;
+        }
+        //only entering sensor matters, e.g. ball or carrier leaving goal must not score again
+        if !intersection_event.intersecting {
+            continue;
+        }
         send_rr_collision_event(
             &collider_set,
             &query_type,
             RRCollisionEventTypes::Intersection,
-            intersection_event.collider1,
-            intersection_event.collider2,
+            idxl,
+            idxr,
             &mut ev_collision
         );
     }
@@ -125,7 +167,7 @@ pub fn match_entity_pair_to_colliders(
     if _collider_type1 == e2_type {
         type1_result = Some(e2);
     } else if _collider_type2 == e2_type {
-        type2_result = Some(e1);
+        type2_result = Some(e2);
     }
 
     if type1_result.is_some() && type2_result.is_some() {
@@ -138,11 +180,22 @@ pub fn match_entity_pair_to_colliders(
 pub fn handle_collision_events(
     mut events: EventReader<RRCollisionEvent>,
     mut events_ball: EventWriter<ball::BallEvent>,
-    query: Query<&actor::Actor>,
+    query: Query<(&actor::Actor, &team::Team, &Transform, &actor::ActorStats, &stamina::Stamina)>,
     query_gp: Query<&arena::GoalPost>,
+    query_air_time: Query<&ball::AirTime>,
     mut events_actor: EventWriter<actor::ActorEvents>,
     mut events_matchup: EventWriter<matchup::MatchupEvents>,
+    ball_possession: Res<ball::BallPossession>,
+    ruleset: Res<rules::Ruleset>,
+    mut game_rng: ResMut<rng::GameRng>,
 ) {
+    let mut send_score = |team_scored_against: team::Team, method: matchup::ScoringMethod, scorer: Option<Entity>| {
+        let points = ruleset.points.get(method);
+        if points > 0 {
+            events_matchup.send(matchup::MatchupEvents::Scored { team_scored_against, points, method, scorer });
+        }
+    };
+
     for event in events.iter() {
         let (e1, e1_type) = event.a;
         let (e2, e2_type) = event.b;
@@ -150,8 +203,46 @@ pub fn handle_collision_events(
         println!("Collision between {:?} and {:?}", e1_type, e2_type);
 
         if e1_type == ColliderType::Actor && e2_type == ColliderType::Actor {
-            let actor1 = query.get(e1).unwrap();
-            let actor2 = query.get(e2).unwrap();
+            let (actor1, _team1, _transform1, _stats1, _stamina1) = query.get(e1).unwrap();
+            let (actor2, _team2, _transform2, _stats2, _stamina2) = query.get(e2).unwrap();
+
+            //dodging actor ignores the tackle, with or without the ball
+            let dodge = match (actor1.act_action, actor2.act_action) {
+                (actor::ActorAction::Tackling { x: _, y: _ }, actor::ActorAction::Dodge { x: _, y: _ }) => Some((e1, e2)),
+                (actor::ActorAction::Dodge { x: _, y: _ }, actor::ActorAction::Tackling { x: _, y: _ }) => Some((e2, e1)),
+                _ => None
+            };
+            if let Some((tackler_entity, dodger_entity)) = dodge {
+                events_actor.send(actor::ActorEvents::Dodged { tackler_entity, dodger_entity });
+                continue;
+            }
+
+            //tackle on the ball carrier is resolved once for both actors
+            let tackle = match (actor1.act_action, actor2.act_action) {
+                (actor::ActorAction::Tackling { x: _, y: _ }, _) if ball_possession.has_actor_ball(e2) => Some((e1, e2)),
+                (_, actor::ActorAction::Tackling { x: _, y: _ }) if ball_possession.has_actor_ball(e1) => Some((e2, e1)),
+                _ => None
+            };
+            if let Some((tackler_entity, carrier_entity)) = tackle {
+                let (tackler, _team, tackler_transform, tackler_stats, tackler_stamina) = query.get(tackler_entity).unwrap();
+                let (carrier, _team, carrier_transform, carrier_stats, carrier_stamina) = query.get(carrier_entity).unwrap();
+                let tackler_stats = tackler_stamina.apply(tackler_stats);
+                let carrier_stats = carrier_stamina.apply(carrier_stats);
+                let tackler_velocity = actor::get_action_velocity(tackler.act_action, Vec2::from(tackler_transform.translation), &tackler_stats);
+                let carrier_velocity = actor::get_action_velocity(carrier.act_action, Vec2::from(carrier_transform.translation), &carrier_stats);
+                let outcome = tackle::resolve_tackle(&tackler_stats, &carrier_stats, tackler_velocity, carrier_velocity, game_rng.0.gen::<f32>());
+                events_actor.send(match outcome {
+                    tackle::TackleOutcome::CleanSteal => actor::ActorEvents::CleanSteal { tackler_entity, carrier_entity },
+                    tackle::TackleOutcome::Fumble => actor::ActorEvents::Fumble {
+                        tackler_entity,
+                        carrier_entity,
+                        bounce: tackle::get_fumble_bounce(game_rng.0.gen::<f32>()),
+                    },
+                    tackle::TackleOutcome::BrokenTackle => actor::ActorEvents::BrokenTackle { tackler_entity, carrier_entity },
+                    tackle::TackleOutcome::BothDown => actor::ActorEvents::BothDown { tackler_entity, carrier_entity },
+                });
+                continue;
+            }
 
             events_actor.send_batch(
                 vec![
@@ -174,16 +265,33 @@ pub fn handle_collision_events(
 
         let collision_result = match_entity_pair_to_colliders(e1, e1_type, e2, e2_type, ColliderType::Ball, ColliderType::Actor);
         if let Some((ball_entity, actor_entity)) = collision_result {
-            let actor = query.get(actor_entity).unwrap();
+            let (actor, team, transform, stats, _stamina) = query.get(actor_entity).unwrap();
             let can_pickup_ball = match actor.act_action {
-                actor::ActorAction::Recovering(_) | actor::ActorAction::Throwing { x: _, y: _ } | actor::ActorAction::Tackling { x: _, y: _ } => false,
+                actor::ActorAction::Recovering(_) | actor::ActorAction::Throwing { x: _, y: _, pass_type: _ } | actor::ActorAction::Tackling { x: _, y: _ } | actor::ActorAction::Shove { x: _, y: _ } => false,
                 _ => true
             };
-            if can_pickup_ball {
+            if !can_pickup_ball {
+                continue;
+            }
+            //loose ball is always picked up, ball in flight has to be caught
+            let is_caught = match query_air_time.get(ball_entity) {
+                Ok(air_time) if air_time.is_in_flight() => {
+                    let position = Vec2::from(transform.translation);
+                    let pressure = query.iter()
+                        .filter(|(_actor, other_team, other_transform, _other_stats, _other_stamina)| *other_team != team && (Vec2::from(other_transform.translation) - position).length_squared() <= passing::PRESSURE_RADIUS.powi(2))
+                        .count();
+                    let chance = passing::get_catch_chance(air_time.pass_type, air_time.get_pass_length(), air_time.team != *team, pressure, stats.catching);
+                    game_rng.0.gen::<f32>() < chance
+                },
+                _ => true
+            };
+            if is_caught {
                 events_ball.send(ball::BallEvent::Pickup {
                     actor_entity,
                     ball_entity
                 });
+            } else {
+                events_ball.send(ball::BallEvent::Deflect { ball_entity });
             }
             continue;
         }
@@ -197,9 +305,42 @@ pub fn handle_collision_events(
 
         let collision_result = match_entity_pair_to_colliders(e1, e1_type, e2, e2_type, ColliderType::Ball, ColliderType::GoalPost);
         if let Some((_ball_entity, gp_entity)) = collision_result {
-            let team_scored_against= query_gp.get(gp_entity).unwrap().team;
-            events_matchup.send(matchup::MatchupEvents::Scored(team_scored_against, 1));
+            let goal_post = query_gp.get(gp_entity).unwrap();
+            if goal_post.kind == arena::GoalKind::Goal {
+                send_score(goal_post.team, matchup::ScoringMethod::Goal, ball_possession.get_last());
+            }
             continue;
         }
+
+        let collision_result = match_entity_pair_to_colliders(e1, e1_type, e2, e2_type, ColliderType::Actor, ColliderType::GoalPost);
+        if let Some((actor_entity, gp_entity)) = collision_result {
+            let (_actor, team, _transform, _stats, _stamina) = query.get(actor_entity).unwrap();
+            let goal_post = query_gp.get(gp_entity).unwrap();
+            if ball_possession.has_actor_ball(actor_entity) && goal_post.team != *team {
+                let method = match goal_post.kind {
+                    arena::GoalKind::Goal => matchup::ScoringMethod::CarriedGoal,
+                    arena::GoalKind::EndZone => matchup::ScoringMethod::Touchdown,
+                };
+                send_score(goal_post.team, method, Some(actor_entity));
+            }
+            continue;
+        }
+    }
+}
+
+impl Plugin for CollisionPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .add_event::<RRCollisionEvent>()
+            .add_system_set(
+                SystemSet::on_update(states::AppState::Play)
+                    .with_system(get_contact_events.system()
+                        .label(CollisionLabel::GetContactEvents)
+                    )
+                    .with_system(handle_collision_events.system()
+                        .label(CollisionLabel::HandleCollisionEvents)
+                        .after(CollisionLabel::GetContactEvents)
+                    )
+            );
     }
 }
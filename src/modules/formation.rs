@@ -0,0 +1,75 @@
+use std::fs;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use super::{actor, ai, matchup, team};
+
+pub const FORMATIONS_PATH: &str = "assets/formations";
+pub const DEFAULT_FORMATION: &str = "default";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FormationSlot {
+    pub spawn: Vec2,
+    pub position: Vec2,
+    #[serde(default)]
+    pub offense_focus: Option<ai::AiFocus>,
+    #[serde(default)]
+    pub defense_focus: Option<ai::AiFocus>,
+}
+
+//slot positions are for the Home team (left side), Away team gets them mirrored
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Formation {
+    pub name: String,
+    pub slots: Vec<FormationSlot>,
+}
+
+impl Formation {
+    pub fn load(name: &str) -> Self {
+        let path = format!("{}/{}.ron", FORMATIONS_PATH, name);
+        let data = fs::read_to_string(&path).expect(format!("Cannot read formation '{}'", path).as_str());
+        let formation: Formation = ron::from_str(&data).expect(format!("Cannot parse formation '{}'", path).as_str());
+        if formation.slots.is_empty() {
+            panic!("Formation '{}' has no slots", path);
+        }
+        formation
+    }
+
+    pub fn get_lineup(&self, team: team::Team) -> Vec<matchup::LineupSlot> {
+        let side = match team {
+            team::Team::Home => 1.0,
+            team::Team::Away => -1.0,
+        };
+        self.slots.iter().map(|slot| matchup::LineupSlot {
+            spawn_position: Vec2::new(slot.spawn.x * side, slot.spawn.y),
+            start_position: Vec2::new(slot.position.x * side, slot.position.y),
+            team,
+            offense_focus: slot.offense_focus.unwrap_or(ai::DEFAULT_OFFENSE_FOCUS),
+            defense_focus: slot.defense_focus.unwrap_or(ai::DEFAULT_DEFENSE_FOCUS),
+            stats: actor::ActorStats::default(),
+        }).collect()
+    }
+}
+
+pub struct SelectedFormations {
+    pub home: Formation,
+    pub away: Formation,
+}
+impl SelectedFormations {
+    pub fn load(home: &str, away: &str) -> Self {
+        Self {
+            home: Formation::load(home),
+            away: Formation::load(away),
+        }
+    }
+
+    pub fn get_lineup(&self) -> Vec<matchup::LineupSlot> {
+        get_lineup(&self.home, &self.away)
+    }
+}
+
+//teams don't have to be of the same size, e.g. 3v4 handicap matches
+pub fn get_lineup(home: &Formation, away: &Formation) -> Vec<matchup::LineupSlot> {
+    let mut lineup = home.get_lineup(team::Team::Home);
+    lineup.extend(away.get_lineup(team::Team::Away));
+    lineup
+}
@@ -1,13 +1,21 @@
 use bevy::prelude::*;
-use super::{actor, utils};
+use super::{actor, ai, passing, stamina, states, utils};
 
 
 const LINE_THICKNESS: f32 = 2.0;
+const LANDING_ZONE_THICKNESS: f32 = 6.0;
+const RANGE_HELPER_DOTS: usize = 48;
+const STAMINA_BAR_WIDTH: f32 = 16.0;
+const STAMINA_BAR_HEIGHT: f32 = 2.0;
+const STAMINA_BAR_OFFSET: f32 = 14.0;
 
+pub struct HelpersPlugin;
 pub struct SelectedHelper {}
 pub struct MovementHelper {
     pub actor: Entity
 }
+pub struct RangeHelper;
+pub struct StaminaBar;
 pub enum HelperType {
     Run,
     Throw,
@@ -19,6 +27,13 @@ pub struct HelperMaterials {
     pub movement_line: Handle<ColorMaterial>,
     pub throw_line: Handle<ColorMaterial>,
     pub tackle_zone: Handle<ColorMaterial>,
+    pub ghost_actor: Handle<ColorMaterial>,
+    pub ghost_ball: Handle<ColorMaterial>,
+    pub ghost_tackle: Handle<ColorMaterial>,
+    pub landing_zone: Handle<ColorMaterial>,
+    pub throw_range: Handle<ColorMaterial>,
+    pub stamina: Handle<ColorMaterial>,
+    pub stamina_tired: Handle<ColorMaterial>,
 }
 
 pub fn setup_helper_materials(commands: &mut Commands, asset_server: &Res<AssetServer>, materials: &mut ResMut<Assets<ColorMaterial>>) {
@@ -28,6 +43,13 @@ pub fn setup_helper_materials(commands: &mut Commands, asset_server: &Res<AssetS
         movement_line: materials.add(Color::rgb(0.67, 0.2, 0.2).into()),
         throw_line: materials.add(Color::rgb(0.8, 0.65, 0.1).into()),
         tackle_zone: materials.add(asset_server.load("tacklezone.png").into()),
+        ghost_actor: materials.add(Color::rgba(0.9, 0.9, 0.9, 0.4).into()),
+        ghost_ball: materials.add(Color::rgba(0.8, 0.65, 0.1, 0.6).into()),
+        ghost_tackle: materials.add(Color::rgba(0.9, 0.1, 0.1, 0.7).into()),
+        landing_zone: materials.add(Color::rgba(0.8, 0.65, 0.1, 0.4).into()),
+        throw_range: materials.add(Color::rgba(0.8, 0.65, 0.1, 0.7).into()),
+        stamina: materials.add(Color::rgb(0.2, 0.75, 0.3).into()),
+        stamina_tired: materials.add(Color::rgb(0.85, 0.3, 0.2).into()),
     });
 }
 
@@ -103,6 +125,152 @@ pub fn spawn_movement_helper(
         .id()
 }
 
+//part of the throw where the ball is low enough to be caught or intercepted
+pub fn spawn_landing_zone_helper(
+    commands: &mut Commands,
+    helper_materials: &Res<HelperMaterials>,
+    to: Vec2,
+    from: Vec2,
+    actor: Entity,
+) -> Entity {
+    let line_data = calculate_line(from, to);
+    commands
+        .spawn_bundle(SpriteBundle {
+            material: helper_materials.landing_zone.clone(),
+            sprite: Sprite::new(Vec2::new(line_data.0, LANDING_ZONE_THICKNESS)),
+            transform: Transform {
+                translation: line_data.1.0,
+                rotation: line_data.1.1,
+                scale: line_data.1.2
+            },
+            ..Default::default()
+        })
+        .insert(MovementHelper {
+            actor
+        })
+        .id()
+}
+
+//whole plan of an actor is redrawn whenever it changes, one segment per planned action
+pub fn update_plan_helpers(
+    mut commands: Commands,
+    helper_materials: Res<HelperMaterials>,
+    query_actors: Query<(Entity, &actor::Actor, &Transform, &actor::ActorStats, &stamina::Stamina), (Changed<actor::Actor>, Without<ai::AiControlled>)>,
+    query_movement_helper: Query<(Entity, &MovementHelper)>,
+) {
+    for (entity, actor, transform, stats, stamina) in query_actors.iter() {
+        let stats = &stamina.apply(stats);
+        for (helper_entity, movement_helper) in query_movement_helper.iter() {
+            if movement_helper.actor == entity {
+                commands.entity(helper_entity).despawn_recursive();
+            }
+        }
+
+        let position = Vec2::new(transform.translation.x, transform.translation.y);
+        if let Some(actor::ConditionalOrder { condition: _, reaction: actor::ActorReaction::Act(actor::ActorAction::Running { x, y }) }) = actor.get_conditional_order() {
+            spawn_movement_helper(&mut commands, &helper_materials, Vec2::new(x, y), actor.get_plan_end_position(position, stats), entity, HelperType::Run);
+        }
+        for (action, from, _duration) in actor.get_plan(position, stats) {
+            let (to, htype) = match action {
+                actor::ActorAction::Running { x, y } | actor::ActorAction::Tackling { x, y } | actor::ActorAction::Dodge { x, y } => (Vec2::new(x, y), HelperType::Run),
+                actor::ActorAction::Throwing { x, y, pass_type } => {
+                    let to = passing::clamp_to_range(from, Vec2::new(x, y), pass_type, stats.get_throw_range());
+                    let landing_from = from + (to - from) * passing::get_catchable_start(pass_type);
+                    spawn_landing_zone_helper(&mut commands, &helper_materials, to, landing_from, entity);
+                    (to, HelperType::Throw)
+                },
+                _ => continue
+            };
+            spawn_movement_helper(&mut commands, &helper_materials, to, from, entity, htype);
+        }
+    }
+}
+
+//ring around the place selected actor will throw from, shown only in throw mode
+pub fn update_range_helper(
+    mut commands: Commands,
+    helper_materials: Res<HelperMaterials>,
+    control_mode: Res<actor::CurrentControlMode>,
+    query_selected: Query<(&actor::Actor, &Transform, &actor::ActorStats, &stamina::Stamina), With<actor::Selected>>,
+    query_changed: Query<Entity, (With<actor::Selected>, Or<(Added<actor::Selected>, Changed<actor::Actor>)>)>,
+    removed_selected: RemovedComponents<actor::Selected>,
+    query_range_helper: Query<Entity, With<RangeHelper>>,
+) {
+    if !control_mode.is_changed() && query_changed.iter().next().is_none() && removed_selected.iter().next().is_none() {
+        return;
+    }
+    for range_helper in query_range_helper.iter() {
+        commands.entity(range_helper).despawn_recursive();
+    }
+    let pass_type = match control_mode.0 {
+        actor::ControlMode::Throw(pass_type) => pass_type,
+        _ => return
+    };
+    if let Ok((actor, transform, stats, stamina)) = query_selected.single() {
+        let stats = &stamina.apply(stats);
+        let center = actor.get_plan_end_position(Vec2::new(transform.translation.x, transform.translation.y), stats);
+        let radius = passing::get_pass_range(pass_type, stats.get_throw_range());
+        for i in 0..RANGE_HELPER_DOTS {
+            let angle = i as f32 / RANGE_HELPER_DOTS as f32 * std::f32::consts::TAU;
+            let position = center + Vec2::new(angle.cos(), angle.sin()) * radius;
+            commands
+                .spawn_bundle(SpriteBundle {
+                    material: helper_materials.throw_range.clone(),
+                    sprite: Sprite::new(Vec2::new(LINE_THICKNESS, LINE_THICKNESS)),
+                    transform: Transform::from_translation(Vec3::new(position.x, position.y, 0.3)),
+                    ..Default::default()
+                })
+                .insert(RangeHelper);
+        }
+    }
+}
+
+pub fn cleanup_range_helpers(
+    mut commands: Commands,
+    query_range_helper: Query<Entity, With<RangeHelper>>,
+) {
+    for range_helper in query_range_helper.iter() {
+        commands.entity(range_helper).despawn_recursive();
+    }
+}
+
+pub fn spawn_stamina_bars(
+    mut commands: Commands,
+    helper_materials: Res<HelperMaterials>,
+    query: Query<Entity, Added<stamina::Stamina>>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).with_children(|parent| {
+            parent
+                .spawn_bundle(SpriteBundle {
+                    material: helper_materials.stamina.clone(),
+                    sprite: Sprite::new(Vec2::new(STAMINA_BAR_WIDTH, STAMINA_BAR_HEIGHT)),
+                    transform: Transform::from_translation(Vec3::new(0.0, STAMINA_BAR_OFFSET, 0.1)),
+                    ..Default::default()
+                })
+                .insert(StaminaBar);
+        });
+    }
+}
+
+//bar shrinks towards its left end and turns red once fatigue kicks in
+pub fn update_stamina_bars(
+    helper_materials: Res<HelperMaterials>,
+    query_actors: Query<(&stamina::Stamina, &Children), Changed<stamina::Stamina>>,
+    mut query_bars: Query<(&mut Sprite, &mut Transform, &mut Handle<ColorMaterial>), With<StaminaBar>>,
+) {
+    for (stamina, children) in query_actors.iter() {
+        for child in children.iter() {
+            if let Ok((mut sprite, mut transform, mut material)) = query_bars.get_mut(*child) {
+                let width = STAMINA_BAR_WIDTH * stamina.0 / stamina::MAX_STAMINA;
+                sprite.size.x = width;
+                transform.translation.x = (width - STAMINA_BAR_WIDTH) / 2.0;
+                *material = if stamina.get_fatigue_factor() < 1.0 { helper_materials.stamina_tired.clone() } else { helper_materials.stamina.clone() };
+            }
+        }
+    }
+}
+
 pub fn update_selected_helper(
     mut query: QuerySet<(
         Query<&mut Transform, With<SelectedHelper>>,
@@ -136,3 +304,34 @@ fn calculate_line(
 
     (length, (Vec3::new(midpoint.x, midpoint.y, 0.3), Quat::from_rotation_z(angle), Vec3::splat(1.0)))
 }
+
+impl Plugin for HelpersPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .add_system(spawn_stamina_bars.system())
+            .add_system(update_stamina_bars.system())
+            .add_system_set(
+                SystemSet::on_enter(states::AppState::Introduction)
+                    .with_system(spawn_selected_helper.system())
+            )
+            .add_system_set(
+                SystemSet::on_enter(states::AppState::Plan)
+                    .with_system(cleanup_movement_helpers.system())
+            )
+            .add_system_set(
+                SystemSet::on_update(states::AppState::Plan)
+                    .with_system(update_selected_helper.system())
+                    .with_system(update_plan_helpers.system()
+                        .after(actor::ActorLabel::ApplyOrders)
+                    )
+                    .with_system(update_range_helper.system()
+                        .after(actor::ActorLabel::ApplyOrders)
+                    )
+            )
+            .add_system_set(
+                SystemSet::on_exit(states::AppState::Plan)
+                    .with_system(deselect_all.system())
+                    .with_system(cleanup_range_helpers.system())
+            );
+    }
+}
@@ -0,0 +1,95 @@
+use bevy::prelude::*;
+use super::{actor, helpers, states, team};
+
+//two players on one machine - Home plans, hands over, then Away plans and both plans are played together
+pub struct HotseatPlugin;
+
+pub struct Hotseat {
+    pub planning_team: team::Team,
+}
+impl Hotseat {
+    pub fn new() -> Self {
+        Self {
+            planning_team: team::Team::Home,
+        }
+    }
+}
+
+pub fn can_plan(
+    hotseat: Option<&Hotseat>,
+    team: team::Team,
+) -> bool {
+    hotseat.map_or(true, |hotseat| hotseat.planning_team == team)
+}
+
+fn start_turn(
+    mut hotseat: ResMut<Hotseat>,
+    mut planning_hold: ResMut<states::PlanningHold>,
+) {
+    hotseat.planning_team = team::Team::Home;
+    planning_hold.0 = Some(states::AppState::HandOver);
+}
+
+fn set_helpers_visibility(
+    query_helpers: &Query<(Entity, &helpers::MovementHelper, Option<&Children>)>,
+    query_visible: &mut Query<&mut Visible>,
+    query_actors: &Query<&team::Team, With<actor::Actor>>,
+    visible_team: Option<team::Team>,
+) {
+    for (helper_entity, movement_helper, children) in query_helpers.iter() {
+        let is_visible = match (visible_team, query_actors.get(movement_helper.actor)) {
+            (Some(visible_team), Ok(team)) => *team == visible_team,
+            _ => true
+        };
+        //landing zones have no children
+        for entity in std::iter::once(&helper_entity).chain(children.into_iter().flat_map(|children| children.iter())) {
+            if let Ok(mut visible) = query_visible.get_mut(*entity) {
+                visible.is_visible = is_visible;
+            }
+        }
+    }
+}
+
+fn hand_over(
+    mut commands: Commands,
+    mut hotseat: ResMut<Hotseat>,
+    mut planning_hold: ResMut<states::PlanningHold>,
+    query_helpers: Query<(Entity, &helpers::MovementHelper, Option<&Children>)>,
+    mut query_visible: Query<&mut Visible>,
+    query_actors: Query<&team::Team, With<actor::Actor>>,
+    query_selected: Query<Entity, (With<actor::Actor>, With<actor::Selected>)>,
+) {
+    hotseat.planning_team = team::get_oposing_team(hotseat.planning_team);
+    planning_hold.0 = None;
+    for actor_selected in query_selected.iter() {
+        commands.entity(actor_selected).remove::<actor::Selected>();
+    }
+    set_helpers_visibility(&query_helpers, &mut query_visible, &query_actors, Some(hotseat.planning_team));
+}
+
+fn reveal_plans(
+    query_helpers: Query<(Entity, &helpers::MovementHelper, Option<&Children>)>,
+    mut query_visible: Query<&mut Visible>,
+    query_actors: Query<&team::Team, With<actor::Actor>>,
+) {
+    set_helpers_visibility(&query_helpers, &mut query_visible, &query_actors, None);
+}
+
+impl Plugin for HotseatPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .insert_resource(Hotseat::new())
+            .add_system_set(
+                SystemSet::on_enter(states::AppState::Plan)
+                    .with_system(start_turn.system())
+            )
+            .add_system_set(
+                SystemSet::on_pause(states::AppState::Plan)
+                    .with_system(hand_over.system())
+            )
+            .add_system_set(
+                SystemSet::on_exit(states::AppState::Plan)
+                    .with_system(reveal_plans.system())
+            );
+    }
+}
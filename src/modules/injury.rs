@@ -0,0 +1,227 @@
+use bevy::prelude::*;
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+use super::{actor, ai, ball, matchup, rng, round, rules, stamina, states, team};
+
+//chance of injury from an average tackle, every hard hit taken before in the match adds to it
+const BASE_INJURY_CHANCE: f32 = 0.03;
+const REPEATED_HIT_CHANCE: f32 = 0.03;
+//part of the injuries that end the match for the actor
+const SEVERE_INJURY_CHANCE: f32 = 0.2;
+const MIN_INJURY_TURNS: u32 = 2;
+const MAX_INJURY_TURNS: u32 = 6;
+
+pub struct InjuryPlugin;
+
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum InjuryLabel {
+    CheckInjuries,
+    HealInjuries,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Injury {
+    //turns left to sit out, the one when it happened counts too
+    Turns(u32),
+    Match,
+}
+
+//injured actor stays down until it heals or gets substituted
+#[derive(Clone, Copy, Debug)]
+pub struct Health {
+    pub hard_hits: u32,
+    pub injury: Option<Injury>,
+}
+impl Health {
+    pub fn new() -> Self {
+        Self {
+            hard_hits: 0,
+            injury: None,
+        }
+    }
+    pub fn is_injured(&self) -> bool {
+        self.injury.is_some()
+    }
+}
+
+//actor knocked down by a tackle, severity is 1.0 for an average tackle and grows with recovery time
+pub struct HardHit {
+    pub entity: Entity,
+    pub severity: f32,
+}
+
+//injuries and substitutions since the last round started, announced to the players
+pub struct InjuryReport {
+    pub injuries: Vec<(team::Team, Injury)>,
+    pub substitutions: Vec<team::Team>,
+}
+impl InjuryReport {
+    pub fn new() -> Self {
+        Self {
+            injuries: vec![],
+            substitutions: vec![],
+        }
+    }
+}
+
+pub fn get_injury_chance(hard_hits: u32, severity: f32) -> f32 {
+    (BASE_INJURY_CHANCE + REPEATED_HIT_CHANCE * hard_hits.saturating_sub(1) as f32) * severity
+}
+
+pub fn check_injuries(
+    mut events: EventReader<HardHit>,
+    mut ball_events: EventWriter<ball::BallEvent>,
+    mut query: Query<(&mut Health, &mut actor::Actor, &Transform, &team::Team)>,
+    mut game_rng: ResMut<rng::GameRng>,
+    mut report: ResMut<InjuryReport>,
+    ball_possession: Res<ball::BallPossession>,
+    ruleset: Res<rules::Ruleset>,
+) {
+    if !ruleset.injuries {
+        return;
+    }
+    for event in events.iter() {
+        let (mut health, mut actor, transform, team) = match query.get_mut(event.entity) {
+            Ok(result) => result,
+            Err(_) => continue
+        };
+        if health.is_injured() {
+            continue;
+        }
+        health.hard_hits += 1;
+        if game_rng.0.gen::<f32>() >= get_injury_chance(health.hard_hits, event.severity) {
+            continue;
+        }
+        let injury = if game_rng.0.gen::<f32>() < SEVERE_INJURY_CHANCE {
+            Injury::Match
+        } else {
+            Injury::Turns(game_rng.0.gen_range(MIN_INJURY_TURNS..=MAX_INJURY_TURNS))
+        };
+        report.injuries.push((*team, injury));
+        health.injury = Some(injury);
+        actor.set_action(actor::ActorAction::Recovering(round::ROUND_TIME));
+        //ball slips out of hands of the injured carrier
+        if ball_possession.has_actor_ball(event.entity) {
+            ball_events.send(ball::BallEvent::Drop {
+                entity: event.entity,
+                position: Vec2::new(transform.translation.x, transform.translation.y),
+                velocity_vector: Vec2::new(0.0, -1.0),
+            });
+        }
+    }
+}
+
+pub fn clear_report(
+    mut report: ResMut<InjuryReport>,
+) {
+    report.injuries.clear();
+    report.substitutions.clear();
+}
+
+//orders given to injured actors are ignored, they lie down for the whole round
+pub fn keep_injured_down(
+    mut query: Query<(&Health, &mut actor::Actor)>,
+) {
+    for (health, mut actor) in query.iter_mut() {
+        if health.is_injured() {
+            actor.set_action(actor::ActorAction::Recovering(round::ROUND_TIME));
+        }
+    }
+}
+
+pub fn heal_injuries(
+    mut query: Query<(&mut Health, &mut actor::Actor)>,
+) {
+    for (mut health, mut actor) in query.iter_mut() {
+        if let Some(Injury::Turns(turns)) = health.injury {
+            if turns > 1 {
+                health.injury = Some(Injury::Turns(turns - 1));
+            } else {
+                health.injury = None;
+                actor.set_action(actor::ActorAction::Lookout);
+            }
+        }
+    }
+}
+
+//injured actors are replaced by the first player on the bench of their team, who takes over the same slot
+pub fn substitute_injured(
+    mut commands: Commands,
+    mut matchup: ResMut<matchup::Matchup>,
+    mut ball_possession: ResMut<ball::BallPossession>,
+    mut report: ResMut<InjuryReport>,
+    query: Query<(&Health, Option<&ai::AiControlled>, Option<&ai::PlayerControlled>)>,
+    actor_sprites: Option<Res<actor::ActorTextures>>,
+) {
+    for slot in 0..matchup.get_actors().len() {
+        let (entity, start_position, team) = matchup.get_actors()[slot];
+        let (health, ai_controlled, player_controlled) = match query.get(entity) {
+            Ok(result) => result,
+            Err(_) => continue
+        };
+        if !health.is_injured() {
+            continue;
+        }
+        let stats = match matchup.take_bench_player(team) {
+            Some(stats) => stats,
+            None => continue
+        };
+        report.substitutions.push(team);
+        if ball_possession.has_actor_ball(entity) {
+            ball_possession.clear();
+        }
+        commands.entity(entity).despawn_recursive();
+        let substitute = actor::spawn_actor(
+            &mut commands,
+            actor_sprites.as_deref(),
+            start_position,
+            team,
+            player_controlled.is_some(),
+            ai_controlled.map_or(ai::DEFAULT_OFFENSE_FOCUS, |ai| ai.get_offense_focus()),
+            ai_controlled.map_or(ai::DEFAULT_DEFENSE_FOCUS, |ai| ai.get_defense_focus()),
+            stats,
+        );
+        //remote and replayed actors are controlled by nobody, substitute neither
+        if player_controlled.is_none() && ai_controlled.is_none() {
+            commands.entity(substitute).remove::<ai::AiControlled>();
+        }
+        matchup.replace_actor(slot, substitute);
+    }
+}
+
+impl Plugin for InjuryPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .add_event::<HardHit>()
+            .insert_resource(InjuryReport::new())
+            .add_system_set(
+                SystemSet::on_update(states::AppState::Play)
+                    .with_system(check_injuries.system()
+                        .label(InjuryLabel::CheckInjuries)
+                        .after(actor::ActorLabel::HandleEvents)
+                        .before(ball::BallLabel::HandleEvents)
+                        .before(stamina::StaminaLabel::Update)
+                    )
+            )
+            .add_system_set(
+                SystemSet::on_enter(states::AppState::Play)
+                    .with_system(clear_report.system())
+                    .with_system(keep_injured_down.system())
+            )
+            .add_system_set(
+                SystemSet::on_exit(states::AppState::Play)
+                    .with_system(heal_injuries.system()
+                        .label(InjuryLabel::HealInjuries)
+                        .before(actor::ActorLabel::AfterRoundReset)
+                    )
+            )
+            .add_system_set(
+                SystemSet::on_enter(states::AppState::Scored)
+                    .with_system(substitute_injured.system())
+            )
+            .add_system_set(
+                SystemSet::on_enter(states::AppState::MovingToStartPosition)
+                    .with_system(substitute_injured.system())
+            );
+    }
+}
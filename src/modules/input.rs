@@ -1,5 +1,10 @@
-use bevy::prelude::*;
-use super::{actor, ai, ball, helpers, states, ui, utils};
+use bevy::{prelude::*, render::camera::OrthographicProjection};
+use super::{actor, ai, ball, camera, hotseat, injury, passing, preview, referee, round, stamina, savegame, states, team, ui, utils};
+
+pub struct InputPlugin;
+
+//shift + click plans a run that starts only after this part of the round
+const CONDITIONAL_WAIT_TIME: f32 = 0.5;
 
 pub fn handle_keyboard_input_pre_round(
     mut keyboard_input: ResMut<Input<KeyCode>>,
@@ -11,28 +16,53 @@ pub fn handle_keyboard_input_pre_round(
     }
 }
 
+pub fn handle_keyboard_input_hand_over(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut app_state: ResMut<State<states::AppState>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Return) {
+        app_state.pop().unwrap();
+        keyboard_input.reset(KeyCode::Return);
+    }
+}
+
 pub fn handle_keyboard_input(
     mut keyboard_input: ResMut<Input<KeyCode>>,
     mut app_state: ResMut<State<states::AppState>>,
     mut control_mode: ResMut<actor::CurrentControlMode>,
     ball_possession: Res<ball::BallPossession>,
     query: Query<Entity, With<actor::Selected>>,
+    mut event_save: EventWriter<savegame::SaveGameRequest>,
+    planning_hold: Res<states::PlanningHold>,
+    preview: Option<ResMut<preview::Preview>>,
 ) {
+    if keyboard_input.just_pressed(KeyCode::F5) {
+        event_save.send(savegame::SaveGameRequest);
+    }
+    if keyboard_input.just_pressed(KeyCode::P) {
+        if let Some(mut preview) = preview {
+            preview.toggle();
+        }
+    }
     if keyboard_input.just_pressed(KeyCode::Space) {
-        app_state.set(states::AppState::Play).unwrap();
+        states::finish_planning(&mut app_state, &planning_hold);
         keyboard_input.reset(KeyCode::Space); //according to https://bevy-cheatbook.github.io/programming/states.html#with-input
         return;
     }
     if let Ok(entity) = query.single() {
         let has_ball = ball_possession.has_actor_ball(entity);
         if keyboard_input.just_pressed(KeyCode::Return) && has_ball {
+            //enter cycles through run, lob and bullet pass
             control_mode.0 = match control_mode.0 {
-                actor::ControlMode::Throw => {
+                actor::ControlMode::Throw(passing::PassType::Bullet) => {
                     actor::ControlMode::Run
                 }
-                actor::ControlMode::Run => {
+                actor::ControlMode::Throw(passing::PassType::Lob) => {
+                    actor::ControlMode::Throw(passing::PassType::Bullet)
+                }
+                _ => {
                     if query.single().is_ok() {
-                        actor::ControlMode::Throw
+                        actor::ControlMode::Throw(passing::PassType::Lob)
                     } else {
                         actor::ControlMode::Run
                     }
@@ -40,60 +70,86 @@ pub fn handle_keyboard_input(
             };
             return;
         }
+        //B, D and S switch between run and block, dodge or shove, pressing the same key again goes back to run
+        let toggled_mode = if keyboard_input.just_pressed(KeyCode::B) {
+            Some(actor::ControlMode::Block)
+        } else if keyboard_input.just_pressed(KeyCode::D) {
+            Some(actor::ControlMode::Dodge)
+        } else if keyboard_input.just_pressed(KeyCode::S) {
+            Some(actor::ControlMode::Shove)
+        } else {
+            None
+        };
+        if let Some(mode) = toggled_mode {
+            control_mode.0 = if control_mode.0 == mode { actor::ControlMode::Run } else { mode };
+        }
     }
 }
 
+//first order of the turn replaces the plan, following ones are chained after it,
+//action is created from the position where it will start
+fn plan_action(
+    actor: &actor::Actor,
+    position: Vec2,
+    stats: &actor::ActorStats,
+    get_action: impl FnOnce(Vec2) -> actor::ActorAction,
+) -> Option<actor::ActorOrder> {
+    let (is_queued, time_left, from) = match (actor.has_planned_orders(), actor.act_action) {
+        (false, actor::ActorAction::Recovering(_)) | (true, _) => (true, actor.get_plan_time_left(position, stats), actor.get_plan_end_position(position, stats)),
+        (false, _) => (false, round::ROUND_TIME, position),
+    };
+    let action = get_action(from);
+    //range and plan helpers show the time left, order that doesn't fit is just ignored
+    if actor::estimate_action(action, from, stats).0 > time_left {
+        return None;
+    }
+    Some(if is_queued { actor::ActorOrder::Queue(action) } else { actor::ActorOrder::Set(action) })
+}
+
 pub fn handle_mouse_click(
     mut commands: Commands,
     mut query:  QuerySet<(
-        Query<(Entity, &Transform), (With<actor::Actor>, With<ai::PlayerControlled>, Without<actor::Selected>)>,
-        Query<(Entity, &Transform, &mut actor::Actor), (With<actor::Selected>, With<ai::PlayerControlled>)>,
+        Query<(Entity, &Transform, &team::Team, &injury::Health), (With<actor::Actor>, With<ai::PlayerControlled>, Without<actor::Selected>, Without<referee::SinBin>)>,
+        Query<(Entity, &Transform, &actor::Actor, &actor::ActorStats, &stamina::Stamina), (With<actor::Selected>, With<ai::PlayerControlled>)>,
     )>,
-    query_movement_helper: Query<(Entity, &helpers::MovementHelper)>,
     mut control_mode: ResMut<actor::CurrentControlMode>,
-    helper_materials: Res<helpers::HelperMaterials>,
     mouse_input: Res<Input<MouseButton>>,
+    keyboard_input: Res<Input<KeyCode>>,
     windows: Res<Windows>,
+    query_camera: Query<(&Transform, &OrthographicProjection), With<camera::GameCamera>>,
     query_buttons: Query<(Entity, &ui::ButtonAction, &ui::ButtonGroup), With<ui::RRButton>>,
     mut event_buttons: EventWriter<ui::ButtonEvent>,
     ball_possession: Res<ball::BallPossession>,
+    mut event_orders: EventWriter<actor::ActorOrderEvent>,
+    hotseat: Option<Res<hotseat::Hotseat>>,
 ) {
-    let mouse_left_pressed = mouse_input.just_pressed(MouseButton::Left);
-    let mouse_right_pressed = mouse_input.just_pressed(MouseButton::Right);
-
-    if mouse_right_pressed {
-        for (prev_selected, _, _) in query.q1_mut().iter_mut() {
-            commands.entity(prev_selected).remove::<actor::Selected> ();
-        }
+    if !mouse_input.just_pressed(MouseButton::Left) {
         return;
     }
 
-    //get mouse position if mouse left button is clicked
-    let click_pos = windows
+    //if click is inside UI bar at the top of the window then return
+    let is_in_ui = windows
         .get_primary()
-        .and_then(|win| -> Option<bevy::prelude::Vec2> {
-            if !mouse_left_pressed {
-                return None;
-            }
-            win.cursor_position()
-        })
-        .and_then(|pos| -> Option<bevy::prelude::Vec2> {
-            Some(utils::transform_pos_window_to_screen(pos))
-        });
-    if click_pos.is_none() {
+        .and_then(|win| win.cursor_position().map(|pos| pos.y >= win.height() - ui::UI_SIZE))
+        .unwrap_or(true);
+    if is_in_ui {
         return;
     }
-    let click_pos = click_pos.unwrap();
 
-    //if click is inside UI then return
-    if click_pos.y >= utils::WIN_H/2.0 - ui::UI_SIZE {
-        return;
-    }
+    //get mouse position in the arena
+    let click_pos = match camera::get_cursor_position(&windows, &query_camera) {
+        Some(click_pos) => click_pos,
+        None => return
+    };
 
     //get if some actor is clicked
     let mut clicked_entity = None;
     let mut has_ball = false;
-    for (entity, transform) in query.q0().iter() {
+    for (entity, transform, team, health) in query.q0().iter() {
+        //injured and sent off actors can't be given orders
+        if !hotseat::can_plan(hotseat.as_deref(), *team) || health.is_injured() {
+            continue;
+        }
         if utils::is_point_in_square(&click_pos, &transform.translation, utils::TRUE_SPRITE_SIZE/2.0) {
             clicked_entity = Some(entity);
             has_ball = ball_possession.has_actor_ball(entity);
@@ -102,7 +158,7 @@ pub fn handle_mouse_click(
 
     //if it is, select him
     if clicked_entity.is_some() {
-        for (prev_selected, _,  _) in query.q1_mut().iter_mut() {
+        for (prev_selected, _, _, _, _) in query.q1_mut().iter_mut() {
             commands.entity(prev_selected).remove::<actor::Selected> ();
         }
         let clicked_entity = clicked_entity.unwrap();
@@ -129,34 +185,142 @@ pub fn handle_mouse_click(
         return;
     }
 
-    //if not set target position
-    for (selected, transform, mut actor) in query.q1_mut().iter_mut() {
-        let (action, htype) = match control_mode.0 {
-            actor::ControlMode::Run => (actor::ActorAction::Running { x: click_pos.x, y: click_pos.y }, helpers::HelperType::Run),
-            actor::ControlMode::Throw => (actor::ActorAction::Throwing { x: click_pos.x, y: click_pos.y }, helpers::HelperType::Throw),
-        };
-        match actor.act_action {
-            actor::ActorAction::Recovering(_) => {
-                actor.queue_action(action);
+    //if not add target position to actor's plan
+    for (selected, transform, actor, stats, stamina) in query.q1().iter() {
+        //plan is estimated with how tired the actor is now
+        let stats = &stamina.apply(stats);
+        let position = Vec2::new(transform.translation.x, transform.translation.y);
+        let is_shift_pressed = keyboard_input.pressed(KeyCode::LShift) || keyboard_input.pressed(KeyCode::RShift);
+        if is_shift_pressed && control_mode.0 == actor::ControlMode::Run {
+            event_orders.send(actor::ActorOrderEvent {
+                entity: selected,
+                order: actor::ActorOrder::Conditional(actor::ConditionalOrder {
+                    condition: actor::ActorCondition::TimeElapsed(CONDITIONAL_WAIT_TIME),
+                    reaction: actor::ActorReaction::Act(actor::ActorAction::Running { x: click_pos.x, y: click_pos.y }),
+                }),
+            });
+            continue;
+        }
+        let order = plan_action(actor, position, stats, |from| match control_mode.0 {
+            actor::ControlMode::Run => actor::ActorAction::Running { x: click_pos.x, y: click_pos.y },
+            actor::ControlMode::Throw(pass_type) => actor::ActorAction::Throwing { x: click_pos.x, y: click_pos.y, pass_type },
+            actor::ControlMode::Dodge => {
+                let target = actor::clamp_dodge_target(from, click_pos, stats);
+                actor::ActorAction::Dodge { x: target.x, y: target.y }
             },
-            _ => {
-                actor.set_action(action);
-            }
+            actor::ControlMode::Block => actor::ActorAction::Block { x: click_pos.x, y: click_pos.y },
+            actor::ControlMode::Shove => actor::ActorAction::Shove { x: click_pos.x, y: click_pos.y },
+        });
+        if let Some(order) = order {
+            event_orders.send(actor::ActorOrderEvent { entity: selected, order });
+        }
+    }
+}
+
+//H makes selected actor hold the place where its plan ends and guard the zone around it
+pub fn handle_hold_position_input(
+    keyboard_input: Res<Input<KeyCode>>,
+    query: Query<(Entity, &Transform, &actor::Actor, &actor::ActorStats, &stamina::Stamina), (With<actor::Selected>, With<ai::PlayerControlled>)>,
+    mut event_orders: EventWriter<actor::ActorOrderEvent>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::H) {
+        return;
+    }
+
+    for (selected, transform, actor, stats, stamina) in query.iter() {
+        let stats = &stamina.apply(stats);
+        let position = Vec2::new(transform.translation.x, transform.translation.y);
+        if let Some(order) = plan_action(actor, position, stats, |from| actor::ActorAction::HoldPosition { x: from.x, y: from.y }) {
+            event_orders.send(actor::ActorOrderEvent { entity: selected, order });
         }
+    }
+}
+
+//T tackles ball carrier that comes into tackle range, I intercepts ball thrown nearby
+pub fn handle_conditional_input(
+    keyboard_input: Res<Input<KeyCode>>,
+    query: Query<(Entity, &actor::ActorStats, &stamina::Stamina), (With<actor::Selected>, With<ai::PlayerControlled>)>,
+    mut event_orders: EventWriter<actor::ActorOrderEvent>,
+) {
+    let is_tackle = keyboard_input.just_pressed(KeyCode::T);
+    if !is_tackle && !keyboard_input.just_pressed(KeyCode::I) {
+        return;
+    }
 
-        for (movement_helper, actor_entity) in query_movement_helper.iter() {
-            if actor_entity.actor == selected {
-                commands.entity(movement_helper).despawn_recursive();
+    for (selected, stats, stamina) in query.iter() {
+        let stats = stamina.apply(stats);
+        let conditional_order = if is_tackle {
+            actor::ConditionalOrder {
+                condition: actor::ActorCondition::CarrierInRange(stats.get_tackle_radius()),
+                reaction: actor::ActorReaction::TackleCarrier,
             }
+        } else {
+            actor::ConditionalOrder {
+                condition: actor::ActorCondition::BallThrownNear(stats.get_tackle_radius()),
+                reaction: actor::ActorReaction::InterceptBall,
+            }
+        };
+        event_orders.send(actor::ActorOrderEvent { entity: selected, order: actor::ActorOrder::Conditional(conditional_order) });
+    }
+}
+
+//right click or backspace takes back the last order of selected actor, right click without any orders deselects
+pub fn handle_undo(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    mouse_input: Res<Input<MouseButton>>,
+    query: Query<(Entity, &actor::Actor), (With<actor::Selected>, With<ai::PlayerControlled>)>,
+    mut event_orders: EventWriter<actor::ActorOrderEvent>,
+) {
+    let mouse_right_pressed = mouse_input.just_pressed(MouseButton::Right);
+    if !mouse_right_pressed && !keyboard_input.just_pressed(KeyCode::Back) {
+        return;
+    }
+
+    for (selected, actor) in query.iter() {
+        if actor.has_planned_orders() {
+            event_orders.send(actor::ActorOrderEvent { entity: selected, order: actor::ActorOrder::Undo });
+        } else if mouse_right_pressed {
+            commands.entity(selected).remove::<actor::Selected> ();
         }
+    }
+}
 
-        helpers::spawn_movement_helper(
-            &mut commands,
-            &helper_materials,
-            Vec2::new(click_pos.x, click_pos.y),
-            Vec2::new(transform.translation.x, transform.translation.y),
-            selected.clone(),
-            htype
-        );
+impl Plugin for InputPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .add_system_set(
+                SystemSet::on_update(states::AppState::Introduction)
+                    .with_system(handle_keyboard_input_pre_round.system())
+            )
+            .add_system_set(
+                SystemSet::on_update(states::AppState::Scored)
+                    .with_system(handle_keyboard_input_pre_round.system())
+            )
+            .add_system_set(
+                SystemSet::on_update(states::AppState::HalfTime)
+                    .with_system(handle_keyboard_input_pre_round.system())
+            )
+            .add_system_set(
+                SystemSet::on_update(states::AppState::HandOver)
+                    .with_system(handle_keyboard_input_hand_over.system())
+            )
+            .add_system_set(
+                SystemSet::on_update(states::AppState::Plan)
+                    .with_system(handle_mouse_click.system()
+                        .before(actor::ActorLabel::ApplyOrders)
+                    )
+                    .with_system(handle_undo.system()
+                        .before(actor::ActorLabel::ApplyOrders)
+                    )
+                    .with_system(handle_conditional_input.system()
+                        .before(actor::ActorLabel::ApplyOrders)
+                    )
+                    .with_system(handle_hold_position_input.system()
+                        .before(actor::ActorLabel::ApplyOrders)
+                    )
+                    .with_system(handle_keyboard_input.system())
+            )
+            .add_system(bevy::input::system::exit_on_esc_system.system());
     }
-}
\ No newline at end of file
+}
@@ -5,37 +5,103 @@ use bevy_rapier2d::{
         dynamics::{RigidBodySet},
     }
 };
-use super::{actor, states, physics, team};
+use super::{actor, ai, ball, rules, states, physics, team};
+
+pub struct MatchupPlugin;
+
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MatchupLabel {
+    HandleEvents,
+}
 
 pub struct Matchup {
     pub score_home: u8,
     pub score_away: u8,
     actors: Vec<(Entity, Vec2, team::Team)>,
     pub serving_side: team::Team,
-    pub ball_home_position: Vec2,
-    pub ball_away_position: Vec2
+    pub last_score: Option<(ScoringMethod, Option<Entity>)>,
+    //players waiting for substitution, first one goes in first
+    pub bench_home: Vec<actor::ActorStats>,
+    pub bench_away: Vec<actor::ActorStats>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LineupSlot {
+    pub spawn_position: Vec2,
+    pub start_position: Vec2,
+    pub team: team::Team,
+    pub offense_focus: ai::AiFocus,
+    pub defense_focus: ai::AiFocus,
+    pub stats: actor::ActorStats,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScoringMethod {
+    //ball thrown or rolled into goal
+    Goal,
+    CarriedGoal,
+    Touchdown,
 }
 
 pub enum MatchupEvents {
-    Scored(team::Team, u8)
+    //scorer is the actor who carried the ball in or who had the ball last
+    Scored {
+        team_scored_against: team::Team,
+        points: u8,
+        method: ScoringMethod,
+        scorer: Option<Entity>,
+    }
 }
 
 impl  Matchup {
-    pub fn new(ball_home_position: Vec2, ball_away_position: Vec2) -> Self {
+    pub fn new() -> Self {
         Self {
             score_away: 0,
             score_home: 0,
             actors: vec![],
             serving_side: team::Team::Home,
-            ball_home_position,
-            ball_away_position,
+            last_score: None,
+            bench_home: vec![],
+            bench_away: vec![],
         }
     }
 
+    pub fn default() -> Self {
+        Self::new()
+    }
+
     pub fn add_actors(&mut self, actors: Vec<(Entity, Vec2, team::Team)>) {
         self.actors.extend(actors);
     }
 
+    pub fn get_actors(&self) -> &Vec<(Entity, Vec2, team::Team)> {
+        &self.actors
+    }
+
+    //actors are addressed by their slot (order in which they were added) outside of running game, e.g. in replays
+    pub fn get_actor_slot(&self, entity: Entity) -> Option<usize> {
+        self.actors.iter().position(|(actor_entity, _position, _team)| *actor_entity == entity)
+    }
+
+    pub fn get_actor_entity(&self, slot: usize) -> Option<Entity> {
+        self.actors.get(slot).map(|(actor_entity, _position, _team)| *actor_entity)
+    }
+
+    //substitute takes over the slot, so replays and savegames keep addressing it the same way
+    pub fn replace_actor(&mut self, slot: usize, entity: Entity) {
+        if let Some(actor) = self.actors.get_mut(slot) {
+            actor.0 = entity;
+        }
+    }
+
+    pub fn take_bench_player(&mut self, team: team::Team) -> Option<actor::ActorStats> {
+        let bench = match team {
+            team::Team::Home => &mut self.bench_home,
+            team::Team::Away => &mut self.bench_away,
+        };
+        if bench.is_empty() { None } else { Some(bench.remove(0)) }
+    }
+
     pub fn add_score(&mut self, team: team::Team, amount: u8) {
         match  team {
             team::Team::Home => {
@@ -48,6 +114,31 @@ impl  Matchup {
     }
 }
 
+//player_teams is empty when both sides are driven by AI and has both teams in hotseat
+pub fn spawn_actors(
+    commands: &mut Commands,
+    actor_sprites: Option<&actor::ActorTextures>,
+    lineup: &Vec<LineupSlot>,
+    player_teams: &[team::Team],
+) -> Vec<(Entity, Vec2, team::Team)> {
+    lineup.iter().map(|slot| -> (Entity, Vec2, team::Team) {
+        (
+            actor::spawn_actor(
+                commands,
+                actor_sprites,
+                slot.spawn_position,
+                slot.team,
+                player_teams.contains(&slot.team),
+                slot.offense_focus,
+                slot.defense_focus,
+                slot.stats,
+            ),
+            slot.start_position,
+            slot.team
+        )
+    }).collect()
+}
+
 pub fn move_actors_to_positions(
     mut query_actors: Query<(&mut actor::Actor, &RigidBodyHandleComponent)>,
     matchup: Res<Matchup>,
@@ -72,7 +163,7 @@ pub fn are_actors_in_position(
      let moving_actor = matchup.actors.iter().find(|(actor_entity, _position, _team)| -> bool {
         if let Ok(actor) = query_actors.get(*actor_entity) {
             match actor.act_action {
-                actor::ActorAction::Lookout | actor::ActorAction::Idle => false,
+                actor::ActorAction::Lookout | actor::ActorAction::Idle | actor::ActorAction::HoldPosition { x: _, y: _ } => false,
                 _ => true
             }
         } else {
@@ -104,14 +195,39 @@ pub fn handle_matchup_events(
     mut events: EventReader<MatchupEvents>,
     mut matchup: ResMut<Matchup>,
     mut app_state: ResMut<State<states::AppState>>,
+    mut progress: ResMut<rules::MatchProgress>,
+    ruleset: Res<rules::Ruleset>,
 ) {
-    for event in events.iter() {
-        match *event {
-            MatchupEvents::Scored(team_scored_against, amount) => {
-                matchup.add_score(team::get_oposing_team(team_scored_against), amount);
-                app_state.set(states::AppState::Scored).unwrap();
-                matchup.serving_side = team_scored_against;
-            }
-        }
+    if let Some(state) = rules::get_state_after_scores(&mut progress, &ruleset, &mut matchup, events.iter()) {
+        app_state.set(state).unwrap();
     }
-}
\ No newline at end of file
+}
+
+impl Plugin for MatchupPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .insert_resource(Matchup::default())
+            .add_event::<MatchupEvents>()
+            .add_system_set(
+                SystemSet::on_enter(states::AppState::MovingToStartPosition)
+                    .with_system(move_actors_to_positions.system())
+            )
+            .add_system_set(
+                SystemSet::on_update(states::AppState::MovingToStartPosition)
+                    .with_system(are_actors_in_position.system()
+                        .after(actor::ActorLabel::ActionStartStartPosition)
+                    )
+            )
+            .add_system_set(
+                SystemSet::on_exit(states::AppState::MovingToStartPosition)
+                    .with_system(update_actors_facing.system())
+            )
+            .add_system_set(
+                SystemSet::on_update(states::AppState::Play)
+                    .with_system(handle_matchup_events.system()
+                        .label(MatchupLabel::HandleEvents)
+                        .after(ball::BallLabel::UpdateThrown)
+                    )
+            );
+    }
+}
@@ -13,3 +13,21 @@ pub mod team;
 pub mod arena;
 pub mod matchup;
 pub mod ai;
+pub mod clock;
+pub mod simulation;
+pub mod rng;
+pub mod replay;
+pub mod savegame;
+pub mod formation;
+pub mod hotseat;
+pub mod network;
+pub mod preview;
+pub mod rules;
+pub mod passing;
+pub mod roster;
+pub mod stamina;
+pub mod tackle;
+pub mod injury;
+pub mod referee;
+pub mod zone;
+pub mod camera;
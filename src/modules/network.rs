@@ -0,0 +1,520 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}, mpsc::{self, Receiver, RecvTimeoutError, Sender}},
+    thread,
+    time::Duration,
+};
+use bevy::{app::AppExit, prelude::*};
+use serde::{Deserialize, Serialize};
+use super::{actor, ai, ball, matchup, rules, states, team};
+
+//bump whenever NetMessage or anything influencing simulation changes, both sides have to simulate the same way
+pub const PROTOCOL_VERSION: u32 = 15;
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+const RECONNECT_ATTEMPTS: u32 = 30;
+
+//lockstep multiplayer - clients only exchange orders at the end of Plan, Play is simulated by each client on its own
+pub struct NetworkPlugin;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum NetMessage {
+    Hello { version: u32, team: Option<team::Team> },
+    Welcome { team: team::Team, seed: u64, home_formation: String, away_formation: String, home_roster: String, away_roster: String, arena: String, ruleset: rules::Ruleset },
+    Rejected { reason: String },
+    Orders { turn: u32, orders: Vec<(usize, actor::ActorOrder)> },
+    TurnOrders { turn: u32, orders: Vec<(usize, actor::ActorOrder)> },
+    StateHash { turn: u32, hash: u64 },
+    Desync { turn: u32 },
+    //never sent over the network, client thread tells the game it gave up reconnecting
+    ConnectionLost { reason: String },
+}
+
+pub struct MatchSettings {
+    pub seed: u64,
+    pub home_formation: String,
+    pub away_formation: String,
+    pub home_roster: String,
+    pub away_roster: String,
+    pub arena: String,
+    pub ruleset: rules::Ruleset,
+}
+
+fn send_message(stream: &mut TcpStream, message: &NetMessage) -> std::io::Result<()> {
+    let mut line = ron::to_string(message).expect("Cannot serialize network message");
+    line.push('\n');
+    stream.write_all(line.as_bytes())
+}
+
+fn read_message(reader: &mut BufReader<TcpStream>) -> Option<NetMessage> {
+    let mut line = String::new();
+    match reader.read_line(&mut line) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => match ron::from_str(&line) {
+            Ok(message) => Some(message),
+            Err(e) => {
+                println!("Cannot parse network message '{}': {}", line.trim(), e);
+                None
+            }
+        }
+    }
+}
+
+// ----- host -----
+
+enum HostEvent {
+    Connected(u32, TcpStream),
+    Message(u32, NetMessage),
+    Disconnected(u32),
+}
+
+struct HostState {
+    settings: MatchSettings,
+    connections: HashMap<u32, (TcpStream, Option<team::Team>)>,
+    turn: u32,
+    orders: HashMap<team::Team, Vec<(usize, actor::ActorOrder)>>,
+    last_turn_orders: Option<NetMessage>,
+    hashes: HashMap<u32, Vec<u64>>,
+}
+
+impl HostState {
+    fn get_connection(&self, team: team::Team) -> Option<u32> {
+        self.connections.iter()
+            .find(|(_id, (_stream, connection_team))| *connection_team == Some(team))
+            .map(|(id, _connection)| *id)
+    }
+
+    fn send(&mut self, id: u32, message: &NetMessage) {
+        if let Some((stream, _team)) = self.connections.get_mut(&id) {
+            if send_message(stream, message).is_err() {
+                println!("Host: cannot send message to connection {}", id);
+            }
+        }
+    }
+
+    fn broadcast(&mut self, message: &NetMessage) {
+        let ids: Vec<u32> = self.connections.keys().cloned().collect();
+        for id in ids {
+            self.send(id, message);
+        }
+    }
+
+    //reconnecting client asks for its team again (it is free once host notices the old connection dropped), new clients get first free one
+    fn handshake(&mut self, id: u32, version: u32, requested_team: Option<team::Team>) {
+        if version != PROTOCOL_VERSION {
+            self.send(id, &NetMessage::Rejected { reason: format!("Host runs protocol version {}, client {}", PROTOCOL_VERSION, version) });
+            return;
+        }
+        let candidates = match requested_team {
+            Some(team) => vec![team],
+            None => vec![team::Team::Home, team::Team::Away],
+        };
+        let team = match candidates.into_iter().find(|team| self.get_connection(*team).is_none()) {
+            Some(team) => team,
+            None => {
+                self.send(id, &NetMessage::Rejected { reason: "Team is already connected".to_string() });
+                return;
+            }
+        };
+        if let Some((_stream, connection_team)) = self.connections.get_mut(&id) {
+            *connection_team = Some(team);
+        }
+        println!("Host: connection {} plays for {:?} team", id, team);
+        let welcome = NetMessage::Welcome {
+            team,
+            seed: self.settings.seed,
+            home_formation: self.settings.home_formation.clone(),
+            away_formation: self.settings.away_formation.clone(),
+            home_roster: self.settings.home_roster.clone(),
+            away_roster: self.settings.away_roster.clone(),
+            arena: self.settings.arena.clone(),
+            ruleset: self.settings.ruleset.clone(),
+        };
+        self.send(id, &welcome);
+    }
+
+    fn receive_orders(&mut self, id: u32, turn: u32, orders: Vec<(usize, actor::ActorOrder)>) {
+        let team = match self.connections.get(&id) {
+            Some((_stream, Some(team))) => *team,
+            _ => return
+        };
+        if turn < self.turn {
+            //client has reconnected and missed the result of the turn
+            if let Some(last_turn_orders) = self.last_turn_orders.clone() {
+                self.send(id, &last_turn_orders);
+            }
+            return;
+        }
+        self.orders.insert(team, orders);
+        if self.orders.len() < 2 {
+            return;
+        }
+        let mut combined = self.orders.remove(&team::Team::Home).unwrap_or_default();
+        combined.extend(self.orders.remove(&team::Team::Away).unwrap_or_default());
+        let turn_orders = NetMessage::TurnOrders { turn: self.turn, orders: combined };
+        self.broadcast(&turn_orders);
+        self.last_turn_orders = Some(turn_orders);
+        self.turn += 1;
+    }
+
+    fn receive_hash(&mut self, turn: u32, hash: u64) {
+        let hashes = self.hashes.entry(turn).or_insert(vec![]);
+        hashes.push(hash);
+        if hashes.len() < 2 {
+            return;
+        }
+        let is_synced = hashes.iter().all(|h| *h == hashes[0]);
+        self.hashes.remove(&turn);
+        if !is_synced {
+            println!("Host: clients desynced in turn {}", turn);
+            self.broadcast(&NetMessage::Desync { turn });
+        }
+    }
+}
+
+fn run_host(settings: MatchSettings, events: Receiver<HostEvent>) {
+    let mut host = HostState {
+        settings,
+        connections: HashMap::new(),
+        turn: 1,
+        orders: HashMap::new(),
+        last_turn_orders: None,
+        hashes: HashMap::new(),
+    };
+    for event in events.iter() {
+        match event {
+            HostEvent::Connected(id, stream) => {
+                host.connections.insert(id, (stream, None));
+            },
+            HostEvent::Disconnected(id) => {
+                println!("Host: connection {} dropped", id);
+                host.connections.remove(&id);
+            },
+            HostEvent::Message(id, message) => match message {
+                NetMessage::Hello { version, team } => host.handshake(id, version, team),
+                NetMessage::Orders { turn, orders } => host.receive_orders(id, turn, orders),
+                NetMessage::StateHash { turn, hash } => host.receive_hash(turn, hash),
+                _ => println!("Host: unexpected message {:?}", message),
+            }
+        }
+    }
+}
+
+//host runs in background threads of one of the players' game, that player connects to it like the other one
+pub fn start_host(address: &str, settings: MatchSettings) {
+    let listener = TcpListener::bind(address).expect(format!("Cannot listen on {}", address).as_str());
+    println!("Hosting match on {}", address);
+    let (event_sender, event_receiver) = mpsc::channel();
+    thread::spawn(move || run_host(settings, event_receiver));
+    thread::spawn(move || {
+        for (id, stream) in listener.incoming().enumerate() {
+            let id = id as u32;
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(_) => continue
+            };
+            let writer = match stream.try_clone() {
+                Ok(writer) => writer,
+                Err(_) => continue
+            };
+            let event_sender = event_sender.clone();
+            if event_sender.send(HostEvent::Connected(id, writer)).is_err() {
+                return;
+            }
+            thread::spawn(move || {
+                let mut reader = BufReader::new(stream);
+                while let Some(message) = read_message(&mut reader) {
+                    if event_sender.send(HostEvent::Message(id, message)).is_err() {
+                        return;
+                    }
+                }
+                let _ = event_sender.send(HostEvent::Disconnected(id));
+            });
+        }
+    });
+}
+
+// ----- client -----
+
+pub struct NetworkClient {
+    outgoing: Mutex<Sender<NetMessage>>,
+    incoming: Mutex<Receiver<NetMessage>>,
+}
+impl NetworkClient {
+    //client thread is gone once the connection is lost for good, the game finds out from ConnectionLost
+    pub fn send(&self, message: NetMessage) {
+        if self.outgoing.lock().unwrap().send(message).is_err() {
+            println!("Cannot send network message, connection is closed");
+        }
+    }
+}
+
+fn handshake(address: &str, team: Option<team::Team>) -> Result<(TcpStream, NetMessage), String> {
+    let mut stream = TcpStream::connect(address).map_err(|e| format!("Cannot connect to {}: {}", address, e))?;
+    send_message(&mut stream, &NetMessage::Hello { version: PROTOCOL_VERSION, team }).map_err(|e| e.to_string())?;
+    let mut reader = BufReader::new(stream.try_clone().map_err(|e| e.to_string())?);
+    match read_message(&mut reader) {
+        Some(NetMessage::Rejected { reason }) => Err(reason),
+        Some(welcome @ NetMessage::Welcome { .. }) => Ok((stream, welcome)),
+        _ => Err("Host closed connection during handshake".to_string()),
+    }
+}
+
+fn spawn_reader(stream: &TcpStream, incoming: Sender<NetMessage>) -> Arc<AtomicBool> {
+    let is_alive = Arc::new(AtomicBool::new(true));
+    let reader_alive = is_alive.clone();
+    let mut reader = BufReader::new(stream.try_clone().expect("Cannot clone network stream"));
+    thread::spawn(move || {
+        while let Some(message) = read_message(&mut reader) {
+            if incoming.send(message).is_err() {
+                break;
+            }
+        }
+        reader_alive.store(false, Ordering::SeqCst);
+    });
+    is_alive
+}
+
+fn reconnect(address: &str, team: team::Team) -> Result<TcpStream, String> {
+    for attempt in 1..=RECONNECT_ATTEMPTS {
+        println!("Connection lost, reconnecting ({}/{})", attempt, RECONNECT_ATTEMPTS);
+        thread::sleep(RECONNECT_INTERVAL);
+        match handshake(address, Some(team)) {
+            Ok((stream, _welcome)) => return Ok(stream),
+            Err(e) => println!("{}", e),
+        }
+    }
+    Err(format!("Cannot reconnect to {}", address))
+}
+
+//last sent orders are sent again after reconnect, host answers with the turn result if it was already resolved
+fn run_client(address: String, team: team::Team, mut stream: TcpStream, outgoing: Receiver<NetMessage>, incoming: Sender<NetMessage>) {
+    let mut is_alive = spawn_reader(&stream, incoming.clone());
+    let mut last_orders: Option<NetMessage> = None;
+    loop {
+        let message = match outgoing.recv_timeout(Duration::from_millis(100)) {
+            Ok(message) => Some(message),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => return,
+        };
+        if let Some(orders @ NetMessage::Orders { .. }) = message.as_ref() {
+            last_orders = Some(orders.clone());
+        }
+        let is_sent = message.as_ref().map_or(true, |message| send_message(&mut stream, message).is_ok());
+        if is_sent && is_alive.load(Ordering::SeqCst) {
+            continue;
+        }
+        stream = match reconnect(&address, team) {
+            Ok(stream) => stream,
+            Err(reason) => {
+                let _ = incoming.send(NetMessage::ConnectionLost { reason });
+                return;
+            }
+        };
+        is_alive = spawn_reader(&stream, incoming.clone());
+        if let Some(orders) = last_orders.as_ref() {
+            let _ = send_message(&mut stream, orders);
+        }
+    }
+}
+
+//blocking, game can't be set up before the seed, formations and rosters are known
+pub fn connect(address: &str) -> Result<(NetworkClient, team::Team, MatchSettings), String> {
+    let (stream, welcome) = handshake(address, None)?;
+    let (team, settings) = match welcome {
+        NetMessage::Welcome { team, seed, home_formation, away_formation, home_roster, away_roster, arena, ruleset } => (team, MatchSettings { seed, home_formation, away_formation, home_roster, away_roster, arena, ruleset }),
+        _ => unreachable!(),
+    };
+    println!("Connected to {}, playing for {:?} team", address, team);
+    let (outgoing_sender, outgoing_receiver) = mpsc::channel();
+    let (incoming_sender, incoming_receiver) = mpsc::channel();
+    let address = address.to_string();
+    thread::spawn(move || run_client(address, team, stream, outgoing_receiver, incoming_sender));
+    Ok((
+        NetworkClient {
+            outgoing: Mutex::new(outgoing_sender),
+            incoming: Mutex::new(incoming_receiver),
+        },
+        team,
+        settings,
+    ))
+}
+
+// ----- game -----
+
+pub struct NetworkSession {
+    pub team: team::Team,
+    pub turn: u32,
+    local_orders: Vec<(usize, actor::ActorOrder)>,
+    received_orders: HashMap<u32, Vec<(usize, actor::ActorOrder)>>,
+}
+impl NetworkSession {
+    pub fn new(team: team::Team) -> Self {
+        Self {
+            team,
+            turn: 0,
+            local_orders: vec![],
+            received_orders: HashMap::new(),
+        }
+    }
+}
+
+//remote actors are not controlled by anyone locally, they only follow orders coming from the host
+pub fn release_remote_actors(
+    commands: &mut Commands,
+    actors: &Vec<(Entity, Vec2, team::Team)>,
+    local_team: team::Team,
+) {
+    for (entity, _position, team) in actors.iter() {
+        if *team != local_team {
+            commands.entity(*entity).remove::<ai::AiControlled>();
+        }
+    }
+}
+
+fn start_turn(
+    mut session: ResMut<NetworkSession>,
+    mut planning_hold: ResMut<states::PlanningHold>,
+) {
+    session.turn += 1;
+    session.local_orders.clear();
+    planning_hold.0 = Some(states::AppState::WaitingForOrders);
+}
+
+fn record_local_orders(
+    mut events: EventReader<actor::ActorOrderEvent>,
+    mut session: ResMut<NetworkSession>,
+    matchup: Res<matchup::Matchup>,
+) {
+    for event in events.iter() {
+        if let Some(slot) = matchup.get_actor_slot(event.entity) {
+            if matchup.get_actors()[slot].2 == session.team {
+                session.local_orders.push((slot, event.order));
+            }
+        }
+    }
+}
+
+fn submit_orders(
+    session: Res<NetworkSession>,
+    client: Res<NetworkClient>,
+) {
+    client.send(NetMessage::Orders { turn: session.turn, orders: session.local_orders.clone() });
+}
+
+fn receive_messages(
+    client: Res<NetworkClient>,
+    mut session: ResMut<NetworkSession>,
+    mut app_exit_events: EventWriter<AppExit>,
+) {
+    let incoming = client.incoming.lock().unwrap();
+    for message in incoming.try_iter() {
+        match message {
+            NetMessage::TurnOrders { turn, orders } => {
+                session.received_orders.insert(turn, orders);
+            },
+            NetMessage::Desync { turn } => {
+                println!("Game desynced in turn {}, match cannot continue!", turn);
+                app_exit_events.send(AppExit);
+            },
+            NetMessage::ConnectionLost { reason } => {
+                println!("{}, match cannot continue!", reason);
+                app_exit_events.send(AppExit);
+            },
+            _ => println!("Unexpected message {:?}", message),
+        }
+    }
+}
+
+//own orders were already applied during Plan, only the other team's are applied here
+fn play_received_orders(
+    mut session: ResMut<NetworkSession>,
+    mut app_state: ResMut<State<states::AppState>>,
+    mut query_actors: Query<&mut actor::Actor>,
+    matchup: Res<matchup::Matchup>,
+) {
+    let turn = session.turn;
+    let orders = match session.received_orders.remove(&turn) {
+        Some(orders) => orders,
+        None => return
+    };
+    for (slot, order) in orders.iter() {
+        let (entity, _position, team) = matchup.get_actors().get(*slot).expect(format!("Host sent order for missing actor {}", slot).as_str());
+        if *team == session.team {
+            continue;
+        }
+        if let Ok(mut actor) = query_actors.get_mut(*entity) {
+            actor::apply_order(&mut actor, *order);
+        }
+    }
+    app_state.replace(states::AppState::Play).unwrap();
+}
+
+fn hash_vec2(hasher: &mut DefaultHasher, v: Vec2) {
+    v.x.to_bits().hash(hasher);
+    v.y.to_bits().hash(hasher);
+}
+
+fn send_state_hash(
+    session: Res<NetworkSession>,
+    client: Res<NetworkClient>,
+    matchup: Res<matchup::Matchup>,
+    ball_possession: Res<ball::BallPossession>,
+    query_transforms: Query<&Transform>,
+    query_ball: Query<&Transform, With<ball::Ball>>,
+) {
+    let mut hasher = DefaultHasher::new();
+    for (entity, _position, _team) in matchup.get_actors().iter() {
+        if let Ok(transform) = query_transforms.get(*entity) {
+            hash_vec2(&mut hasher, Vec2::from(transform.translation));
+        }
+    }
+    if let Ok(transform) = query_ball.single() {
+        hash_vec2(&mut hasher, Vec2::from(transform.translation));
+    }
+    ball_possession.get().and_then(|entity| matchup.get_actor_slot(entity)).hash(&mut hasher);
+    client.send(NetMessage::StateHash { turn: session.turn, hash: hasher.finish() });
+}
+
+impl Plugin for NetworkPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .add_system(receive_messages.system())
+            .add_system_set(
+                SystemSet::on_update(states::AppState::Introduction)
+                    .with_system(states::advance_to_start_position.system())
+            )
+            .add_system_set(
+                SystemSet::on_update(states::AppState::Scored)
+                    .with_system(states::advance_to_start_position.system())
+            )
+            .add_system_set(
+                SystemSet::on_update(states::AppState::HalfTime)
+                    .with_system(states::advance_to_start_position.system())
+            )
+            .add_system_set(
+                SystemSet::on_enter(states::AppState::Plan)
+                    .with_system(start_turn.system())
+            )
+            .add_system_set(
+                SystemSet::on_update(states::AppState::Plan)
+                    .with_system(record_local_orders.system()
+                        .after(actor::ActorLabel::ApplyOrders)
+                    )
+            )
+            .add_system_set(
+                SystemSet::on_enter(states::AppState::WaitingForOrders)
+                    .with_system(submit_orders.system())
+            )
+            .add_system_set(
+                SystemSet::on_update(states::AppState::WaitingForOrders)
+                    .with_system(play_received_orders.system())
+            )
+            .add_system_set(
+                SystemSet::on_exit(states::AppState::Play)
+                    .with_system(send_state_hash.system())
+            );
+    }
+}
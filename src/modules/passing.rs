@@ -0,0 +1,127 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+//throw range of an average thrower, lob can use all of it
+pub const DEFAULT_THROW_RANGE: f32 = 300.0;
+//bullet pass is flat and fast, so it can't go as far as lob
+const BULLET_RANGE_FACTOR: f32 = 0.7;
+const LOB_SPEED: f32 = 180.0;
+const BULLET_SPEED: f32 = 320.0;
+//lob flies over everybody's head until this part of the flight
+const LOB_CATCHABLE_PROGRESS: f32 = 0.8;
+//how much bigger ball looks at the top of the lob
+const LOB_HEIGHT_SCALE: f32 = 0.8;
+//opponents this close to the catcher make catching harder
+pub const PRESSURE_RADIUS: f32 = 40.0;
+const PRESSURE_PENALTY: f32 = 0.15;
+const DISTANCE_PENALTY: f32 = 0.2;
+const MIN_CHANCE: f32 = 0.05;
+const MAX_CHANCE: f32 = 0.95;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum PassType {
+    Lob,
+    Bullet,
+}
+
+pub fn get_pass_speed(pass_type: PassType) -> f32 {
+    match pass_type {
+        PassType::Lob => LOB_SPEED,
+        PassType::Bullet => BULLET_SPEED,
+    }
+}
+
+pub fn get_pass_range(pass_type: PassType, throw_range: f32) -> f32 {
+    match pass_type {
+        PassType::Lob => throw_range,
+        PassType::Bullet => throw_range * BULLET_RANGE_FACTOR,
+    }
+}
+
+//throws further than the range land at its edge in the same direction
+pub fn clamp_to_range(from: Vec2, target: Vec2, pass_type: PassType, throw_range: f32) -> Vec2 {
+    let range = get_pass_range(pass_type, throw_range);
+    let delta = target - from;
+    if delta.length() > range {
+        from + delta.normalize() * range
+    } else {
+        target
+    }
+}
+
+//progress is elapsed part of the flight between 0 and 1
+pub fn is_catchable(pass_type: PassType, progress: f32) -> bool {
+    match pass_type {
+        PassType::Lob => progress >= LOB_CATCHABLE_PROGRESS,
+        PassType::Bullet => true,
+    }
+}
+
+//ball sprite grows with its height, bullet stays low
+pub fn get_ball_scale(pass_type: PassType, progress: f32) -> f32 {
+    match pass_type {
+        PassType::Lob => 1.0 + LOB_HEIGHT_SCALE * (std::f32::consts::PI * progress).sin(),
+        PassType::Bullet => 1.0,
+    }
+}
+
+//part of the flight path where ball can be caught, used for landing zone helper
+pub fn get_catchable_start(pass_type: PassType) -> f32 {
+    match pass_type {
+        PassType::Lob => LOB_CATCHABLE_PROGRESS,
+        PassType::Bullet => 0.0,
+    }
+}
+
+//teammates of the thrower catch, opponents intercept, longer passes are harder for the receiver and easier to read for defenders
+pub fn get_catch_chance(pass_type: PassType, distance: f32, is_interception: bool, pressure: usize, catching: f32) -> f32 {
+    let distance_factor = (distance / DEFAULT_THROW_RANGE).min(1.0) * DISTANCE_PENALTY;
+    let chance = match (is_interception, pass_type) {
+        (false, PassType::Lob) => 0.9 - distance_factor,
+        (false, PassType::Bullet) => 0.8 - distance_factor,
+        (true, PassType::Lob) => 0.45 + distance_factor,
+        (true, PassType::Bullet) => 0.3 + distance_factor,
+    } * catching - pressure as f32 * PRESSURE_PENALTY;
+    chance.max(MIN_CHANCE).min(MAX_CHANCE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn longer_pass_is_harder_to_catch_and_easier_to_intercept() {
+        for pass_type in [PassType::Lob, PassType::Bullet].iter() {
+            let short_catch = get_catch_chance(*pass_type, 50.0, false, 0, 1.0);
+            let long_catch = get_catch_chance(*pass_type, 250.0, false, 0, 1.0);
+            let short_interception = get_catch_chance(*pass_type, 50.0, true, 0, 1.0);
+            let long_interception = get_catch_chance(*pass_type, 250.0, true, 0, 1.0);
+            assert!(long_catch < short_catch);
+            assert!(long_interception > short_interception);
+        }
+    }
+
+    #[test]
+    fn bullet_is_harder_to_intercept_than_lob() {
+        let lob = get_catch_chance(PassType::Lob, 150.0, true, 0, 1.0);
+        let bullet = get_catch_chance(PassType::Bullet, 150.0, true, 0, 1.0);
+        assert!(bullet < lob);
+    }
+
+    #[test]
+    fn pressure_and_catching_change_the_chance() {
+        let free = get_catch_chance(PassType::Lob, 150.0, false, 0, 1.0);
+        let pressured = get_catch_chance(PassType::Lob, 150.0, false, 1, 1.0);
+        let skilled = get_catch_chance(PassType::Lob, 150.0, false, 0, 1.05);
+        assert!(pressured < free);
+        assert!(skilled > free);
+    }
+
+    #[test]
+    fn chance_is_clamped() {
+        assert_eq!(get_catch_chance(PassType::Bullet, 300.0, false, 10, 1.0), MIN_CHANCE);
+        assert_eq!(get_catch_chance(PassType::Lob, 0.0, false, 0, 2.0), MAX_CHANCE);
+        //passes beyond the default range are not penalized any further
+        assert_eq!(get_catch_chance(PassType::Lob, 300.0, false, 0, 1.0), get_catch_chance(PassType::Lob, 600.0, false, 0, 1.0));
+    }
+}
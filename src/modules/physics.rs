@@ -1,13 +1,32 @@
 use bevy::prelude::*;
 use bevy_rapier2d::{
-    na::Vector2,
-    physics::{RigidBodyHandleComponent, RapierConfiguration},
+    na::{Point2, Vector2},
+    physics::{RigidBodyHandleComponent, RapierConfiguration, RapierPhysicsPlugin},
     rapier::{
         dynamics::{RigidBodySet, RigidBodyBuilder},
-        geometry::{ColliderBuilder},
-        math::Isometry,
+        geometry::{ColliderBuilder, InteractionGroups},
+        math::{Isometry, Vector},
     }
 };
+use super::states;
+
+pub struct PhysicsPlugin;
+
+//matches default rapier IntegrationParameters::dt, one physics step per frame
+pub const PHYSICS_STEP: f32 = 1.0 / 60.0;
+
+//actors are in their own collision group, so high ball can fly over them and still bounce off walls
+fn get_actor_groups() -> InteractionGroups {
+    InteractionGroups::new(0b10, 0xffff)
+}
+
+fn get_ball_groups(is_high: bool) -> InteractionGroups {
+    if is_high {
+        InteractionGroups::new(0b01, 0xfffd)
+    } else {
+        InteractionGroups::new(0b01, 0xffff)
+    }
+}
 
 pub fn set_rb_properties(
     rigid_body_handle: &RigidBodyHandleComponent,
@@ -41,6 +60,15 @@ pub fn get_velocity(
         })
 }
 
+pub fn get_linear_damping(
+    rigid_body_handle: &RigidBodyHandleComponent,
+    rigid_body_set: &ResMut<RigidBodySet>,
+) -> Option<f32> {
+    rigid_body_set
+        .get(rigid_body_handle.handle())
+        .map(|rb| rb.linear_damping)
+}
+
 pub fn create_physics_actor(
     commands: &mut Commands,
     e: Entity,
@@ -56,6 +84,7 @@ pub fn create_physics_actor(
         .density(80.0)
         .friction(0.0)
         .restitution(0.2)
+        .collision_groups(get_actor_groups())
         .user_data(e.to_bits() as u128)
     );
 }
@@ -78,6 +107,7 @@ pub fn create_physics_ball(
     position: Vec2,
     velocity_vector: Vec2,
     linear_damping: f32,
+    is_high: bool,
 ) {
     commands.entity(e).insert(
     RigidBodyBuilder::new_dynamic()
@@ -91,16 +121,37 @@ pub fn create_physics_ball(
         .density(1.0)
         .friction(0.7)
         .restitution(0.5)
+        .collision_groups(get_ball_groups(is_high))
         .user_data(e.to_bits() as u128)
     );
 }
 
+//collider of a wall, obstacle or zone around its position
+#[derive(Debug, Clone)]
+pub enum WallShape {
+    Cuboid { w: f32, h: f32 },
+    Ball { radius: f32 },
+    //points are relative to the position
+    ConvexPolygon(Vec<Vec2>),
+}
+
+fn get_shape_collider(shape: &WallShape) -> ColliderBuilder {
+    match shape {
+        WallShape::Cuboid { w, h } => ColliderBuilder::cuboid(w/2.0, h/2.0),
+        WallShape::Ball { radius } => ColliderBuilder::ball(*radius),
+        WallShape::ConvexPolygon(points) => {
+            let points: Vec<Point2<f32>> = points.iter().map(|point| Point2::new(point.x, point.y)).collect();
+            ColliderBuilder::convex_hull(&points).expect("Cannot create collider from flat polygon!")
+        },
+    }
+}
+
 pub fn create_physics_wall(
     commands: &mut Commands,
     e: Entity,
     position: Vec2,
-    w: f32,
-    h: f32,
+    shape: &WallShape,
+    restitution: f32,
 ) {
     commands.entity(e).insert(
     RigidBodyBuilder::new_static()
@@ -108,10 +159,10 @@ pub fn create_physics_wall(
         .lock_rotations()
     );
     commands.entity(e).insert(
-    ColliderBuilder::cuboid(w/2.0, h/2.0)
+    get_shape_collider(shape)
         .density(1.0)
         .friction(0.7)
-        .restitution(0.1)
+        .restitution(restitution)
         .user_data(e.to_bits() as u128)
     );
 }
@@ -136,4 +187,63 @@ pub fn create_physics_goalpost(
         .sensor(true)
         .user_data(e.to_bits() as u128)
     );
-}
\ No newline at end of file
+}
+
+//sensor covering part of the ground, actors and ball pass through it
+pub fn create_physics_zone(
+    commands: &mut Commands,
+    e: Entity,
+    position: Vec2,
+    shape: &WallShape,
+) {
+    commands.entity(e).insert(
+    RigidBodyBuilder::new_static()
+        .translation(position.x, position.y)
+        .lock_rotations()
+    );
+    commands.entity(e).insert(
+    get_shape_collider(shape)
+        .sensor(true)
+        .user_data(e.to_bits() as u128)
+    );
+}
+
+fn setup_physics(
+    mut configuration: ResMut<RapierConfiguration>,
+) {
+    configuration.gravity = Vector::y() * 0.0;
+    // configuration.time_dependent_number_of_timesteps = true;
+}
+
+impl Plugin for PhysicsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .add_plugin(RapierPhysicsPlugin)
+            // .add_plugin(RapierRenderPlugin)
+            .add_startup_system(setup_physics.system())
+            .add_system_set(
+                SystemSet::on_enter(states::AppState::Scored)
+                    .with_system(pause_physics.system())
+            )
+            .add_system_set(
+                SystemSet::on_enter(states::AppState::HalfTime)
+                    .with_system(pause_physics.system())
+            )
+            .add_system_set(
+                SystemSet::on_enter(states::AppState::MatchOver)
+                    .with_system(pause_physics.system())
+            )
+            .add_system_set(
+                SystemSet::on_enter(states::AppState::MovingToStartPosition)
+                    .with_system(resume_physics.system())
+            )
+            .add_system_set(
+                SystemSet::on_enter(states::AppState::Plan)
+                    .with_system(pause_physics.system())
+            )
+            .add_system_set(
+                SystemSet::on_enter(states::AppState::Play)
+                    .with_system(resume_physics.system())
+            );
+    }
+}
@@ -0,0 +1,285 @@
+use bevy::prelude::*;
+use crate::RrGamePlugin;
+use super::{actor, ai, arena, ball, helpers, hotseat, matchup, rng, round, savegame, states, team};
+
+//every n-th simulated step is drawn as a ghost dot
+const GHOST_STEP: usize = 4;
+//a few steps to get from Introduction through Plan into Play on top of the round itself
+const MAX_EXTRA_UPDATES: u32 = 10;
+
+//forks the game from the Plan snapshot in a headless app and plays the next round with orders given so far,
+//opponent follows the same AI plan as in the real game or stands still when controlled by other player
+pub struct PreviewPlugin;
+struct PreviewSimulationPlugin;
+
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PreviewLabel {
+    RecordOrders,
+}
+
+pub struct Preview {
+    pub is_enabled: bool,
+    is_dirty: bool,
+    orders: Vec<(usize, team::Team, actor::ActorOrder)>,
+}
+impl Preview {
+    pub fn new() -> Self {
+        Self {
+            is_enabled: false,
+            is_dirty: false,
+            orders: vec![],
+        }
+    }
+    pub fn toggle(&mut self) {
+        self.is_enabled = !self.is_enabled;
+        self.is_dirty = true;
+    }
+}
+
+pub struct PreviewGhost;
+
+//trajectories of the simulated round, actors are in Matchup slot order
+#[derive(Default)]
+pub struct PreviewTrace {
+    pub actors: Vec<Vec<Vec2>>,
+    pub ball: Vec<Vec2>,
+    pub tackles: Vec<Vec2>,
+    finished: bool,
+}
+
+struct PreviewOrders(Vec<(usize, actor::ActorOrder)>);
+
+pub fn simulate_turn(save_game: savegame::SaveGame, orders: Vec<(usize, actor::ActorOrder)>) -> PreviewTrace {
+    let mut builder = App::build();
+    builder
+        .insert_resource(rng::GameSeed(save_game.seed))
+        .insert_resource(save_game.arena.clone())
+        .insert_resource(savegame::PendingLoad(save_game))
+        .insert_resource(PreviewOrders(orders))
+        .init_resource::<PreviewTrace>()
+        .add_plugins(MinimalPlugins)
+        .add_plugin(PreviewSimulationPlugin);
+    let mut app = std::mem::take(&mut builder.app);
+
+    let max_updates = round::get_round_steps() + MAX_EXTRA_UPDATES;
+    for _ in 0..max_updates {
+        app.update();
+        if app.world.get_resource::<PreviewTrace>().map_or(false, |trace| trace.finished) {
+            break;
+        }
+    }
+    app.world.remove_resource::<PreviewTrace>().unwrap_or_default()
+}
+
+//all orders, including AI ones, are replayed, so nobody is left to be controlled
+fn initialize_preview(
+    mut commands: Commands,
+    mut matchup_res: ResMut<matchup::Matchup>,
+    pending_load: Res<savegame::PendingLoad>,
+) {
+    let actors = matchup::spawn_actors(&mut commands, None, &pending_load.0.get_lineup(), &[]);
+    for (entity, _position, _team) in actors.iter() {
+        commands.entity(*entity).remove::<ai::AiControlled>();
+    }
+    matchup_res.add_actors(actors);
+    arena::create_from_layout(&mut commands, None, None, &pending_load.0.arena, None, pending_load.0.ruleset.points.has_touchdowns());
+}
+
+fn play_preview_orders(
+    orders: Res<PreviewOrders>,
+    matchup: Res<matchup::Matchup>,
+    mut event_orders: EventWriter<actor::ActorOrderEvent>,
+    mut app_state: ResMut<State<states::AppState>>,
+) {
+    for (slot, order) in orders.0.iter() {
+        if let Some(entity) = matchup.get_actor_entity(*slot) {
+            event_orders.send(actor::ActorOrderEvent { entity, order: *order });
+        }
+    }
+    app_state.set(states::AppState::Play).unwrap();
+}
+
+fn trace_round(
+    mut trace: ResMut<PreviewTrace>,
+    matchup: Res<matchup::Matchup>,
+    query_actors: Query<(&actor::Actor, &Transform)>,
+    query_ball: Query<&Transform, With<ball::Ball>>,
+) {
+    let actors = matchup.get_actors();
+    trace.actors.resize(actors.len(), vec![]);
+    for (slot, (entity, _start_position, _team)) in actors.iter().enumerate() {
+        if let Ok((actor, transform)) = query_actors.get(*entity) {
+            trace.actors[slot].push(Vec2::from(transform.translation));
+            if let actor::ActorAction::Tackling { x, y } = actor.act_action {
+                let hit_position = Vec2::new(x, y);
+                if !trace.tackles.contains(&hit_position) {
+                    trace.tackles.push(hit_position);
+                }
+            }
+        }
+    }
+    if let Ok(transform) = query_ball.single() {
+        trace.ball.push(Vec2::from(transform.translation));
+    }
+}
+
+fn finish_trace(
+    mut trace: ResMut<PreviewTrace>,
+) {
+    trace.finished = true;
+}
+
+fn reset_preview(
+    mut preview: ResMut<Preview>,
+) {
+    preview.orders.clear();
+    preview.is_dirty = true;
+}
+
+fn mark_preview_dirty(
+    mut preview: ResMut<Preview>,
+) {
+    preview.is_dirty = true;
+}
+
+fn record_preview_orders(
+    mut events: EventReader<actor::ActorOrderEvent>,
+    mut preview: ResMut<Preview>,
+    matchup: Res<matchup::Matchup>,
+    query_teams: Query<&team::Team>,
+) {
+    for event in events.iter() {
+        if let (Some(slot), Ok(team)) = (matchup.get_actor_slot(event.entity), query_teams.get(event.entity)) {
+            preview.orders.push((slot, *team, event.order));
+            preview.is_dirty = true;
+        }
+    }
+}
+
+fn cleanup_ghosts(
+    mut commands: Commands,
+    query_ghosts: Query<Entity, With<PreviewGhost>>,
+) {
+    for ghost in query_ghosts.iter() {
+        commands.entity(ghost).despawn_recursive();
+    }
+}
+
+fn spawn_ghost(
+    commands: &mut Commands,
+    material: Handle<ColorMaterial>,
+    position: Vec2,
+    size: f32,
+) {
+    commands
+        .spawn_bundle(SpriteBundle {
+            material,
+            sprite: Sprite::new(Vec2::new(size, size)),
+            transform: Transform::from_translation(Vec3::new(position.x, position.y, 0.25)),
+            ..Default::default()
+        })
+        .insert(PreviewGhost);
+}
+
+fn update_preview(
+    mut commands: Commands,
+    mut preview: ResMut<Preview>,
+    snapshot: Res<savegame::PlanSnapshot>,
+    game_rng: Res<rng::GameRng>,
+    helper_materials: Res<helpers::HelperMaterials>,
+    query_ghosts: Query<Entity, With<PreviewGhost>>,
+    hotseat: Option<Res<hotseat::Hotseat>>,
+) {
+    if !preview.is_dirty {
+        return;
+    }
+    preview.is_dirty = false;
+    for ghost in query_ghosts.iter() {
+        commands.entity(ghost).despawn_recursive();
+    }
+    if !preview.is_enabled {
+        return;
+    }
+    let mut save_game = match snapshot.get() {
+        Some(save_game) => save_game.clone(),
+        None => return
+    };
+    //AI has already planned this turn, preview continues from the current state of randomness
+    save_game.rng_word_pos = game_rng.0.get_word_pos() as u64;
+    //players in hotseat can't peek at each other's orders
+    let orders = preview.orders.iter()
+        .filter(|(_slot, team, _order)| hotseat::can_plan(hotseat.as_deref(), *team))
+        .map(|(slot, _team, order)| (*slot, *order))
+        .collect();
+
+    let trace = simulate_turn(save_game, orders);
+    for positions in trace.actors.iter() {
+        for position in positions.iter().step_by(GHOST_STEP) {
+            spawn_ghost(&mut commands, helper_materials.ghost_actor.clone(), *position, 3.0);
+        }
+    }
+    for position in trace.ball.iter().step_by(GHOST_STEP) {
+        spawn_ghost(&mut commands, helper_materials.ghost_ball.clone(), *position, 2.0);
+    }
+    for position in trace.tackles.iter() {
+        spawn_ghost(&mut commands, helper_materials.ghost_tackle.clone(), *position, 6.0);
+    }
+}
+
+impl Plugin for PreviewSimulationPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .add_plugin(RrGamePlugin)
+            .add_plugin(savegame::SaveGamePlugin)
+            .add_startup_stage("game_initialization", SystemStage::single(initialize_preview.system()))
+            .add_system_set(
+                SystemSet::on_update(states::AppState::Plan)
+                    .with_system(play_preview_orders.system()
+                        .before(actor::ActorLabel::ApplyOrders)
+                    )
+            )
+            .add_system_set(
+                SystemSet::on_update(states::AppState::Play)
+                    .with_system(trace_round.system()
+                        .after(actor::ActorLabel::HandleEvents)
+                    )
+            )
+            .add_system_set(
+                SystemSet::on_exit(states::AppState::Play)
+                    .with_system(finish_trace.system())
+            );
+    }
+}
+
+impl Plugin for PreviewPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .insert_resource(Preview::new())
+            .add_system_set(
+                SystemSet::on_enter(states::AppState::Plan)
+                    .with_system(reset_preview.system())
+            )
+            .add_system_set(
+                SystemSet::on_update(states::AppState::Plan)
+                    .with_system(record_preview_orders.system()
+                        .label(PreviewLabel::RecordOrders)
+                        .after(actor::ActorLabel::ApplyOrders)
+                    )
+                    .with_system(update_preview.system()
+                        .after(PreviewLabel::RecordOrders)
+                    )
+            )
+            .add_system_set(
+                SystemSet::on_pause(states::AppState::Plan)
+                    .with_system(cleanup_ghosts.system())
+            )
+            .add_system_set(
+                SystemSet::on_resume(states::AppState::Plan)
+                    .with_system(mark_preview_dirty.system())
+            )
+            .add_system_set(
+                SystemSet::on_exit(states::AppState::Plan)
+                    .with_system(cleanup_ghosts.system())
+            );
+    }
+}
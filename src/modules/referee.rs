@@ -0,0 +1,274 @@
+use bevy::prelude::*;
+use bevy_rapier2d::{
+    physics::RigidBodyHandleComponent,
+    rapier::{
+        dynamics::{RigidBodySet},
+    }
+};
+use super::{actor, ai, animation, arena, ball, collision, injury, physics, round, rules, states, team, utils};
+
+//tackler running within this angle of the direction his victim runs hits him from behind
+const FROM_BEHIND_COS: f32 = 0.7;
+//opponents of the actor taking a free throw are moved at least this far away from him
+const FREE_THROW_DISTANCE: f32 = 120.0;
+
+pub struct RefereePlugin;
+
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RefereeLabel {
+    CallFouls,
+    ApplyPenalties,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FoulKind {
+    //tackle on an actor who doesn't hold the ball
+    NoBallTackle,
+    //hit on an actor who is already down
+    LateHit,
+    TackleFromBehind,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Foul {
+    pub kind: FoulKind,
+    pub penalty: rules::Penalty,
+    pub offender: Entity,
+    pub victim: Entity,
+    //team of the offender
+    pub team: team::Team,
+}
+
+//actor sent off by the referee, he stays down until the count runs out
+#[derive(Clone, Copy, Debug)]
+pub struct SinBin(pub u32);
+
+//fouls called in the current round, restart of the play cancels ball penalties that were not given yet
+pub struct Referee {
+    pub calls: Vec<Foul>,
+}
+impl Referee {
+    pub fn new() -> Self {
+        Self {
+            calls: vec![],
+        }
+    }
+}
+
+//standing victim can still turn to face the tackle
+pub fn is_from_behind(tackler_velocity: Vec2, victim_velocity: Vec2) -> bool {
+    if tackler_velocity.length_squared() == 0.0 || victim_velocity.length_squared() == 0.0 {
+        return false;
+    }
+    tackler_velocity.normalize().dot(victim_velocity.normalize()) >= FROM_BEHIND_COS
+}
+
+//runs before actors react to the hits, so actions are the same ones the collision saw
+pub fn call_fouls(
+    mut commands: Commands,
+    mut events: EventReader<actor::ActorEvents>,
+    mut referee: ResMut<Referee>,
+    query: Query<(&actor::Actor, &team::Team, &Transform, &actor::ActorStats)>,
+    ruleset: Res<rules::Ruleset>,
+) {
+    for event in events.iter() {
+        let (offender, victim, is_ball_tackle) = match *event {
+            actor::ActorEvents::ActorsCollided { actor_entity, actor_action, other_actor_entity, other_actor_action } => match (other_actor_action, actor_action) {
+                //tackle against tackle is nobody's fault
+                (actor::ActorAction::Tackling { x: _, y: _ }, actor::ActorAction::Tackling { x: _, y: _ }) => continue,
+                (actor::ActorAction::Tackling { x: _, y: _ }, _) => (other_actor_entity, actor_entity, false),
+                _ => continue
+            },
+            actor::ActorEvents::CleanSteal { tackler_entity, carrier_entity }
+            | actor::ActorEvents::Fumble { tackler_entity, carrier_entity, bounce: _ }
+            | actor::ActorEvents::BrokenTackle { tackler_entity, carrier_entity }
+            | actor::ActorEvents::BothDown { tackler_entity, carrier_entity } => (tackler_entity, carrier_entity, true),
+            _ => continue
+        };
+        let (offender_actor, offender_team, offender_transform, offender_stats) = match query.get(offender) {
+            Ok(result) => result,
+            Err(_) => continue
+        };
+        let (victim_actor, victim_team, victim_transform, victim_stats) = match query.get(victim) {
+            Ok(result) => result,
+            Err(_) => continue
+        };
+        if offender_team == victim_team {
+            continue;
+        }
+
+        let offender_velocity = actor::get_action_velocity(offender_actor.act_action, Vec2::from(offender_transform.translation), offender_stats);
+        let victim_velocity = actor::get_action_velocity(victim_actor.act_action, Vec2::from(victim_transform.translation), victim_stats);
+        //one hit is one foul, the most serious one the ruleset punishes
+        let mut kinds = vec![];
+        if let actor::ActorAction::Recovering(_) = victim_actor.act_action {
+            kinds.push(FoulKind::LateHit);
+        }
+        if is_from_behind(offender_velocity, victim_velocity) {
+            kinds.push(FoulKind::TackleFromBehind);
+        }
+        if !is_ball_tackle {
+            kinds.push(FoulKind::NoBallTackle);
+        }
+        let (kind, penalty) = match kinds.into_iter().find_map(|kind| ruleset.fouls.get(kind).map(|penalty| (kind, penalty))) {
+            Some(call) => call,
+            None => continue
+        };
+        if let rules::Penalty::SinBin(turns) = penalty {
+            commands.entity(offender).insert(SinBin(turns));
+        }
+        referee.calls.push(Foul { kind, penalty, offender, victim, team: *offender_team });
+    }
+}
+
+pub fn clear_calls(
+    mut referee: ResMut<Referee>,
+) {
+    referee.calls.clear();
+}
+
+//restart of the play cancels ball penalties, sent off actors still have to be announced
+pub fn cancel_ball_penalties(
+    mut referee: ResMut<Referee>,
+) {
+    referee.calls.retain(|foul| matches!(foul.penalty, rules::Penalty::SinBin(_)));
+}
+
+//injured actor can't take the ball, the play goes on
+fn give_ball(
+    commands: &mut Commands,
+    query: &mut Query<(&mut actor::Actor, &mut animation::Animation, &team::Team, &mut Transform, &RigidBodyHandleComponent, &injury::Health)>,
+    query_ball: &Query<Entity, With<ball::Ball>>,
+    ball_possession: &mut ball::BallPossession,
+    entity: Entity,
+) {
+    if query.get_component::<injury::Health>(entity).map_or(true, |health| health.is_injured()) {
+        return;
+    }
+    if let Some(carrier) = ball_possession.get() {
+        if let Ok((mut actor, mut animation, _team, _transform, _rigid_body_handle, _health)) = query.get_mut(carrier) {
+            actor::change_ball_possession(&mut actor, &mut animation, false);
+        }
+    }
+    for ball_entity in query_ball.iter() {
+        commands.entity(ball_entity).despawn_recursive();
+    }
+    let (mut actor, mut animation, _team, _transform, _rigid_body_handle, _health) = query.get_mut(entity).expect("Cannot get fouled actor!");
+    actor::change_ball_possession(&mut actor, &mut animation, true);
+    ball_possession.set(entity);
+}
+
+pub fn apply_penalties(
+    mut commands: Commands,
+    referee: Res<Referee>,
+    mut query: Query<(&mut actor::Actor, &mut animation::Animation, &team::Team, &mut Transform, &RigidBodyHandleComponent, &injury::Health)>,
+    query_ball: Query<Entity, With<ball::Ball>>,
+    mut rigid_body_set: ResMut<RigidBodySet>,
+    mut ball_possession: ResMut<ball::BallPossession>,
+    arena: Res<arena::Arena>,
+) {
+    for foul in referee.calls.iter() {
+        match foul.penalty {
+            rules::Penalty::Turnover => {
+                let has_ball = ball_possession.get()
+                    .and_then(|carrier| query.get_component::<team::Team>(carrier).ok())
+                    .map_or(false, |team| *team != foul.team);
+                if !has_ball {
+                    give_ball(&mut commands, &mut query, &query_ball, &mut ball_possession, foul.victim);
+                }
+            },
+            rules::Penalty::FreeThrow => {
+                give_ball(&mut commands, &mut query, &query_ball, &mut ball_possession, foul.victim);
+                let center = match query.get_component::<Transform>(foul.victim) {
+                    Ok(transform) => Vec2::from(transform.translation),
+                    Err(_) => continue
+                };
+                //offenders standing right on top of the victim step back towards their own side
+                let own_side = match foul.team {
+                    team::Team::Home => Vec2::new(-1.0, 0.0),
+                    team::Team::Away => Vec2::new(1.0, 0.0),
+                };
+                for (_actor, _animation, team, mut transform, rigid_body_handle, _health) in query.iter_mut() {
+                    let offset = Vec2::from(transform.translation) - center;
+                    if *team != foul.team || offset.length() >= FREE_THROW_DISTANCE {
+                        continue;
+                    }
+                    let direction = if offset.length_squared() > 0.0 { offset.normalize() } else { own_side };
+                    let position = center + direction * FREE_THROW_DISTANCE;
+                    let position = Vec2::new(
+                        position.x.max(arena.left + utils::TRUE_SPRITE_SIZE/2.0).min(arena.right - utils::TRUE_SPRITE_SIZE/2.0),
+                        position.y.max(arena.bottom + utils::TRUE_SPRITE_SIZE/2.0).min(arena.top - utils::TRUE_SPRITE_SIZE/2.0),
+                    );
+                    transform.translation.x = position.x;
+                    transform.translation.y = position.y;
+                    physics::set_rb_properties(rigid_body_handle, &mut rigid_body_set, Some(Vec2::ZERO), Some(position), None);
+                }
+            },
+            rules::Penalty::SinBin(_) => ()
+        }
+    }
+}
+
+//orders given to sent off actors are ignored, like the injured ones they lie down for the whole round
+pub fn keep_sin_binned_down(
+    mut query: Query<&mut actor::Actor, With<SinBin>>,
+) {
+    for mut actor in query.iter_mut() {
+        actor.set_action(actor::ActorAction::Recovering(round::ROUND_TIME));
+    }
+}
+
+pub fn count_sin_bin(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut SinBin, &mut actor::Actor, &injury::Health)>,
+) {
+    for (entity, mut sin_bin, mut actor, health) in query.iter_mut() {
+        if sin_bin.0 > 1 {
+            sin_bin.0 -= 1;
+        } else {
+            commands.entity(entity).remove::<SinBin>();
+            if !health.is_injured() {
+                actor.set_action(actor::ActorAction::Lookout);
+            }
+        }
+    }
+}
+
+impl Plugin for RefereePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .insert_resource(Referee::new())
+            .add_system_set(
+                SystemSet::on_update(states::AppState::Play)
+                    .with_system(call_fouls.system()
+                        .label(RefereeLabel::CallFouls)
+                        .after(collision::CollisionLabel::HandleCollisionEvents)
+                        .before(actor::ActorLabel::ActionStart)
+                    )
+            )
+            .add_system_set(
+                SystemSet::on_enter(states::AppState::Play)
+                    .with_system(clear_calls.system())
+                    .with_system(keep_sin_binned_down.system())
+            )
+            .add_system_set(
+                SystemSet::on_exit(states::AppState::Play)
+                    .with_system(count_sin_bin.system()
+                        .after(injury::InjuryLabel::HealInjuries)
+                        .before(actor::ActorLabel::AfterRoundReset)
+                    )
+            )
+            .add_system_set(
+                SystemSet::on_enter(states::AppState::MovingToStartPosition)
+                    .with_system(cancel_ball_penalties.system())
+            )
+            .add_system_set(
+                SystemSet::on_enter(states::AppState::Plan)
+                    .with_system(apply_penalties.system()
+                        .label(RefereeLabel::ApplyPenalties)
+                        .before(actor::ActorLabel::StartPlanning)
+                        .before(ai::AiLabel::Process)
+                    )
+            );
+    }
+}
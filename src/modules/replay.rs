@@ -0,0 +1,212 @@
+use std::fs;
+use bevy::prelude::*;
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+use super::{actor, ai, arena, matchup, rng, rules, states, team};
+
+//bump whenever Replay layout or anything influencing simulation changes, old replays would not play back the same
+pub const REPLAY_VERSION: u32 = 13;
+
+pub struct ReplayRecordPlugin;
+pub struct ReplayPlaybackPlugin;
+
+//actors are referenced by their slot in Matchup, entities differ between runs
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Replay {
+    pub version: u32,
+    pub seed: u64,
+    pub ruleset: rules::Ruleset,
+    pub arena: arena::ArenaLayout,
+    pub actors: Vec<(Vec2, team::Team, actor::ActorStats)>,
+    pub bench_home: Vec<actor::ActorStats>,
+    pub bench_away: Vec<actor::ActorStats>,
+    pub turns: Vec<ReplayTurn>,
+}
+
+//AI draws from GameRng while planning, but it doesn't run in playback, so every turn starts from the recorded state of randomness
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReplayTurn {
+    pub rng_word_pos: u64,
+    pub orders: Vec<(usize, actor::ActorOrder)>,
+}
+
+impl Replay {
+    pub fn new(seed: u64, arena: arena::ArenaLayout) -> Self {
+        Self {
+            version: REPLAY_VERSION,
+            seed,
+            ruleset: rules::Ruleset::default(),
+            arena,
+            actors: vec![],
+            bench_home: vec![],
+            bench_away: vec![],
+            turns: vec![],
+        }
+    }
+
+    pub fn load(path: &str) -> Self {
+        let data = fs::read_to_string(path).expect(format!("Cannot read replay file '{}'", path).as_str());
+        let replay: Replay = ron::from_str(&data).expect(format!("Cannot parse replay file '{}'", path).as_str());
+        if replay.version != REPLAY_VERSION {
+            panic!("Replay '{}' has version {}, but only version {} is supported", path, replay.version, REPLAY_VERSION);
+        }
+        replay
+    }
+
+    pub fn save(&self, path: &str) {
+        let data = ron::ser::to_string_pretty(self, PrettyConfig::default()).expect("Cannot serialize replay");
+        fs::write(path, data).expect(format!("Cannot write replay file '{}'", path).as_str());
+    }
+}
+
+pub struct ReplayRecorder {
+    pub path: String,
+    replay: Replay,
+    current_turn: Vec<(usize, actor::ActorOrder)>,
+}
+
+impl ReplayRecorder {
+    pub fn new(path: String, arena: arena::ArenaLayout) -> Self {
+        Self {
+            path,
+            replay: Replay::new(0, arena),
+            current_turn: vec![],
+        }
+    }
+}
+
+pub struct ReplayPlayback {
+    pub replay: Replay,
+    pub turn: usize,
+    pub finished: bool,
+}
+
+impl ReplayPlayback {
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            replay,
+            turn: 0,
+            finished: false,
+        }
+    }
+}
+
+//nobody controls replayed actors, they only follow recorded orders
+pub fn spawn_replay_actors(
+    commands: &mut Commands,
+    actor_sprites: Option<&actor::ActorTextures>,
+    replay: &Replay,
+) -> Vec<(Entity, Vec2, team::Team)> {
+    let lineup = replay.actors.iter().map(|(position, team, stats)| matchup::LineupSlot {
+        spawn_position: *position,
+        start_position: *position,
+        team: *team,
+        offense_focus: ai::DEFAULT_OFFENSE_FOCUS,
+        defense_focus: ai::DEFAULT_DEFENSE_FOCUS,
+        stats: *stats,
+    }).collect();
+    let actors = matchup::spawn_actors(commands, actor_sprites, &lineup, &[]);
+    for (entity, _position, _team) in actors.iter() {
+        commands.entity(*entity).remove::<ai::AiControlled>();
+    }
+    actors
+}
+
+fn record_orders(
+    mut events: EventReader<actor::ActorOrderEvent>,
+    mut recorder: ResMut<ReplayRecorder>,
+    matchup: Res<matchup::Matchup>,
+) {
+    for event in events.iter() {
+        if let Some(slot) = matchup.get_actor_slot(event.entity) {
+            recorder.current_turn.push((slot, event.order));
+        }
+    }
+}
+
+//file is rewritten after every planned turn so the replay survives closing the game at any point
+fn commit_turn(
+    mut recorder: ResMut<ReplayRecorder>,
+    matchup: Res<matchup::Matchup>,
+    seed: Res<rng::GameSeed>,
+    game_rng: Res<rng::GameRng>,
+    ruleset: Res<rules::Ruleset>,
+    query_stats: Query<&actor::ActorStats>,
+) {
+    let orders = std::mem::take(&mut recorder.current_turn);
+    recorder.replay.turns.push(ReplayTurn { rng_word_pos: game_rng.0.get_word_pos() as u64, orders });
+    recorder.replay.seed = seed.0;
+    recorder.replay.ruleset = ruleset.clone();
+    //lineup is recorded as it was at kickoff, substitutions are replayed from the bench
+    if recorder.replay.actors.is_empty() {
+        recorder.replay.actors = matchup.get_actors().iter().map(|(entity, position, team)| (*position, *team, query_stats.get(*entity).map_or(actor::ActorStats::default(), |stats| *stats))).collect();
+        recorder.replay.bench_home = matchup.bench_home.clone();
+        recorder.replay.bench_away = matchup.bench_away.clone();
+    }
+    recorder.replay.save(&recorder.path);
+}
+
+fn play_turn(
+    mut playback: ResMut<ReplayPlayback>,
+    mut app_state: ResMut<State<states::AppState>>,
+    mut event_orders: EventWriter<actor::ActorOrderEvent>,
+    mut game_rng: ResMut<rng::GameRng>,
+    matchup: Res<matchup::Matchup>,
+) {
+    if playback.turn >= playback.replay.turns.len() {
+        if !playback.finished {
+            println!("Replay finished");
+            playback.finished = true;
+        }
+        return;
+    }
+
+    let turn = &playback.replay.turns[playback.turn];
+    for (slot, order) in turn.orders.iter() {
+        let entity = matchup.get_actor_entity(*slot).expect(format!("Replay references missing actor {}", slot).as_str());
+        event_orders.send(actor::ActorOrderEvent { entity, order: *order });
+    }
+    game_rng.0.set_word_pos(turn.rng_word_pos as u128);
+    playback.turn += 1;
+    app_state.set(states::AppState::Play).unwrap();
+}
+
+impl Plugin for ReplayRecordPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .add_system_set(
+                SystemSet::on_update(states::AppState::Plan)
+                    .with_system(record_orders.system()
+                        .after(actor::ActorLabel::ApplyOrders)
+                    )
+            )
+            .add_system_set(
+                SystemSet::on_exit(states::AppState::Plan)
+                    .with_system(commit_turn.system())
+            );
+    }
+}
+
+impl Plugin for ReplayPlaybackPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .add_system_set(
+                SystemSet::on_update(states::AppState::Introduction)
+                    .with_system(states::advance_to_start_position.system())
+            )
+            .add_system_set(
+                SystemSet::on_update(states::AppState::Scored)
+                    .with_system(states::advance_to_start_position.system())
+            )
+            .add_system_set(
+                SystemSet::on_update(states::AppState::HalfTime)
+                    .with_system(states::advance_to_start_position.system())
+            )
+            .add_system_set(
+                SystemSet::on_update(states::AppState::Plan)
+                    .with_system(play_turn.system()
+                        .before(actor::ActorLabel::ApplyOrders)
+                    )
+            );
+    }
+}
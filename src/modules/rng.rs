@@ -0,0 +1,31 @@
+use bevy::prelude::*;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+pub struct RngPlugin;
+
+//all game randomness has to go through GameRng, same seed + same orders = same match
+pub struct GameSeed(pub u64);
+impl Default for GameSeed {
+    fn default() -> Self {
+        Self(rand::random())
+    }
+}
+
+pub struct GameRng(pub ChaCha8Rng);
+
+fn seed_rng(
+    mut commands: Commands,
+    seed: Res<GameSeed>,
+) {
+    println!("Game seed: {}", seed.0);
+    commands.insert_resource(GameRng(ChaCha8Rng::seed_from_u64(seed.0)));
+}
+
+impl Plugin for RngPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .init_resource::<GameSeed>()
+            .add_startup_system_to_stage(StartupStage::PreStartup, seed_rng.system());
+    }
+}
@@ -0,0 +1,80 @@
+use std::fs;
+use serde::{Deserialize, Serialize};
+use super::{actor, matchup, team};
+
+pub const ROSTERS_PATH: &str = "assets/rosters";
+pub const DEFAULT_ROSTER: &str = "default";
+
+//players are assigned to formation slots in order, slots without a player get an average one
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Roster {
+    pub name: String,
+    pub players: Vec<actor::ActorStats>,
+}
+
+impl Roster {
+    pub fn load(name: &str) -> Self {
+        let path = format!("{}/{}.ron", ROSTERS_PATH, name);
+        let data = fs::read_to_string(&path).expect(format!("Cannot read roster '{}'", path).as_str());
+        let roster: Roster = ron::from_str(&data).expect(format!("Cannot parse roster '{}'", path).as_str());
+        let has_invalid_stats = roster.players.iter().any(|stats| {
+            [stats.speed, stats.strength, stats.agility, stats.throwing, stats.catching].iter().any(|stat| *stat <= 0.0)
+        });
+        if has_invalid_stats {
+            panic!("Roster '{}' has player with stat that is not positive", path);
+        }
+        roster
+    }
+
+    pub fn get_stats(&self, slot: usize) -> actor::ActorStats {
+        self.players.get(slot).cloned().unwrap_or_default()
+    }
+}
+
+pub struct SelectedRosters {
+    pub home: Roster,
+    pub away: Roster,
+}
+impl SelectedRosters {
+    pub fn load(home: &str, away: &str) -> Self {
+        Self {
+            home: Roster::load(home),
+            away: Roster::load(away),
+        }
+    }
+
+    pub fn assign(&self, lineup: Vec<matchup::LineupSlot>) -> Vec<matchup::LineupSlot> {
+        assign(lineup, &self.home, &self.away)
+    }
+
+    pub fn get_bench(&self, lineup: &[matchup::LineupSlot]) -> (Vec<actor::ActorStats>, Vec<actor::ActorStats>) {
+        get_bench(lineup, &self.home, &self.away)
+    }
+}
+
+pub fn assign(lineup: Vec<matchup::LineupSlot>, home: &Roster, away: &Roster) -> Vec<matchup::LineupSlot> {
+    let (mut home_slot, mut away_slot) = (0, 0);
+    lineup.into_iter().map(|mut slot| {
+        slot.stats = match slot.team {
+            team::Team::Home => {
+                home_slot += 1;
+                home.get_stats(home_slot - 1)
+            },
+            team::Team::Away => {
+                away_slot += 1;
+                away.get_stats(away_slot - 1)
+            },
+        };
+        slot
+    }).collect()
+}
+
+//players left after filling the formation slots sit on the bench
+pub fn get_bench(lineup: &[matchup::LineupSlot], home: &Roster, away: &Roster) -> (Vec<actor::ActorStats>, Vec<actor::ActorStats>) {
+    let home_slots = lineup.iter().filter(|slot| slot.team == team::Team::Home).count();
+    let away_slots = lineup.iter().filter(|slot| slot.team == team::Team::Away).count();
+    (
+        home.players.iter().skip(home_slots).cloned().collect(),
+        away.players.iter().skip(away_slots).cloned().collect(),
+    )
+}
@@ -1,13 +1,34 @@
 use bevy::prelude::*;
 use super::{
+    matchup,
+    physics,
+    rules,
     states,
 };
 
 
-pub struct RoundTimer {}
+pub struct RoundPlugin;
+
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RoundLabel {
+    UpdateTimer,
+}
+//counted in physics steps instead of Timer, so every round spans exactly the same number of steps
+pub struct RoundTimer {
+    steps_left: u32,
+}
+impl RoundTimer {
+    pub fn elapsed_secs(&self) -> f32 {
+        (get_round_steps() - self.steps_left) as f32 * physics::PHYSICS_STEP
+    }
+}
 
 pub const ROUND_TIME: f32 = 1.0;
 
+pub fn get_round_steps() -> u32 {
+    (ROUND_TIME / physics::PHYSICS_STEP).round() as u32
+}
+
 pub fn start_timer(mut commands: Commands, query: Query<Entity, With<RoundTimer>>) {
     for timer in query.iter() {
         commands.entity(timer).despawn();
@@ -15,24 +36,49 @@ pub fn start_timer(mut commands: Commands, query: Query<Entity, With<RoundTimer>
 
     commands
         .spawn()
-        .insert(Timer::from_seconds(ROUND_TIME, false))
-        .insert(RoundTimer {});
+        .insert(RoundTimer { steps_left: get_round_steps() });
 }
 
 pub fn update_timer(
     mut commands: Commands,
-    time: Res<Time>,
-    mut query: Query<(Entity, &mut Timer), With<RoundTimer>>,
-    mut app_state: ResMut<State<states::AppState>>
+    mut query: Query<(Entity, &mut RoundTimer)>,
+    mut events: EventReader<matchup::MatchupEvents>,
+    mut app_state: ResMut<State<states::AppState>>,
+    mut progress: ResMut<rules::MatchProgress>,
+    ruleset: Res<rules::Ruleset>,
+    mut matchup: ResMut<matchup::Matchup>,
 ) {
+    //score has already ended the play and moved the match on, the round must not do it again
+    let has_scored = events.iter().count() > 0;
     let timer_result = query.single_mut();
     if timer_result.is_err() {
         return;
     }
     let (timer_entity, mut timer) = timer_result.unwrap();
-    timer.tick(time.delta());
-    if timer.finished() {
+    if has_scored {
         commands.entity(timer_entity).despawn();
-        app_state.set(states::AppState::Plan).unwrap();
+        return;
+    }
+    timer.steps_left = timer.steps_left.saturating_sub(1);
+    if timer.steps_left == 0 {
+        commands.entity(timer_entity).despawn();
+        app_state.set(rules::get_state_after_round(&mut progress, &ruleset, &mut matchup)).unwrap();
+    }
+}
+
+impl Plugin for RoundPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .add_system_set(
+                SystemSet::on_enter(states::AppState::Play)
+                    .with_system(start_timer.system())
+            )
+            .add_system_set(
+                SystemSet::on_update(states::AppState::Play)
+                    .with_system(update_timer.system()
+                        .label(RoundLabel::UpdateTimer)
+                        .after(matchup::MatchupLabel::HandleEvents)
+                    )
+            );
     }
 }
@@ -0,0 +1,302 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use super::{matchup, referee, states, team};
+
+pub const DEFAULT_RULESET: &str = "standard";
+pub const RULESET_PRESETS: [&str; 5] = ["standard", "quick", "friendly", "touchdown", "strict"];
+
+pub struct RulesPlugin;
+
+//points for each way of scoring, zero disables it
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct ScoringPoints {
+    pub goal: u8,
+    pub carried_goal: u8,
+    pub touchdown: u8,
+}
+impl ScoringPoints {
+    pub fn get(&self, method: matchup::ScoringMethod) -> u8 {
+        match method {
+            matchup::ScoringMethod::Goal => self.goal,
+            matchup::ScoringMethod::CarriedGoal => self.carried_goal,
+            matchup::ScoringMethod::Touchdown => self.touchdown,
+        }
+    }
+    pub fn has_touchdowns(&self) -> bool {
+        self.touchdown > 0
+    }
+}
+
+//given by the referee, ball penalties are carried out at the start of the next Plan phase
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Penalty {
+    //fouled actor gets the ball, unless his team already has it
+    Turnover,
+    //offender sits out this many turns, the one of the foul counts too
+    SinBin(u32),
+    //fouled actor gets the ball and opponents have to step back from him
+    FreeThrow,
+}
+
+//penalty for each kind of foul, None lets the play go on
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct FoulRules {
+    pub no_ball_tackle: Option<Penalty>,
+    pub late_hit: Option<Penalty>,
+    pub tackle_from_behind: Option<Penalty>,
+}
+impl FoulRules {
+    pub fn get(&self, kind: referee::FoulKind) -> Option<Penalty> {
+        match kind {
+            referee::FoulKind::NoBallTackle => self.no_ball_tackle,
+            referee::FoulKind::LateHit => self.late_hit,
+            referee::FoulKind::TackleFromBehind => self.tackle_from_behind,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Ruleset {
+    pub name: String,
+    pub halves: u32,
+    pub turns_per_half: u32,
+    //match ends right away when one team gets this many points
+    pub score_to_win: Option<u8>,
+    //sudden death after a tied match, otherwise it ends as a draw
+    pub overtime: bool,
+    pub points: ScoringPoints,
+    //hard tackles can injure actors, injured ones are substituted from the bench
+    #[serde(default)]
+    pub injuries: bool,
+    #[serde(default)]
+    pub fouls: FoulRules,
+}
+
+impl Ruleset {
+    pub fn preset(name: &str) -> Self {
+        let simple_points = ScoringPoints { goal: 1, carried_goal: 1, touchdown: 0 };
+        let no_fouls = FoulRules::default();
+        let (halves, turns_per_half, score_to_win, overtime, points, fouls) = match name {
+            "standard" => (2, 20, None, true, simple_points, no_fouls),
+            "quick" => (2, 8, Some(3), true, simple_points, no_fouls),
+            "friendly" => (2, 20, None, false, simple_points, no_fouls),
+            //running the ball in is worth more than throwing it
+            "touchdown" => (2, 20, None, true, ScoringPoints { goal: 1, carried_goal: 2, touchdown: 3 }, no_fouls),
+            "strict" => (2, 20, None, true, simple_points, FoulRules {
+                no_ball_tackle: Some(Penalty::FreeThrow),
+                late_hit: Some(Penalty::SinBin(2)),
+                tackle_from_behind: Some(Penalty::Turnover),
+            }),
+            _ => panic!("Unknown ruleset '{}', available rulesets are {:?}", name, RULESET_PRESETS),
+        };
+        Self {
+            name: name.to_string(),
+            halves,
+            turns_per_half,
+            score_to_win,
+            overtime,
+            points,
+            injuries: false,
+            fouls,
+        }
+    }
+}
+impl Default for Ruleset {
+    fn default() -> Self {
+        Self::preset(DEFAULT_RULESET)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum MatchResult {
+    Winner(team::Team),
+    Draw,
+}
+
+//every Play phase is one turn, whether it ends with the round timer or with a score
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct MatchProgress {
+    pub half: u32,
+    pub turn: u32,
+    pub is_overtime: bool,
+    pub result: Option<MatchResult>,
+}
+impl MatchProgress {
+    pub fn new() -> Self {
+        Self {
+            half: 1,
+            turn: 0,
+            is_overtime: false,
+            result: None,
+        }
+    }
+}
+
+fn get_leader(matchup: &matchup::Matchup) -> Option<team::Team> {
+    if matchup.score_home > matchup.score_away {
+        Some(team::Team::Home)
+    } else if matchup.score_away > matchup.score_home {
+        Some(team::Team::Away)
+    } else {
+        None
+    }
+}
+
+fn finish_match(progress: &mut MatchProgress, result: MatchResult) -> states::AppState {
+    progress.result = Some(result);
+    states::AppState::MatchOver
+}
+
+//None when the half goes on
+fn end_half(progress: &mut MatchProgress, ruleset: &Ruleset, matchup: &mut matchup::Matchup) -> Option<states::AppState> {
+    if progress.is_overtime || progress.turn < ruleset.turns_per_half {
+        return None;
+    }
+    if progress.half < ruleset.halves {
+        progress.half += 1;
+        progress.turn = 0;
+        //teams take turns in kicking off the halves
+        matchup.serving_side = if progress.half % 2 == 1 { team::Team::Home } else { team::Team::Away };
+        return Some(states::AppState::HalfTime);
+    }
+    match (get_leader(matchup), ruleset.overtime) {
+        (Some(winner), _) => Some(finish_match(progress, MatchResult::Winner(winner))),
+        (None, true) => {
+            progress.is_overtime = true;
+            progress.turn = 0;
+            Some(states::AppState::HalfTime)
+        },
+        (None, false) => Some(finish_match(progress, MatchResult::Draw)),
+    }
+}
+
+pub fn get_state_after_round(progress: &mut MatchProgress, ruleset: &Ruleset, matchup: &mut matchup::Matchup) -> states::AppState {
+    progress.turn += 1;
+    end_half(progress, ruleset, matchup).unwrap_or(states::AppState::Plan)
+}
+
+//score is already added to matchup
+pub fn get_state_after_score(progress: &mut MatchProgress, ruleset: &Ruleset, matchup: &mut matchup::Matchup) -> states::AppState {
+    progress.turn += 1;
+    let leader = get_leader(matchup);
+    let has_reached_score = ruleset.score_to_win.map_or(false, |score| matchup.score_home >= score || matchup.score_away >= score);
+    match leader {
+        Some(winner) if has_reached_score || progress.is_overtime => finish_match(progress, MatchResult::Winner(winner)),
+        _ => end_half(progress, ruleset, matchup).unwrap_or(states::AppState::Scored),
+    }
+}
+
+//only the first score of a frame counts, the ball can reach more goals at once
+pub fn get_state_after_scores<'a>(
+    progress: &mut MatchProgress,
+    ruleset: &Ruleset,
+    matchup: &mut matchup::Matchup,
+    mut events: impl Iterator<Item = &'a matchup::MatchupEvents>,
+) -> Option<states::AppState> {
+    events.next().map(|event| match *event {
+        matchup::MatchupEvents::Scored { team_scored_against, points, method, scorer } => {
+            matchup.add_score(team::get_oposing_team(team_scored_against), points);
+            matchup.last_score = Some((method, scorer));
+            matchup.serving_side = team_scored_against;
+            get_state_after_score(progress, ruleset, matchup)
+        }
+    })
+}
+
+impl Plugin for RulesPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .init_resource::<Ruleset>()
+            .insert_resource(MatchProgress::new());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_matchup(score_home: u8, score_away: u8) -> matchup::Matchup {
+        let mut matchup = matchup::Matchup::new();
+        matchup.score_home = score_home;
+        matchup.score_away = score_away;
+        matchup
+    }
+
+    #[test]
+    fn score_during_half_goes_on() {
+        let ruleset = Ruleset::preset("standard");
+        let mut progress = MatchProgress::new();
+        let mut matchup = get_matchup(1, 0);
+        assert_eq!(get_state_after_score(&mut progress, &ruleset, &mut matchup), states::AppState::Scored);
+        assert_eq!(progress.turn, 1);
+        assert_eq!(progress.result, None);
+    }
+
+    #[test]
+    fn score_to_win_ends_match() {
+        let ruleset = Ruleset::preset("quick");
+        let mut progress = MatchProgress::new();
+        let mut matchup = get_matchup(1, 3);
+        assert_eq!(get_state_after_score(&mut progress, &ruleset, &mut matchup), states::AppState::MatchOver);
+        assert_eq!(progress.result, Some(MatchResult::Winner(team::Team::Away)));
+    }
+
+    #[test]
+    fn score_on_last_turn_ends_half() {
+        let ruleset = Ruleset::preset("standard");
+        let mut progress = MatchProgress::new();
+        progress.turn = ruleset.turns_per_half - 1;
+        let mut matchup = get_matchup(1, 0);
+        assert_eq!(get_state_after_score(&mut progress, &ruleset, &mut matchup), states::AppState::HalfTime);
+        assert_eq!(progress.half, 2);
+        assert_eq!(progress.turn, 0);
+        assert_eq!(matchup.serving_side, team::Team::Away);
+    }
+
+    #[test]
+    fn score_on_last_turn_of_match() {
+        let ruleset = Ruleset::preset("standard");
+        let mut progress = MatchProgress::new();
+        progress.half = ruleset.halves;
+        progress.turn = ruleset.turns_per_half - 1;
+        let mut matchup = get_matchup(2, 1);
+        assert_eq!(get_state_after_score(&mut progress, &ruleset, &mut matchup), states::AppState::MatchOver);
+        assert_eq!(progress.result, Some(MatchResult::Winner(team::Team::Home)));
+    }
+
+    #[test]
+    fn equalizer_on_last_turn_goes_to_overtime_or_draw() {
+        let mut progress = MatchProgress::new();
+        let ruleset = Ruleset::preset("standard");
+        progress.half = ruleset.halves;
+        progress.turn = ruleset.turns_per_half - 1;
+        assert_eq!(get_state_after_score(&mut progress, &ruleset, &mut get_matchup(1, 1)), states::AppState::HalfTime);
+        assert!(progress.is_overtime);
+        //first score in overtime wins
+        assert_eq!(get_state_after_score(&mut progress, &ruleset, &mut get_matchup(1, 2)), states::AppState::MatchOver);
+        assert_eq!(progress.result, Some(MatchResult::Winner(team::Team::Away)));
+
+        let mut progress = MatchProgress::new();
+        let ruleset = Ruleset::preset("friendly");
+        progress.half = ruleset.halves;
+        progress.turn = ruleset.turns_per_half - 1;
+        assert_eq!(get_state_after_score(&mut progress, &ruleset, &mut get_matchup(1, 1)), states::AppState::MatchOver);
+        assert_eq!(progress.result, Some(MatchResult::Draw));
+    }
+
+    #[test]
+    fn two_scores_in_one_frame_count_once() {
+        let ruleset = Ruleset::preset("standard");
+        let mut progress = MatchProgress::new();
+        let mut matchup = get_matchup(0, 0);
+        let events = vec![
+            matchup::MatchupEvents::Scored { team_scored_against: team::Team::Away, points: 1, method: matchup::ScoringMethod::CarriedGoal, scorer: None },
+            matchup::MatchupEvents::Scored { team_scored_against: team::Team::Away, points: 1, method: matchup::ScoringMethod::Goal, scorer: None },
+        ];
+        assert_eq!(get_state_after_scores(&mut progress, &ruleset, &mut matchup, events.iter()), Some(states::AppState::Scored));
+        assert_eq!((matchup.score_home, matchup.score_away), (1, 0));
+        assert_eq!(matchup.last_score, Some((matchup::ScoringMethod::CarriedGoal, None)));
+        assert_eq!(progress.turn, 1);
+        assert_eq!(get_state_after_scores(&mut progress, &ruleset, &mut matchup, std::iter::empty()), None);
+    }
+}
@@ -0,0 +1,334 @@
+use std::fs;
+use bevy::prelude::*;
+use bevy_rapier2d::{
+    physics::RigidBodyHandleComponent,
+    rapier::{
+        dynamics::{RigidBodySet},
+    }
+};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+use super::{actor, ai, animation, arena, ball, injury, matchup, passing, physics, referee, rng, rules, stamina, states, team, zone};
+
+//bump whenever SaveGame layout changes
+pub const SAVEGAME_VERSION: u32 = 12;
+pub const DEFAULT_SAVEGAME_PATH: &str = "savegame.ron";
+
+pub struct SaveGamePlugin;
+
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SaveGameLabel {
+    TakeSnapshot,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ActorSave {
+    pub start_position: Vec2,
+    pub team: team::Team,
+    pub offense_focus: ai::AiFocus,
+    pub defense_focus: ai::AiFocus,
+    pub stats: actor::ActorStats,
+    pub stamina: f32,
+    pub hard_hits: u32,
+    pub injury: Option<injury::Injury>,
+    pub sin_bin: Option<u32>,
+    //indexes of arena zones the actor stands in
+    pub zones: Vec<usize>,
+    pub position: Vec2,
+    pub velocity: Vec2,
+    pub act_action: actor::ActorAction,
+    pub queued_actions: Vec<actor::ActorAction>,
+    pub has_tackled: bool,
+    pub is_tackle_target: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BallSave {
+    pub position: Vec2,
+    pub velocity: Vec2,
+    pub linear_damping: f32,
+    pub zones: Vec<usize>,
+    //duration, elapsed, pass type and team of the thrower
+    pub air_time: Option<(f32, f32, passing::PassType, team::Team)>,
+}
+
+//whole game state at the start of Plan phase, actors are stored in Matchup slot order
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SaveGame {
+    pub version: u32,
+    pub seed: u64,
+    pub rng_word_pos: u64,
+    pub ruleset: rules::Ruleset,
+    pub progress: rules::MatchProgress,
+    pub score_home: u8,
+    pub score_away: u8,
+    pub serving_side: team::Team,
+    pub arena: arena::ArenaLayout,
+    pub actors: Vec<ActorSave>,
+    pub bench_home: Vec<actor::ActorStats>,
+    pub bench_away: Vec<actor::ActorStats>,
+    pub ball: Option<BallSave>,
+    pub ball_possession: Option<usize>,
+}
+
+impl SaveGame {
+    pub fn load(path: &str) -> Self {
+        let data = fs::read_to_string(path).expect(format!("Cannot read savegame '{}'", path).as_str());
+        let save_game: SaveGame = ron::from_str(&data).expect(format!("Cannot parse savegame '{}'", path).as_str());
+        if save_game.version != SAVEGAME_VERSION {
+            panic!("Savegame '{}' has version {}, but only version {} is supported", path, save_game.version, SAVEGAME_VERSION);
+        }
+        save_game
+    }
+
+    pub fn save(&self, path: &str) {
+        let data = ron::ser::to_string_pretty(self, PrettyConfig::default()).expect("Cannot serialize savegame");
+        fs::write(path, data).expect(format!("Cannot write savegame '{}'", path).as_str());
+    }
+
+    //physics reports the zones again after the load, but the saved velocities already count with them
+    pub fn get_surface(&self, zones: &[usize]) -> zone::Surface {
+        let mut surface = zone::Surface::new();
+        for index in zones.iter() {
+            let zone = self.arena.zones.get(*index).expect("Savegame puts actor or ball into missing zone!");
+            surface.enter(*index, zone.kind);
+        }
+        surface
+    }
+
+    pub fn get_lineup(&self) -> Vec<matchup::LineupSlot> {
+        self.actors.iter().map(|actor| matchup::LineupSlot {
+            spawn_position: actor.position,
+            start_position: actor.start_position,
+            team: actor.team,
+            offense_focus: actor.offense_focus,
+            defense_focus: actor.defense_focus,
+            stats: actor.stats,
+        }).collect()
+    }
+}
+
+pub struct SaveGameSettings {
+    pub path: String,
+}
+impl Default for SaveGameSettings {
+    fn default() -> Self {
+        Self {
+            path: DEFAULT_SAVEGAME_PATH.to_string(),
+        }
+    }
+}
+
+pub struct SaveGameRequest;
+
+//game loaded on startup, it is restored when leaving Introduction straight into Plan
+pub struct PendingLoad(pub SaveGame);
+
+//snapshot is taken before AI or player plan anything so loaded game replays the same turn
+pub struct PlanSnapshot {
+    save_game: Option<SaveGame>,
+    //restored game is already the snapshot of this turn
+    is_restored: bool,
+}
+impl PlanSnapshot {
+    pub fn get(&self) -> Option<&SaveGame> {
+        self.save_game.as_ref()
+    }
+}
+
+pub fn take_snapshot(
+    mut snapshot: ResMut<PlanSnapshot>,
+    query_actors: Query<(&actor::Actor, &Transform, &actor::IsTackleTarget, &RigidBodyHandleComponent, Option<&ai::AiControlled>, &actor::ActorStats, &stamina::Stamina, &injury::Health, Option<&referee::SinBin>, &zone::Surface)>,
+    query_ball: Query<(&Transform, Option<&RigidBodyHandleComponent>, Option<&ball::AirTime>, &zone::Surface), With<ball::Ball>>,
+    rigid_body_set: ResMut<RigidBodySet>,
+    matchup: Res<matchup::Matchup>,
+    ball_possession: Res<ball::BallPossession>,
+    seed: Res<rng::GameSeed>,
+    game_rng: Res<rng::GameRng>,
+    ruleset: Res<rules::Ruleset>,
+    progress: Res<rules::MatchProgress>,
+    arena_layout: Res<arena::ArenaLayout>,
+) {
+    if snapshot.is_restored {
+        snapshot.is_restored = false;
+        return;
+    }
+
+    let actors = matchup.get_actors().iter().filter_map(|(entity, start_position, team)| {
+        query_actors.get(*entity).ok().map(|(actor, transform, is_tackle_target, rigid_body_handle, ai_controlled, stats, stamina, health, sin_bin, surface)| ActorSave {
+            start_position: *start_position,
+            team: *team,
+            offense_focus: ai_controlled.map_or(ai::DEFAULT_OFFENSE_FOCUS, |ai| ai.get_offense_focus()),
+            defense_focus: ai_controlled.map_or(ai::DEFAULT_DEFENSE_FOCUS, |ai| ai.get_defense_focus()),
+            stats: *stats,
+            stamina: stamina.0,
+            hard_hits: health.hard_hits,
+            injury: health.injury,
+            sin_bin: sin_bin.map(|sin_bin| sin_bin.0),
+            zones: surface.get_zones(),
+            position: Vec2::from(transform.translation),
+            velocity: physics::get_velocity(rigid_body_handle, &rigid_body_set).unwrap_or(Vec2::ZERO),
+            act_action: actor.act_action,
+            queued_actions: actor.get_queued_actions(),
+            has_tackled: actor.has_tackled(),
+            is_tackle_target: is_tackle_target.0,
+        })
+    }).collect();
+
+    //ball spawned for serve doesn't have rigid body yet, it is lying still
+    let ball = query_ball.single().ok().map(|(transform, rigid_body_handle, air_time, surface)| BallSave {
+        position: Vec2::from(transform.translation),
+        velocity: rigid_body_handle.and_then(|rbh| physics::get_velocity(rbh, &rigid_body_set)).unwrap_or(Vec2::ZERO),
+        linear_damping: rigid_body_handle.and_then(|rbh| physics::get_linear_damping(rbh, &rigid_body_set)).unwrap_or(ball::BALL_LINEAR_DAMPING_DROPPED),
+        zones: surface.get_zones(),
+        air_time: air_time.map(|at| (at.duration_secs(), at.elapsed_secs(), at.pass_type, at.team)),
+    });
+
+    snapshot.save_game = Some(SaveGame {
+        version: SAVEGAME_VERSION,
+        seed: seed.0,
+        //word position can't realistically get over u64 during one match
+        rng_word_pos: game_rng.0.get_word_pos() as u64,
+        ruleset: ruleset.clone(),
+        progress: *progress,
+        score_home: matchup.score_home,
+        score_away: matchup.score_away,
+        serving_side: matchup.serving_side,
+        arena: arena_layout.clone(),
+        actors,
+        bench_home: matchup.bench_home.clone(),
+        bench_away: matchup.bench_away.clone(),
+        ball,
+        ball_possession: ball_possession.get().and_then(|entity| matchup.get_actor_slot(entity)),
+    });
+}
+
+pub fn save_game(
+    mut events: EventReader<SaveGameRequest>,
+    snapshot: Res<PlanSnapshot>,
+    settings: Res<SaveGameSettings>,
+) {
+    for _event in events.iter() {
+        match snapshot.save_game.as_ref() {
+            Some(save_game) => {
+                save_game.save(&settings.path);
+                println!("Game saved to {}", settings.path);
+            },
+            None => println!("Nothing to save yet!")
+        };
+    }
+}
+
+pub fn enter_loaded_game(
+    pending_load: Option<Res<PendingLoad>>,
+    mut app_state: ResMut<State<states::AppState>>,
+) {
+    if pending_load.is_some() {
+        app_state.set(states::AppState::Plan).unwrap();
+    }
+}
+
+pub fn restore_game(
+    mut commands: Commands,
+    pending_load: Option<Res<PendingLoad>>,
+    mut query_actors: Query<(&mut actor::Actor, &mut Transform, &mut actor::IsTackleTarget, &mut animation::Animation, &RigidBodyHandleComponent, &mut stamina::Stamina, &mut injury::Health, &mut zone::Surface)>,
+    mut rigid_body_set: ResMut<RigidBodySet>,
+    mut matchup: ResMut<matchup::Matchup>,
+    mut ball_possession: ResMut<ball::BallPossession>,
+    mut game_rng: ResMut<rng::GameRng>,
+    mut snapshot: ResMut<PlanSnapshot>,
+    ball_sprite: Option<Res<ball::BallTexture>>,
+    mut ruleset: ResMut<rules::Ruleset>,
+    mut progress: ResMut<rules::MatchProgress>,
+) {
+    let pending_load = match pending_load {
+        Some(pending_load) => pending_load,
+        None => return
+    };
+    let save_game = &pending_load.0;
+
+    if matchup.get_actors().len() != save_game.actors.len() {
+        panic!("Savegame has {} actors, but {} were spawned", save_game.actors.len(), matchup.get_actors().len());
+    }
+
+    *ruleset = save_game.ruleset.clone();
+    *progress = save_game.progress;
+    matchup.score_home = save_game.score_home;
+    matchup.score_away = save_game.score_away;
+    matchup.serving_side = save_game.serving_side;
+
+    ball_possession.clear();
+    if let Some(slot) = save_game.ball_possession {
+        ball_possession.set(matchup.get_actor_entity(slot).expect("Savegame gives ball to missing actor!"));
+    }
+
+    for (slot, actor_save) in save_game.actors.iter().enumerate() {
+        let entity = matchup.get_actor_entity(slot).unwrap();
+        let (mut actor, mut transform, mut is_tackle_target, mut animation, rigid_body_handle, mut stamina, mut health, mut surface) = query_actors.get_mut(entity).expect("Cannot get actor from savegame!");
+        stamina.0 = actor_save.stamina;
+        health.hard_hits = actor_save.hard_hits;
+        health.injury = actor_save.injury;
+        *surface = save_game.get_surface(&actor_save.zones);
+        match actor_save.sin_bin {
+            Some(turns) => commands.entity(entity).insert(referee::SinBin(turns)),
+            None => commands.entity(entity).remove::<referee::SinBin>(),
+        };
+        actor.restore(actor_save.act_action, actor_save.queued_actions.clone(), actor_save.has_tackled);
+        actor::change_ball_possession(&mut actor, &mut animation, ball_possession.has_actor_ball(entity));
+        is_tackle_target.0 = actor_save.is_tackle_target;
+        transform.translation.x = actor_save.position.x;
+        transform.translation.y = actor_save.position.y;
+        physics::set_rb_properties(rigid_body_handle, &mut rigid_body_set, Some(actor_save.velocity), Some(actor_save.position), None);
+    }
+
+    if let Some(ball_save) = save_game.ball.as_ref() {
+        ball::spawn_ball_with_state(
+            &mut commands,
+            ball_sprite.as_deref(),
+            ball_save.position,
+            ball_save.velocity,
+            ball_save.linear_damping,
+            ball_save.air_time.map(|(duration, elapsed, pass_type, team)| ball::AirTime::new(duration, elapsed, pass_type, team)),
+            save_game.get_surface(&ball_save.zones),
+        );
+    }
+
+    game_rng.0 = ChaCha8Rng::seed_from_u64(save_game.seed);
+    game_rng.0.set_word_pos(save_game.rng_word_pos as u128);
+
+    snapshot.save_game = Some(save_game.clone());
+    snapshot.is_restored = true;
+
+    commands.remove_resource::<PendingLoad>();
+}
+
+impl Plugin for SaveGamePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .init_resource::<SaveGameSettings>()
+            .insert_resource(PlanSnapshot { save_game: None, is_restored: false })
+            .add_event::<SaveGameRequest>()
+            .add_system_set(
+                SystemSet::on_update(states::AppState::Introduction)
+                    .with_system(enter_loaded_game.system())
+            )
+            .add_system_set(
+                SystemSet::on_exit(states::AppState::Introduction)
+                    .with_system(restore_game.system())
+            )
+            .add_system_set(
+                SystemSet::on_enter(states::AppState::Plan)
+                    .with_system(take_snapshot.system()
+                        .label(SaveGameLabel::TakeSnapshot)
+                        .after(referee::RefereeLabel::ApplyPenalties)
+                        .before(ai::AiLabel::Process)
+                    )
+            )
+            .add_system_set(
+                SystemSet::on_update(states::AppState::Plan)
+                    .with_system(save_game.system())
+            );
+    }
+}
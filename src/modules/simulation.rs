@@ -0,0 +1,111 @@
+use bevy::prelude::*;
+use crate::RrGamePlugin;
+use super::{arena, formation, matchup, roster, rules, states};
+
+//runs AI vs AI matches without window, renderer or asset server - see src/bin/simulate.rs
+pub struct SimulationPlugin;
+
+//match length is given by rules::Ruleset resource
+pub struct SimulationSettings {
+    pub home_formation: String,
+    pub away_formation: String,
+    pub home_roster: String,
+    pub away_roster: String,
+    pub arena: String,
+}
+impl Default for SimulationSettings {
+    fn default() -> Self {
+        Self {
+            home_formation: formation::DEFAULT_FORMATION.to_string(),
+            away_formation: formation::DEFAULT_FORMATION.to_string(),
+            home_roster: roster::DEFAULT_ROSTER.to_string(),
+            away_roster: roster::DEFAULT_ROSTER.to_string(),
+            arena: arena::DEFAULT_ARENA.to_string(),
+        }
+    }
+}
+
+pub struct SimulationProgress {
+    pub turns_played: u32,
+    pub finished: bool,
+}
+impl SimulationProgress {
+    pub fn new() -> Self {
+        Self {
+            turns_played: 0,
+            finished: false,
+        }
+    }
+}
+
+fn initialize_game(
+    mut commands: Commands,
+    mut matchup_res: ResMut<matchup::Matchup>,
+    settings: Res<SimulationSettings>,
+    ruleset: Res<rules::Ruleset>,
+) {
+    let formations = formation::SelectedFormations::load(&settings.home_formation, &settings.away_formation);
+    let rosters = roster::SelectedRosters::load(&settings.home_roster, &settings.away_roster);
+    let lineup = formations.get_lineup();
+    let actors = matchup::spawn_actors(&mut commands, None, &rosters.assign(lineup.clone()), &[]);
+    matchup_res.add_actors(actors);
+    let (bench_home, bench_away) = rosters.get_bench(&lineup);
+    matchup_res.bench_home = bench_home;
+    matchup_res.bench_away = bench_away;
+    let arena_layout = arena::ArenaLayout::load(&settings.arena);
+    arena::create_from_layout(&mut commands, None, None, &arena_layout, None, ruleset.points.has_touchdowns());
+    commands.insert_resource(arena_layout);
+}
+
+//AI has already planned both teams on enter, so nothing is left to wait for
+fn advance_to_play(
+    mut app_state: ResMut<State<states::AppState>>,
+) {
+    app_state.set(states::AppState::Play).unwrap();
+}
+
+fn count_turn(
+    mut progress: ResMut<SimulationProgress>,
+) {
+    progress.turns_played += 1;
+}
+
+fn finish_match(
+    mut progress: ResMut<SimulationProgress>,
+) {
+    progress.finished = true;
+}
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .init_resource::<SimulationSettings>()
+            .insert_resource(SimulationProgress::new())
+            .add_plugin(RrGamePlugin)
+            .add_startup_stage("game_initialization", SystemStage::single(initialize_game.system()))
+            .add_system_set(
+                SystemSet::on_update(states::AppState::Introduction)
+                    .with_system(states::advance_to_start_position.system())
+            )
+            .add_system_set(
+                SystemSet::on_update(states::AppState::Scored)
+                    .with_system(states::advance_to_start_position.system())
+            )
+            .add_system_set(
+                SystemSet::on_update(states::AppState::HalfTime)
+                    .with_system(states::advance_to_start_position.system())
+            )
+            .add_system_set(
+                SystemSet::on_update(states::AppState::Plan)
+                    .with_system(advance_to_play.system())
+            )
+            .add_system_set(
+                SystemSet::on_exit(states::AppState::Play)
+                    .with_system(count_turn.system())
+            )
+            .add_system_set(
+                SystemSet::on_enter(states::AppState::MatchOver)
+                    .with_system(finish_match.system())
+            );
+    }
+}
@@ -0,0 +1,89 @@
+use bevy::prelude::*;
+use super::{actor, clock, states};
+
+pub const MAX_STAMINA: f32 = 1.0;
+//per second of Play phase, one round of running costs about one eighth of full stamina
+const RUNNING_DRAIN: f32 = 0.12;
+const TACKLING_DRAIN: f32 = 0.4;
+const RESTING_REGENERATION: f32 = 0.15;
+//tired actor gets slower, weaker and reaches less, down to this factor when completely exhausted
+const FATIGUE_THRESHOLD: f32 = 0.5;
+const MIN_FATIGUE_FACTOR: f32 = 0.6;
+
+pub struct StaminaPlugin;
+
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum StaminaLabel {
+    Update,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Stamina(pub f32);
+impl Stamina {
+    pub fn new() -> Self {
+        Self(MAX_STAMINA)
+    }
+    pub fn get_fatigue_factor(&self) -> f32 {
+        if self.0 >= FATIGUE_THRESHOLD {
+            1.0
+        } else {
+            MIN_FATIGUE_FACTOR + (1.0 - MIN_FATIGUE_FACTOR) * (self.0 / FATIGUE_THRESHOLD).max(0.0)
+        }
+    }
+    //stats the actor can actually use right now, throwing and catching don't get tired
+    pub fn apply(&self, stats: &actor::ActorStats) -> actor::ActorStats {
+        let factor = self.get_fatigue_factor();
+        actor::ActorStats {
+            speed: stats.speed * factor,
+            strength: stats.strength * factor,
+            agility: stats.agility * factor,
+            ..*stats
+        }
+    }
+}
+
+pub fn update_stamina(
+    clock: Res<clock::GameClock>,
+    mut query: Query<(&actor::Actor, &mut Stamina)>,
+) {
+    let delta = clock.delta_seconds();
+    for (actor, mut stamina) in query.iter_mut() {
+        let change = match actor.act_action {
+            actor::ActorAction::Running { x: _, y: _ } | actor::ActorAction::Dodge { x: _, y: _ } => -RUNNING_DRAIN,
+            actor::ActorAction::Tackling { x: _, y: _ } => -TACKLING_DRAIN,
+            actor::ActorAction::Idle | actor::ActorAction::Lookout | actor::ActorAction::HoldPosition { x: _, y: _ } => RESTING_REGENERATION,
+            _ => 0.0
+        };
+        let value = (stamina.0 + change * delta).max(0.0).min(MAX_STAMINA);
+        //untouched stamina isn't marked as changed, bars are redrawn only when needed
+        if value != stamina.0 {
+            stamina.0 = value;
+        }
+    }
+}
+
+//half time break is long enough to catch breath completely
+pub fn restore_stamina(
+    mut query: Query<&mut Stamina>,
+) {
+    for mut stamina in query.iter_mut() {
+        stamina.0 = MAX_STAMINA;
+    }
+}
+
+impl Plugin for StaminaPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .add_system_set(
+                SystemSet::on_update(states::AppState::Play)
+                    .with_system(update_stamina.system()
+                        .label(StaminaLabel::Update)
+                        .after(actor::ActorLabel::HandleEvents)
+                    )
+            )
+            .add_system_set(
+                SystemSet::on_enter(states::AppState::HalfTime)
+                    .with_system(restore_stamina.system())
+            );
+    }
+}
@@ -1,3 +1,5 @@
+use bevy::prelude::*;
+
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub enum AppState {
     Plan,
@@ -5,4 +7,30 @@ pub enum AppState {
     Introduction,
     Scored,
     MovingToStartPosition,
+    HandOver,
+    WaitingForOrders,
+    HalfTime,
+    MatchOver,
+}
+
+//state pushed on top of Plan when local planning is finished, e.g. for hotseat hand over, None goes straight to Play
+#[derive(Default)]
+pub struct PlanningHold(pub Option<AppState>);
+
+//Plan is paused (not exited) while held, so on_enter Plan systems like AI or helpers cleanup run only once per turn
+pub fn finish_planning(
+    app_state: &mut ResMut<State<AppState>>,
+    planning_hold: &PlanningHold,
+) {
+    match planning_hold.0.as_ref() {
+        Some(state) => app_state.push(state.clone()).unwrap(),
+        None => app_state.set(AppState::Play).unwrap(),
+    };
+}
+
+//stands in for the Enter key on intro and score screens when nobody is at the keyboard
+pub fn advance_to_start_position(
+    mut app_state: ResMut<State<AppState>>,
+) {
+    app_state.set(AppState::MovingToStartPosition).unwrap();
 }
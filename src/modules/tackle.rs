@@ -0,0 +1,145 @@
+use bevy::prelude::*;
+use super::actor;
+
+//tackle from the side or from behind catches the carrier off balance
+const SIDE_TACKLE_BONUS: f32 = 0.2;
+const REAR_TACKLE_BONUS: f32 = 0.4;
+//relative speed of head-on tackle between two average actors running at full speed
+const REFERENCE_IMPACT_SPEED: f32 = 325.0;
+//how much of the tackle power comes from the speed of the hit, the rest is technique
+const IMPACT_WEIGHT: f32 = 0.25;
+//chances for two average actors in head-on tackle
+const BASE_BREAK_CHANCE: f32 = 0.35;
+const BASE_BOTH_DOWN_CHANCE: f32 = 0.1;
+//part of the successful tackles where the ball gets loose instead of being stolen
+const BASE_FUMBLE_SHARE: f32 = 0.3;
+const MAX_FUMBLE_SHARE: f32 = 0.8;
+const FUMBLE_BOUNCE_SPEED: f32 = 80.0;
+const MIN_CHANCE: f32 = 0.05;
+const MAX_CHANCE: f32 = 0.9;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TackleOutcome {
+    CleanSteal,
+    Fumble,
+    BrokenTackle,
+    BothDown,
+}
+
+//1.0 for head-on tackle, grows towards the back of the carrier, standing carrier is ready for it
+pub fn get_angle_factor(tackler_velocity: Vec2, carrier_velocity: Vec2) -> f32 {
+    if tackler_velocity.length_squared() == 0.0 || carrier_velocity.length_squared() == 0.0 {
+        return 1.0;
+    }
+    let cos = tackler_velocity.normalize().dot(carrier_velocity.normalize());
+    if cos <= 0.0 {
+        1.0 + SIDE_TACKLE_BONUS * (1.0 + cos)
+    } else {
+        1.0 + SIDE_TACKLE_BONUS + (REAR_TACKLE_BONUS - SIDE_TACKLE_BONUS) * cos
+    }
+}
+
+pub fn get_impact(tackler_velocity: Vec2, carrier_velocity: Vec2) -> f32 {
+    (tackler_velocity - carrier_velocity).length() / REFERENCE_IMPACT_SPEED
+}
+
+//tackler's power against carrier's balance, 1.0 for head-on tackle between average actors
+pub fn get_tackle_ratio(
+    tackler_stats: &actor::ActorStats,
+    carrier_stats: &actor::ActorStats,
+    tackler_velocity: Vec2,
+    carrier_velocity: Vec2,
+) -> f32 {
+    let impact = get_impact(tackler_velocity, carrier_velocity);
+    let power = tackler_stats.strength * get_angle_factor(tackler_velocity, carrier_velocity) * (1.0 - IMPACT_WEIGHT + IMPACT_WEIGHT * impact);
+    let balance = carrier_stats.strength * 0.6 + carrier_stats.agility * 0.4;
+    power / balance
+}
+
+//roll is uniform between 0 and 1, so the same roll always gives the same outcome
+pub fn resolve_tackle(
+    tackler_stats: &actor::ActorStats,
+    carrier_stats: &actor::ActorStats,
+    tackler_velocity: Vec2,
+    carrier_velocity: Vec2,
+    roll: f32,
+) -> TackleOutcome {
+    let ratio = get_tackle_ratio(tackler_stats, carrier_stats, tackler_velocity, carrier_velocity);
+    let impact = get_impact(tackler_velocity, carrier_velocity);
+    let break_chance = (BASE_BREAK_CHANCE / ratio.powi(2)).max(MIN_CHANCE).min(MAX_CHANCE);
+    //hard hits take both actors down and shake the ball loose more often
+    let both_down_chance = (BASE_BOTH_DOWN_CHANCE * impact).min(1.0 - break_chance);
+    let fumble_share = (BASE_FUMBLE_SHARE * impact).min(MAX_FUMBLE_SHARE);
+
+    if roll < break_chance {
+        TackleOutcome::BrokenTackle
+    } else if roll < break_chance + both_down_chance {
+        TackleOutcome::BothDown
+    } else {
+        let tackled_part = (roll - break_chance - both_down_chance) / (1.0 - break_chance - both_down_chance);
+        if tackled_part < fumble_share { TackleOutcome::Fumble } else { TackleOutcome::CleanSteal }
+    }
+}
+
+//roll is uniform between 0 and 1 and picks the direction
+pub fn get_fumble_bounce(roll: f32) -> Vec2 {
+    let angle = roll * std::f32::consts::TAU;
+    Vec2::new(angle.cos(), angle.sin()) * FUMBLE_BOUNCE_SPEED
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //two average actors running into each other at full speed
+    fn get_head_on_velocities() -> (Vec2, Vec2) {
+        (Vec2::new(-REFERENCE_IMPACT_SPEED / 2.0, 0.0), Vec2::new(REFERENCE_IMPACT_SPEED / 2.0, 0.0))
+    }
+
+    #[test]
+    fn rear_tackle_beats_head_on_tackle() {
+        let stats = actor::ActorStats::new();
+        let carrier_velocity = Vec2::new(100.0, 0.0);
+        let head_on = get_tackle_ratio(&stats, &stats, Vec2::new(-100.0, 0.0), carrier_velocity);
+        let rear = get_tackle_ratio(&stats, &stats, Vec2::new(200.0, 0.0), carrier_velocity);
+        assert!(rear > head_on);
+        //the same roll breaks the head-on tackle but not the one from behind
+        assert_eq!(resolve_tackle(&stats, &stats, Vec2::new(-100.0, 0.0), carrier_velocity, 0.3), TackleOutcome::BrokenTackle);
+        assert_ne!(resolve_tackle(&stats, &stats, Vec2::new(200.0, 0.0), carrier_velocity, 0.3), TackleOutcome::BrokenTackle);
+    }
+
+    #[test]
+    fn zero_velocity_is_not_nan() {
+        let stats = actor::ActorStats::new();
+        assert_eq!(get_angle_factor(Vec2::ZERO, Vec2::ZERO), 1.0);
+        assert_eq!(get_angle_factor(Vec2::new(100.0, 0.0), Vec2::ZERO), 1.0);
+        assert!(get_tackle_ratio(&stats, &stats, Vec2::ZERO, Vec2::ZERO).is_finite());
+        assert_eq!(resolve_tackle(&stats, &stats, Vec2::ZERO, Vec2::ZERO, 0.0), TackleOutcome::BrokenTackle);
+        //without impact there is no fumble and nobody goes down
+        assert_eq!(resolve_tackle(&stats, &stats, Vec2::ZERO, Vec2::ZERO, 0.999), TackleOutcome::CleanSteal);
+    }
+
+    #[test]
+    fn roll_boundaries() {
+        let stats = actor::ActorStats::new();
+        let (tackler_velocity, carrier_velocity) = get_head_on_velocities();
+        let outcome = |roll: f32| resolve_tackle(&stats, &stats, tackler_velocity, carrier_velocity, roll);
+        //break 0.35, both down 0.1, fumble 0.3 of the remaining 0.55
+        assert_eq!(outcome(0.0), TackleOutcome::BrokenTackle);
+        assert_eq!(outcome(0.349), TackleOutcome::BrokenTackle);
+        assert_eq!(outcome(0.351), TackleOutcome::BothDown);
+        assert_eq!(outcome(0.449), TackleOutcome::BothDown);
+        assert_eq!(outcome(0.451), TackleOutcome::Fumble);
+        assert_eq!(outcome(0.61), TackleOutcome::Fumble);
+        assert_eq!(outcome(0.62), TackleOutcome::CleanSteal);
+        assert_eq!(outcome(0.999), TackleOutcome::CleanSteal);
+    }
+
+    #[test]
+    fn fumble_bounce_has_fixed_speed() {
+        for roll in [0.0, 0.25, 0.5, 0.999].iter() {
+            assert!((get_fumble_bounce(*roll).length() - FUMBLE_BOUNCE_SPEED).abs() < 0.01);
+        }
+        assert!(get_fumble_bounce(0.25).y > 0.0);
+    }
+}
@@ -3,6 +3,12 @@ use bevy::prelude::*;
 use super:: {
     actor,
     ball,
+    hotseat,
+    injury,
+    passing,
+    referee,
+    rules,
+    stamina,
     team,
     states,
     utils,
@@ -10,11 +16,20 @@ use super:: {
 };
 
 
+pub struct UiPlugin;
+
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum UiLabel {
+    SpawnButtons,
+}
+
 pub struct SelectedText;
 pub struct StateText;
 pub struct ControlModeText;
+pub struct TackleText;
 pub struct GameText;
 pub struct ScoreText;
+pub struct MatchProgressText;
 
 pub const UI_SIZE: f32 = 20.0;
 
@@ -60,6 +75,7 @@ const DEBUG_OFF_SET_X: f32 = 20.0;
 const DEBUG_OFF_SET_Y: f32 = 40.0;
 const GAME_INFO_TEXT_SIZE: f32 = 32.0;
 const CONTROL_BUTTON_GROUP: u32 = 1;
+const PLAY_BUTTON_WIDTH: f32 = 40.0;
 
 fn update_text(text: &mut Text, value: String) {
     text.sections[0].value = value;
@@ -85,8 +101,21 @@ fn create_debug_text_bundle(fonts: &Res<FontMaterials>, text: String, y: f32) ->
     create_text_bundle(fonts, text, DEBUG_OFF_SET_X + 5.0, DEBUG_OFF_SET_Y + y, DEBUG_TEXT_SIZE, AlignSelf::FlexStart)
 }
 
+//texts and buttons placed relative to the window width are moved by keep_ui_in_window when it is resized
+fn get_pre_game_text_x(window_width: f32, text: &str) -> f32 {
+    window_width/2.0 - (text.len() as f32)*15.0/2.0
+}
+
+fn get_score_text_x(window_width: f32, text: &str) -> f32 {
+    window_width - (text.len() as f32)*10.0
+}
+
+fn get_play_button_x(window_width: f32) -> f32 {
+    window_width/2.0 - PLAY_BUTTON_WIDTH/2.0
+}
+
 fn create_pre_game_text(fonts: &Res<FontMaterials>, text: String, y: f32) -> TextBundle {
-    create_text_bundle(fonts, text.clone(), utils::WIN_W/2.0 - (text.len() as f32)*15.0/2.0, y, GAME_INFO_TEXT_SIZE, AlignSelf::FlexStart)
+    create_text_bundle(fonts, text.clone(), get_pre_game_text_x(utils::WIN_W, &text), y, GAME_INFO_TEXT_SIZE, AlignSelf::FlexStart)
 }
 
 fn create_text_bundle(fonts: &Res<FontMaterials>, text: String, x: f32, y: f32, font_size: f32, alignment: AlignSelf) -> TextBundle {
@@ -128,6 +157,9 @@ pub fn spawn_debug_ui(mut commands: Commands, fonts: Res<FontMaterials>) {
     commands
         .spawn_bundle(create_debug_text_bundle(&fonts, "No control mode".to_string(), 29.0))
         .insert(ControlModeText);
+    commands
+        .spawn_bundle(create_debug_text_bundle(&fonts, "No tackle".to_string(), 41.0))
+        .insert(TackleText);
 }
 
 pub fn spawn_score_text(
@@ -136,12 +168,32 @@ pub fn spawn_score_text(
     matchup: Res<matchup::Matchup>,
 ) {
     let text = format!("{}-{}", matchup.score_home, matchup.score_away);
-    let bundle = create_text_bundle(&fonts, text.clone(), utils::WIN_W - (text.len() as f32)*10.0, 2.0, 16.0, AlignSelf::FlexStart);
+    let bundle = create_text_bundle(&fonts, text.clone(), get_score_text_x(utils::WIN_W, &text), 2.0, 16.0, AlignSelf::FlexStart);
     commands
         .spawn_bundle(bundle)
         .insert(ScoreText);
 }
 
+fn get_match_progress_text(progress: &rules::MatchProgress, ruleset: &rules::Ruleset) -> String {
+    if progress.is_overtime {
+        "Overtime".to_string()
+    } else {
+        format!("Half {}/{}, turn {}/{}", progress.half, ruleset.halves, progress.turn + 1, ruleset.turns_per_half)
+    }
+}
+
+pub fn spawn_match_progress_text(
+    mut commands: Commands,
+    fonts: Res<FontMaterials>,
+    progress: Res<rules::MatchProgress>,
+    ruleset: Res<rules::Ruleset>,
+) {
+    let bundle = create_text_bundle(&fonts, get_match_progress_text(&progress, &ruleset), 120.0, 2.0, 16.0, AlignSelf::FlexStart);
+    commands
+        .spawn_bundle(bundle)
+        .insert(MatchProgressText);
+}
+
 pub fn add_pre_game_text(
     mut commands: Commands,
     fonts: Res<FontMaterials>
@@ -157,7 +209,66 @@ pub fn add_score_text(
     fonts: Res<FontMaterials>,
     matchup: Res<matchup::Matchup>,
 ) {
-    let text_top = format!("{:?} team scores, score is now {} - {}", team::get_oposing_team(matchup.serving_side), matchup.score_home, matchup.score_away);
+    let method = match matchup.last_score {
+        Some((matchup::ScoringMethod::Touchdown, _scorer)) => "with a touchdown",
+        Some((matchup::ScoringMethod::CarriedGoal, _scorer)) => "by carrying the ball in",
+        _ => "a goal",
+    };
+    let text_top = format!("{:?} team scores {}, score is now {} - {}", team::get_oposing_team(matchup.serving_side), method, matchup.score_home, matchup.score_away);
+    let text_bottom = "Press Enter to continue".to_owned();
+    commands
+        .spawn_bundle(create_pre_game_text(&fonts, text_top, 300.0))
+        .insert(GameText);
+    commands
+        .spawn_bundle(create_pre_game_text(&fonts, text_bottom, 350.0))
+        .insert(GameText);
+}
+
+pub fn add_half_time_text(
+    mut commands: Commands,
+    fonts: Res<FontMaterials>,
+    matchup: Res<matchup::Matchup>,
+    progress: Res<rules::MatchProgress>,
+) {
+    let text_top = if progress.is_overtime {
+        format!("Tied at {} - {}, next score wins", matchup.score_home, matchup.score_away)
+    } else {
+        format!("Half time, score is {} - {}", matchup.score_home, matchup.score_away)
+    };
+    let text_bottom = "Press Enter to continue".to_owned();
+    commands
+        .spawn_bundle(create_pre_game_text(&fonts, text_top, 300.0))
+        .insert(GameText);
+    commands
+        .spawn_bundle(create_pre_game_text(&fonts, text_bottom, 350.0))
+        .insert(GameText);
+}
+
+pub fn add_match_over_text(
+    mut commands: Commands,
+    fonts: Res<FontMaterials>,
+    matchup: Res<matchup::Matchup>,
+    progress: Res<rules::MatchProgress>,
+) {
+    let text_top = match progress.result {
+        Some(rules::MatchResult::Winner(team)) => format!("{:?} team wins {} - {}", team, matchup.score_home, matchup.score_away),
+        _ => format!("Match ends in a draw {} - {}", matchup.score_home, matchup.score_away),
+    };
+    let text_bottom = "Press Esc to quit".to_owned();
+    commands
+        .spawn_bundle(create_pre_game_text(&fonts, text_top, 300.0))
+        .insert(GameText);
+    commands
+        .spawn_bundle(create_pre_game_text(&fonts, text_bottom, 350.0))
+        .insert(GameText);
+}
+
+pub fn add_hand_over_text(
+    mut commands: Commands,
+    fonts: Res<FontMaterials>,
+    hotseat: Res<hotseat::Hotseat>,
+) {
+    let text_top = format!("{:?} team planned, hand over to {:?} player", team::get_oposing_team(hotseat.planning_team), hotseat.planning_team);
     let text_bottom = "Press Enter to continue".to_owned();
     commands
         .spawn_bundle(create_pre_game_text(&fonts, text_top, 300.0))
@@ -167,6 +278,54 @@ pub fn add_score_text(
         .insert(GameText);
 }
 
+pub fn add_waiting_for_orders_text(
+    mut commands: Commands,
+    fonts: Res<FontMaterials>,
+) {
+    let text = "Waiting for the other player".to_owned();
+    commands
+        .spawn_bundle(create_pre_game_text(&fonts, text, 300.0))
+        .insert(GameText);
+}
+
+//fouls, injuries and substitutions of the last round are announced while the next one is planned
+pub fn add_round_report_text(
+    mut commands: Commands,
+    fonts: Res<FontMaterials>,
+    referee: Res<referee::Referee>,
+    injury_report: Res<injury::InjuryReport>,
+) {
+    let mut lines = vec![];
+    for foul in referee.calls.iter() {
+        let kind = match foul.kind {
+            referee::FoulKind::NoBallTackle => "tackle without ball",
+            referee::FoulKind::LateHit => "late hit",
+            referee::FoulKind::TackleFromBehind => "tackle from behind",
+        };
+        let penalty = match foul.penalty {
+            rules::Penalty::Turnover => "turnover".to_string(),
+            rules::Penalty::SinBin(turns) => format!("sin bin for {} turns", turns),
+            rules::Penalty::FreeThrow => "free throw".to_string(),
+        };
+        lines.push(format!("Foul by {:?} team, {}: {}", foul.team, kind, penalty));
+    }
+    for (team, injury) in injury_report.injuries.iter() {
+        let length = match injury {
+            injury::Injury::Turns(turns) => format!("for {} turns", turns),
+            injury::Injury::Match => "for the rest of the match".to_string(),
+        };
+        lines.push(format!("{:?} team player injured {}", team, length));
+    }
+    for team in injury_report.substitutions.iter() {
+        lines.push(format!("Injured {:?} team player substituted", team));
+    }
+    for (i, text) in lines.into_iter().enumerate() {
+        commands
+            .spawn_bundle(create_pre_game_text(&fonts, text, 100.0 + 40.0 * i as f32))
+            .insert(GameText);
+    }
+}
+
 pub fn clear_game_text(
     mut commands: Commands,
     query: Query<Entity, With<GameText>>
@@ -199,15 +358,15 @@ fn state_changed(
 }
 
 fn selected_actor_changed(
-    query_actor: Query<(Entity, &actor::Actor), With<actor::Selected>>,
+    query_actor: Query<(Entity, &actor::Actor, &stamina::Stamina), With<actor::Selected>>,
     mut query_text: Query<&mut Text, With<SelectedText>>,
     ball_possession: Res<ball::BallPossession>
 ) {
     let q_result = query_actor.single();
     let msg = if q_result.is_ok() {
-        let (entity, actor) = q_result.unwrap();
+        let (entity, actor, stamina) = q_result.unwrap();
         let has_ball = ball_possession.has_actor_ball(entity);
-        format!("Selected actor: {:?}, state: {:?}, has ball: {}", entity, actor.act_action, has_ball)
+        format!("Selected actor: {:?}, state: {:?}, has ball: {}, stamina: {:.0}%", entity, actor.act_action, has_ball, stamina.0 / stamina::MAX_STAMINA * 100.0)
     } else {
         format!("No actor selected")
     };
@@ -216,6 +375,25 @@ fn selected_actor_changed(
     }
 }
 
+fn tackle_resolved(
+    mut events: EventReader<actor::ActorEvents>,
+    mut query_text: Query<&mut Text, With<TackleText>>,
+) {
+    for event in events.iter() {
+        let msg = match event {
+            actor::ActorEvents::CleanSteal { tackler_entity, carrier_entity } => format!("Last tackle: {:?} stole the ball from {:?}", tackler_entity, carrier_entity),
+            actor::ActorEvents::Fumble { tackler_entity, carrier_entity, bounce: _ } => format!("Last tackle: {:?} made {:?} fumble", tackler_entity, carrier_entity),
+            actor::ActorEvents::BrokenTackle { tackler_entity, carrier_entity } => format!("Last tackle: {:?} broke tackle of {:?}", carrier_entity, tackler_entity),
+            actor::ActorEvents::BothDown { tackler_entity, carrier_entity } => format!("Last tackle: {:?} and {:?} both down", tackler_entity, carrier_entity),
+            actor::ActorEvents::Dodged { tackler_entity, dodger_entity } => format!("Last tackle: {:?} dodged {:?}", dodger_entity, tackler_entity),
+            _ => continue
+        };
+        if let Ok(mut text) = query_text.single_mut() {
+            update_text(&mut text, msg);
+        }
+    }
+}
+
 fn score_changed(
     matchup: Res<matchup::Matchup>,
     mut query_text: Query<&mut Text, With<ScoreText>>,
@@ -227,12 +405,61 @@ fn score_changed(
     }
 }
 
+fn match_progress_changed(
+    progress: Res<rules::MatchProgress>,
+    ruleset: Res<rules::Ruleset>,
+    mut query_text: Query<&mut Text, With<MatchProgressText>>,
+) {
+    if progress.is_changed() {
+        if let Ok(mut text) = query_text.single_mut() {
+            update_text(&mut text, get_match_progress_text(&progress, &ruleset));
+        }
+    }
+}
+
+fn set_left(style: &mut Mut<Style>, x: f32) {
+    //style is touched only when it moves, so the ui isn't laid out again every frame
+    if style.position.left != Val::Px(x) {
+        style.position.left = Val::Px(x);
+    }
+}
+
+fn keep_ui_in_window(
+    windows: Res<Windows>,
+    mut query: QuerySet<(
+        Query<(&Text, &mut Style), With<GameText>>,
+        Query<(&Text, &mut Style), With<ScoreText>>,
+        Query<(&ButtonAction, &mut Style)>,
+    )>,
+) {
+    let window_width = match windows.get_primary() {
+        Some(window) => window.width(),
+        None => return
+    };
+    for (text, mut style) in query.q0_mut().iter_mut() {
+        let x = get_pre_game_text_x(window_width, &text.sections[0].value);
+        set_left(&mut style, x);
+    }
+    for (text, mut style) in query.q1_mut().iter_mut() {
+        let x = get_score_text_x(window_width, &text.sections[0].value);
+        set_left(&mut style, x);
+    }
+    for (button_action, mut style) in query.q2_mut().iter_mut() {
+        if let ButtonAction::Play = button_action {
+            set_left(&mut style, get_play_button_x(window_width));
+        }
+    }
+}
+
 pub fn ui_changes_listeners() -> SystemSet {
     SystemSet::new()
+        .with_system(match_progress_changed.system())
         .with_system(control_mode_changed.system())
         .with_system(state_changed.system())
         .with_system(selected_actor_changed.system())
+        .with_system(tackle_resolved.system())
         .with_system(score_changed.system())
+        .with_system(keep_ui_in_window.system())
 }
 
 pub fn create_button_bundles(
@@ -308,7 +535,7 @@ pub fn spawn_buttons(
     commands.entity(e_b_run).insert(ButtonGroup(CONTROL_BUTTON_GROUP));
     commands.entity(e_b_run).insert(ButtonAction::Run);
 
-    let (button_play, text_play) = create_button_bundles(40.0, 20.0, utils::WIN_W/2.0 - 20.0, 0.0, "Play".to_owned(), &fonts, &button_materials);
+    let (button_play, text_play) = create_button_bundles(PLAY_BUTTON_WIDTH, 20.0, get_play_button_x(utils::WIN_W), 0.0, "Play".to_owned(), &fonts, &button_materials);
     let e_b_play = create_button_entity(&mut commands, button_play, text_play, ButtonStates::Disabled);
     commands.entity(e_b_play).insert(ButtonAction::Play);
 }
@@ -374,6 +601,7 @@ pub fn handle_button_events(
     mut query_buttons: Query<(Entity, &mut RRButton, &ButtonGroup)>,
     mut control_mode: ResMut<actor::CurrentControlMode>,
     mut app_state: ResMut<State<states::AppState>>,
+    planning_hold: Res<states::PlanningHold>,
 ) {
     for ev in events_r.iter() {
         match ev {
@@ -383,10 +611,10 @@ pub fn handle_button_events(
                         control_mode.0 = actor::ControlMode::Run;
                     },
                     ButtonAction::Throw => {
-                        control_mode.0 = actor::ControlMode::Throw;
+                        control_mode.0 = actor::ControlMode::Throw(passing::PassType::Lob);
                     },
                     ButtonAction::Play => {
-                        app_state.set(states::AppState::Play).unwrap();
+                        states::finish_planning(&mut app_state, &planning_hold);
                     },
                 };
 
@@ -433,4 +661,78 @@ pub fn enable_buttons(
     for mut button in query.iter_mut() {
         button.state = ButtonStates::Normal;
     }
-}
\ No newline at end of file
+}
+
+impl Plugin for UiPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .add_event::<ButtonEvent>()
+            .add_system_set(ui_changes_listeners())
+            .add_system(button_state_changed.system())
+            .add_system(button_interactions.system())
+            .add_system(handle_button_events.system())
+            .add_system_set(
+                SystemSet::on_enter(states::AppState::Introduction)
+                    .with_system(spawn_score_text.system())
+                    .with_system(spawn_match_progress_text.system())
+                    .with_system(add_pre_game_text.system())
+                    .with_system(spawn_debug_ui.system())
+                    .with_system(spawn_buttons.system()
+                        .label(UiLabel::SpawnButtons)
+                    )
+            )
+            .add_system_set(
+                SystemSet::on_exit(states::AppState::Introduction)
+                    .with_system(clear_game_text.system())
+            )
+            .add_system_set(
+                SystemSet::on_enter(states::AppState::Scored)
+                    .with_system(add_score_text.system())
+            )
+            .add_system_set(
+                SystemSet::on_exit(states::AppState::Scored)
+                    .with_system(clear_game_text.system())
+            )
+            .add_system_set(
+                SystemSet::on_enter(states::AppState::HalfTime)
+                    .with_system(add_half_time_text.system())
+            )
+            .add_system_set(
+                SystemSet::on_exit(states::AppState::HalfTime)
+                    .with_system(clear_game_text.system())
+            )
+            .add_system_set(
+                SystemSet::on_enter(states::AppState::MatchOver)
+                    .with_system(add_match_over_text.system())
+            )
+            .add_system_set(
+                SystemSet::on_enter(states::AppState::HandOver)
+                    .with_system(add_hand_over_text.system())
+                    .with_system(disable_buttons.system())
+            )
+            .add_system_set(
+                SystemSet::on_exit(states::AppState::HandOver)
+                    .with_system(clear_game_text.system())
+                    .with_system(enable_buttons.system())
+            )
+            .add_system_set(
+                SystemSet::on_enter(states::AppState::WaitingForOrders)
+                    .with_system(add_waiting_for_orders_text.system())
+                    .with_system(disable_buttons.system())
+            )
+            .add_system_set(
+                SystemSet::on_exit(states::AppState::WaitingForOrders)
+                    .with_system(clear_game_text.system())
+            )
+            .add_system_set(
+                SystemSet::on_enter(states::AppState::Plan)
+                    .with_system(enable_buttons.system())
+                    .with_system(add_round_report_text.system())
+            )
+            .add_system_set(
+                SystemSet::on_exit(states::AppState::Plan)
+                    .with_system(disable_buttons.system())
+                    .with_system(clear_game_text.system())
+            );
+    }
+}
@@ -1,6 +1,7 @@
 use bevy::prelude::*;
 use bevy_rapier2d::na::Vector2;
 
+//initial size of the window, it can be resized later
 pub const WIN_W: f32 = 800.0;
 pub const WIN_H: f32 = 600.0;
 pub const SPRITE_SIZE: f32 = 32.0;
@@ -9,10 +10,12 @@ pub const TRUE_SPRITE_SIZE: f32 = SPRITE_SIZE * SCALE;
 pub const ACTOR_SPRITE_SIZE_W_PADDING: f32 = 48.0;
 pub const PLAYING_FIELD_Z: f32 = 2.0;
 
-pub fn transform_pos_window_to_screen(window_pos: bevy::prelude::Vec2) -> bevy::prelude::Vec2 {
-    Vec2::new(window_pos.x - WIN_W / 2.0, window_pos.y - WIN_H / 2.0)
+//window position starts in its bottom left corner, camera looks at the middle of it and scale is world units per pixel
+pub fn transform_pos_window_to_world(window_pos: Vec2, window_size: Vec2, camera_pos: Vec2, camera_scale: f32) -> Vec2 {
+    camera_pos + (window_pos - window_size / 2.0) * camera_scale
 }
 
+//point and square are both in world coordinates, so it works the same at any zoom
 pub fn is_point_in_square(point: &bevy::prelude::Vec2, rect_origin: &bevy::prelude::Vec3, rect_half_size: f32) -> bool {
     point.x > (rect_origin.x - rect_half_size) &&
         point.x < (rect_origin.x + rect_half_size) &&
@@ -0,0 +1,170 @@
+use bevy::prelude::*;
+use bevy_rapier2d::{
+    physics::RigidBodyHandleComponent,
+    rapier::{
+        dynamics::{RigidBodySet},
+    }
+};
+use serde::{Deserialize, Serialize};
+use super::{actor, ball, collision, physics, states};
+
+//actors wade through mud and get pushed on by boost pads
+const MUD_SPEED_FACTOR: f32 = 0.6;
+const BOOST_SPEED_FACTOR: f32 = 1.4;
+//mud stops loose ball and fallen actors quickly, on ice they slide far
+const MUD_DAMPING_FACTOR: f32 = 3.0;
+const ICE_DAMPING_FACTOR: f32 = 0.2;
+
+pub struct ZonePlugin;
+
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ZoneLabel {
+    HandleEvents,
+}
+
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum ZoneKind {
+    Mud,
+    Ice,
+    Boost,
+    //knocks low ball back, high ball flies over it
+    Deflector,
+}
+impl ZoneKind {
+    pub fn get_speed_factor(&self) -> f32 {
+        match self {
+            ZoneKind::Mud => MUD_SPEED_FACTOR,
+            ZoneKind::Boost => BOOST_SPEED_FACTOR,
+            _ => 1.0,
+        }
+    }
+    pub fn get_damping_factor(&self) -> f32 {
+        match self {
+            ZoneKind::Mud => MUD_DAMPING_FACTOR,
+            ZoneKind::Ice => ICE_DAMPING_FACTOR,
+            _ => 1.0,
+        }
+    }
+}
+
+//index is the order of the zone in arena layout, it is the same for every spawn of the arena
+pub struct Zone {
+    pub index: usize,
+    pub kind: ZoneKind,
+}
+
+//zones the actor or ball is in, sorted by index so overlapping zones always add up the same way
+#[derive(Clone, Debug, Default)]
+pub struct Surface {
+    zones: Vec<(usize, ZoneKind)>,
+}
+impl Surface {
+    pub fn new() -> Self {
+        Self {
+            zones: vec![],
+        }
+    }
+    pub fn get_zones(&self) -> Vec<usize> {
+        self.zones.iter().map(|(index, _kind)| *index).collect()
+    }
+    pub fn get_speed_factor(&self) -> f32 {
+        self.zones.iter().map(|(_index, kind)| kind.get_speed_factor()).product()
+    }
+    pub fn get_damping_factor(&self) -> f32 {
+        self.zones.iter().map(|(_index, kind)| kind.get_damping_factor()).product()
+    }
+    //false when it is already inside, e.g. physics reports zones of restored game again
+    pub fn enter(&mut self, index: usize, kind: ZoneKind) -> bool {
+        match self.zones.binary_search_by_key(&index, |(zone_index, _kind)| *zone_index) {
+            Ok(_) => false,
+            Err(position) => {
+                self.zones.insert(position, (index, kind));
+                true
+            }
+        }
+    }
+    pub fn leave(&mut self, index: usize) -> bool {
+        match self.zones.binary_search_by_key(&index, |(zone_index, _kind)| *zone_index) {
+            Ok(position) => {
+                self.zones.remove(position);
+                true
+            },
+            Err(_) => false
+        }
+    }
+}
+
+//leaving a zone matters too, unlike leaving goal posts
+pub enum ZoneEvent {
+    Entered { entity: Entity, zone_entity: Entity },
+    Left { entity: Entity, zone_entity: Entity },
+}
+
+//velocity and damping set by actions and ball events already count with the surface,
+//here they are only rescaled when the surface changes in the middle of them
+pub fn handle_zone_events(
+    mut events: EventReader<ZoneEvent>,
+    mut events_ball: EventWriter<ball::BallEvent>,
+    mut query: Query<(&mut Surface, &Transform, &RigidBodyHandleComponent, Option<&ball::Ball>, Option<&ball::AirTime>)>,
+    query_zones: Query<(&Zone, &Transform)>,
+    mut rigid_body_set: ResMut<RigidBodySet>,
+) {
+    for event in events.iter() {
+        let (entity, zone_entity, is_entered) = match *event {
+            ZoneEvent::Entered { entity, zone_entity } => (entity, zone_entity, true),
+            ZoneEvent::Left { entity, zone_entity } => (entity, zone_entity, false),
+        };
+        let (zone, zone_transform) = match query_zones.get(zone_entity) {
+            Ok(result) => result,
+            Err(_) => continue
+        };
+        //ball could have been picked up or replaced in the meantime
+        let (mut surface, transform, rigid_body_handle, ball, air_time) = match query.get_mut(entity) {
+            Ok(result) => result,
+            Err(_) => continue
+        };
+        let old_speed_factor = surface.get_speed_factor();
+        let old_damping_factor = surface.get_damping_factor();
+        let is_changed = if is_entered { surface.enter(zone.index, zone.kind) } else { surface.leave(zone.index) };
+        if !is_changed {
+            continue;
+        }
+
+        let velocity = physics::get_velocity(rigid_body_handle, &rigid_body_set).unwrap_or(Vec2::ZERO);
+        let linear_damping = physics::get_linear_damping(rigid_body_handle, &rigid_body_set).unwrap_or(0.0);
+        let linear_damping = linear_damping * surface.get_damping_factor() / old_damping_factor;
+        if ball.is_none() {
+            let velocity = velocity * surface.get_speed_factor() / old_speed_factor;
+            physics::set_rb_properties(rigid_body_handle, &mut rigid_body_set, Some(velocity), None, Some(linear_damping));
+            continue;
+        }
+        physics::set_rb_properties(rigid_body_handle, &mut rigid_body_set, None, None, Some(linear_damping));
+
+        if !is_entered || zone.kind != ZoneKind::Deflector || air_time.map_or(false, |air_time| air_time.is_high()) {
+            continue;
+        }
+        let normal = Vec2::from(transform.translation) - Vec2::from(zone_transform.translation);
+        if normal.length_squared() > 0.0 && velocity.dot(normal) < 0.0 {
+            let normal = normal.normalize();
+            physics::set_rb_properties(rigid_body_handle, &mut rigid_body_set, Some(velocity - 2.0 * velocity.dot(normal) * normal), None, None);
+        }
+        if air_time.map_or(false, |air_time| air_time.is_in_flight()) {
+            events_ball.send(ball::BallEvent::Deflect { ball_entity: entity });
+        }
+    }
+}
+
+impl Plugin for ZonePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .add_event::<ZoneEvent>()
+            .add_system_set(
+                SystemSet::on_update(states::AppState::Play)
+                    .with_system(handle_zone_events.system()
+                        .label(ZoneLabel::HandleEvents)
+                        .after(collision::CollisionLabel::HandleCollisionEvents)
+                        .before(actor::ActorLabel::ActionStart)
+                    )
+            );
+    }
+}